@@ -2,12 +2,13 @@
 
 in vec2 uv;
 
-uniform sampler2D textureSampler;
+uniform sampler2D albedo;
+uniform vec4 tint;
 
 out vec4 Color;
 
 void main()
 {
     // Color = vec4(uv.x, uv.y, 1.0, 1.0);
-    Color = texture(textureSampler, uv);
+    Color = texture(albedo, uv) * tint;
 }
\ No newline at end of file
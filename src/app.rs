@@ -0,0 +1,1084 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
+
+use cgmath::vec3;
+use glutin::{
+    dpi::LogicalSize, dpi::Size, event::Event, event::WindowEvent, event_loop::ControlFlow,
+    event_loop::EventLoop, window::WindowBuilder, ContextBuilder,
+};
+use specs::prelude::*;
+
+use crate::{
+    component::{
+        camera::{Camera, MainCamera, RenderLayers},
+        character::CharacterController,
+        fly_camera::FlyCameraController,
+        material::Material,
+        mesh::{Mesh, MeshManager},
+        physics::{Collider, Gravity, Velocity},
+        render_target::RenderTargetManager,
+        transform::{Parent, PreviousTransform, Transform, WorldTransform},
+    },
+    game_loop::{GameLoop, GameLoopSettings, Step},
+    loader::{
+        assets::Handle,
+        hot_reload::HotReloader,
+        meshes::ModelLoader,
+        shaders::ShaderManager,
+        textures::{descriptor::TextureDescriptor, TextureManager},
+        vfs::Vfs,
+        vox::VoxLoader,
+        Error, Loader,
+    },
+    render_functions,
+    resource::{
+        cursor::Cursor,
+        input::{
+            actions::ActionMap,
+            recording::{InputRecording, InputReplay},
+            InputEvent, UserInput,
+        },
+        scene::Scenes,
+        screen::Screen,
+        time::Time,
+        Interpolation, Task,
+    },
+    system::{
+        hierarchy::TransformHierarchySys,
+        interpolation::StorePreviousTransformSys,
+        physics::PhysicsSys,
+        tasks::{SetCameraTasksSys, SetRenderTaskSys},
+    },
+    voxel::world::VoxelWorld,
+    vxl_gl::{self, gl, Gl},
+};
+
+/// Window and gl context the `App` creates
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowSettings {
+    pub title: String,
+    /// Logical size, scaled by the dpi factor of the monitor
+    pub width: f32,
+    pub height: f32,
+    /// Samples per pixel, 0 disables multisampling
+    pub msaa: u16,
+    /// Requested OpenGL core profile version, major and minor
+    pub gl_version: (u8, u8),
+    pub clear_color: cgmath::Vector3<f32>,
+    pub resizable: bool,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        WindowSettings {
+            title: "VXL".to_owned(),
+            width: 1280.0,
+            height: 720.0,
+            msaa: 4,
+            gl_version: (3, 3),
+            clear_color: vec3(0.2, 0.2, 0.2),
+            resizable: true,
+        }
+    }
+}
+
+impl WindowSettings {
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = title.to_owned();
+        self
+    }
+
+    pub fn with_size(mut self, width: f32, height: f32) -> Self {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    pub fn with_msaa(mut self, msaa: u16) -> Self {
+        self.msaa = msaa;
+        self
+    }
+
+    pub fn with_gl_version(mut self, major: u8, minor: u8) -> Self {
+        self.gl_version = (major, minor);
+        self
+    }
+
+    pub fn with_clear_color(mut self, clear_color: cgmath::Vector3<f32>) -> Self {
+        self.clear_color = clear_color;
+        self
+    }
+
+    pub fn with_resizable(mut self, resizable: bool) -> Self {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn get_aspect(&self) -> f32 {
+        self.width / self.height
+    }
+}
+
+/// Loaders that live outside of the `World`. Loaded assets are in the `ShaderManager`,
+/// `TextureManager`, `MeshManager` and `RenderTargetManager` resources, where the
+/// render systems resolve the handles of materials and meshes
+pub struct Assets {
+    pub vfs: Arc<Vfs>,
+    pub models: ModelLoader,
+    pub vox: VoxLoader,
+}
+
+impl Assets {
+    /// Deletes every asset that no entity or material references anymore
+    pub fn unload_unused(&self, gl: &Gl, world: &World) {
+        // Targets hold their color textures, they go first
+        for (name, target) in world
+            .write_resource::<RenderTargetManager>()
+            .unload_unused()
+        {
+            target.drop_buffers(gl);
+            println!("Render target \"{}\" - Unloaded", name);
+        }
+        world.write_resource::<ShaderManager>().unload_unused(gl);
+        world.write_resource::<TextureManager>().unload_unused(gl);
+        for (name, mesh) in world.write_resource::<MeshManager>().unload_unused() {
+            mesh.drop_buffers(gl);
+            println!("Mesh \"{}\" - Unloaded", name);
+        }
+    }
+}
+
+/// Everything a scene needs to spawn its entities
+pub struct SceneContext<'a> {
+    pub gl: &'a Gl,
+    pub world: &'a mut World,
+    pub assets: &'a mut Assets,
+    pub window: &'a WindowSettings,
+}
+
+type SceneFn = Box<dyn Fn(&mut SceneContext)>;
+
+const RECORD_INPUT_FLAG: &str = "--record-input";
+const REPLAY_INPUT_FLAG: &str = "--replay-input";
+
+/// Where `UserInput` gets its events from: the devices, recorded along the way if
+/// asked to, or a replay until it ends
+struct InputDriver {
+    recording: Option<(PathBuf, InputRecording)>,
+    replay: Option<InputReplay>,
+}
+
+impl InputDriver {
+    /// Event from a device, arriving before the step after `tick`
+    fn handle(&mut self, world: &World, tick: u64, event: InputEvent) {
+        if self.replay.is_some() {
+            return;
+        }
+        if let Some((_, recording)) = self.recording.as_mut() {
+            recording.record(tick + 1, event);
+        }
+        world.write_resource::<UserInput>().handle_event(&event);
+    }
+
+    /// Called when the step `tick` is about to run
+    fn before_step(&mut self, world: &World, tick: u64) {
+        if let Some(replay) = self.replay.as_mut() {
+            if replay.is_finished(tick) {
+                // The devices take over from here
+                println!("Input replay finished at step {}", tick - 1);
+                self.replay = None;
+            } else {
+                replay.apply(tick, &mut world.write_resource::<UserInput>());
+            }
+        }
+    }
+
+    fn finish(&mut self, tick_count: u64) {
+        if let Some((path, recording)) = self.recording.as_mut() {
+            recording.set_tick_count(tick_count);
+            match recording.save(&path) {
+                Ok(()) => println!("Input recorded to {}", path.display()),
+                Err(err) => println!("{}: {}", path.display(), err),
+            }
+        }
+    }
+}
+
+/// Shader program registered on the builder, compiled once the gl context exists
+struct ShaderSource {
+    name: String,
+    shaders: Vec<(String, gl::types::GLenum)>,
+}
+
+/// Texture registered on the builder, decoded in the background once the gl context exists
+struct TextureSource {
+    path: String,
+    name: String,
+    descriptor: TextureDescriptor,
+}
+
+/// Builds and runs a game: registers components, systems, assets and scenes,
+/// then opens the window and drives the `GameLoop`.
+///
+/// Systems added with `with_system` run on every fixed step, followed by the
+/// `PhysicsSys` that moves entities with a `Velocity` through the `VoxelWorld` resource.
+/// `with_frame_system` ones run once per rendered frame before the transforms and
+/// render tasks are computed
+pub struct App {
+    window: WindowSettings,
+    loop_settings: GameLoopSettings,
+    vfs_args: Vec<String>,
+    asset_root: Option<PathBuf>,
+    world: World,
+    update_dispatcher: DispatcherBuilder<'static, 'static>,
+    frame_dispatcher: DispatcherBuilder<'static, 'static>,
+    shaders: Vec<ShaderSource>,
+    textures: Vec<TextureSource>,
+    scenes: HashMap<String, SceneFn>,
+    start_scene: Option<String>,
+    hot_reload_interval: Option<Duration>,
+    actions: ActionMap,
+    input_config: Option<String>,
+    record_input: Option<PathBuf>,
+    replay_input: Option<InputRecording>,
+}
+
+impl Default for App {
+    fn default() -> Self {
+        App::new()
+    }
+}
+
+impl App {
+    pub fn new() -> Self {
+        let mut world = World::new();
+        world.register::<Handle<Mesh>>();
+        world.register::<Material>();
+        world.register::<Transform>();
+        world.register::<PreviousTransform>();
+        world.register::<WorldTransform>();
+        world.register::<Parent>();
+        world.register::<Camera>();
+        world.register::<MainCamera>();
+        world.register::<RenderLayers>();
+        world.register::<FlyCameraController>();
+        world.register::<CharacterController>();
+        world.register::<Velocity>();
+        world.register::<Collider>();
+        world.register::<Gravity>();
+        world.insert(MeshManager::new());
+        world.insert(RenderTargetManager::new());
+        world.insert(UserInput::default());
+        world.insert(Scenes::default());
+        world.insert(Cursor::default());
+        world.insert(VoxelWorld::new());
+
+        // Systems of the game only see the transforms after they were remembered
+        let update_dispatcher = DispatcherBuilder::new()
+            .with(StorePreviousTransformSys, "store_previous_transform", &[])
+            .with_barrier();
+
+        App {
+            window: WindowSettings::default(),
+            loop_settings: GameLoopSettings::default(),
+            vfs_args: std::env::args().skip(1).collect(),
+            asset_root: None,
+            world,
+            update_dispatcher,
+            frame_dispatcher: DispatcherBuilder::new(),
+            shaders: vec![],
+            textures: vec![],
+            scenes: HashMap::new(),
+            start_scene: None,
+            hot_reload_interval: Some(Duration::from_millis(500)),
+            actions: ActionMap::new(),
+            input_config: None,
+            record_input: None,
+            replay_input: None,
+        }
+    }
+
+    pub fn with_window(mut self, window: WindowSettings) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn with_game_loop(mut self, loop_settings: GameLoopSettings) -> Self {
+        self.loop_settings = loop_settings;
+        self
+    }
+
+    /// Arguments the asset mounts are read from, see `Vfs::from_env_and_args`, and the
+    /// `--record-input <file>`/`--replay-input <file>` flags. Defaults to the command
+    /// line arguments
+    pub fn with_vfs_args<I: IntoIterator<Item = String>>(mut self, args: I) -> Self {
+        self.vfs_args = args.into_iter().collect();
+        self
+    }
+
+    /// Asset root mounted when neither `VXL_ASSET_ROOT` nor `--asset-root` is given, instead
+    /// of `assets.pak` or `res/` next to the executable
+    pub fn with_asset_root<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.asset_root = Some(path.into());
+        self
+    }
+
+    /// `None` turns hot reloading of shaders and textures off
+    pub fn with_hot_reload(mut self, interval: Option<Duration>) -> Self {
+        self.hot_reload_interval = interval;
+        self
+    }
+
+    /// Default bindings of the game's actions
+    pub fn with_actions(mut self, actions: ActionMap) -> Self {
+        self.actions = actions;
+        self
+    }
+
+    /// Bindings read from `path`, relative to `config/`, replace the defaults of the
+    /// actions they list. A missing or broken file leaves the defaults in place
+    pub fn with_input_config(mut self, path: &str) -> Self {
+        self.input_config = Some(path.to_owned());
+        self
+    }
+
+    /// Records every input event with the fixed step it arrived before, saved to `path`
+    /// when the window is closed
+    pub fn with_input_recording<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.record_input = Some(path.into());
+        self
+    }
+
+    /// Drives `UserInput` from `recording` with the bindings it was made with, the
+    /// devices are ignored until it ends
+    pub fn with_input_replay(mut self, recording: InputRecording) -> Self {
+        self.replay_input = Some(recording);
+        self
+    }
+
+    pub fn with_component<C: Component>(mut self) -> Self
+    where
+        C::Storage: Default,
+    {
+        self.world.register::<C>();
+        self
+    }
+
+    pub fn with_resource<R: Send + Sync + 'static>(mut self, resource: R) -> Self {
+        self.world.insert(resource);
+        self
+    }
+
+    /// Adds a system to the fixed simulation step
+    pub fn with_system<S>(mut self, system: S, name: &str, dependencies: &[&str]) -> Self
+    where
+        S: for<'c> System<'c> + Send + 'static,
+    {
+        self.update_dispatcher.add(system, name, dependencies);
+        self
+    }
+
+    /// Adds a system that runs once per rendered frame
+    pub fn with_frame_system<S>(mut self, system: S, name: &str, dependencies: &[&str]) -> Self
+    where
+        S: for<'c> System<'c> + Send + 'static,
+    {
+        self.frame_dispatcher.add(system, name, dependencies);
+        self
+    }
+
+    /// Shader program compiled from `shaders`, paths relative to `shaders/`.
+    /// It stays loaded for as long as the app runs
+    pub fn with_shader_program(
+        mut self,
+        name: &str,
+        shaders: Vec<(&str, gl::types::GLenum)>,
+    ) -> Self {
+        self.shaders.push(ShaderSource {
+            name: name.to_owned(),
+            shaders: shaders
+                .into_iter()
+                .map(|(path, shader_type)| (path.to_owned(), shader_type))
+                .collect(),
+        });
+        self
+    }
+
+    /// Texture loaded in the background from `path`, relative to `images/`, it stays
+    /// loaded for as long as the app runs. Scenes get its handle with `TextureManager::get_handle`
+    pub fn with_texture(mut self, path: &str, name: &str, descriptor: TextureDescriptor) -> Self {
+        self.textures.push(TextureSource {
+            path: path.to_owned(),
+            name: name.to_owned(),
+            descriptor,
+        });
+        self
+    }
+
+    /// Registers a scene, the first one registered is started unless `with_start_scene`
+    /// picks another. Switch scenes with the `Scenes` resource
+    pub fn with_scene<F>(mut self, name: &str, scene: F) -> Self
+    where
+        F: Fn(&mut SceneContext) + 'static,
+    {
+        if self.start_scene.is_none() {
+            self.start_scene = Some(name.to_owned());
+        }
+        self.scenes.insert(name.to_owned(), Box::new(scene));
+        self
+    }
+
+    pub fn with_start_scene(mut self, name: &str) -> Self {
+        self.start_scene = Some(name.to_owned());
+        self
+    }
+
+    /// The world and fixed step systems without a window or gl context, scenes and frame
+    /// systems are left out. An input replay, from the builder or the flags, is loaded
+    /// into it
+    pub fn into_headless(self) -> HeadlessApp {
+        let App {
+            loop_settings,
+            vfs_args,
+            asset_root,
+            mut world,
+            update_dispatcher,
+            mut actions,
+            input_config,
+            mut replay_input,
+            ..
+        } = self;
+        read_input_flags(&vfs_args, &mut None, &mut replay_input);
+
+        if let Some(path) = input_config {
+            let loaded = Vfs::from_env_and_args(vfs_args, asset_root)
+                .and_then(|vfs| load_action_map(&Arc::new(vfs), &path));
+            match loaded {
+                Ok(config) => actions.merge(config),
+                Err(err) => println!("{}", err),
+            }
+        }
+        world.insert(UserInput::with_actions(actions));
+        world.insert(Interpolation::default());
+
+        // Physics moves by the velocities every other fixed step system has settled on
+        let mut update_dispatcher = update_dispatcher
+            .with_barrier()
+            .with(PhysicsSys, "physics", &[])
+            .build();
+        update_dispatcher.setup(&mut world);
+        let mut hierarchy_dispatcher = DispatcherBuilder::new()
+            .with(TransformHierarchySys, "transform_hierarchy", &[])
+            .build();
+        hierarchy_dispatcher.setup(&mut world);
+
+        let time = Time::new(loop_settings.get_tick());
+        world.insert(time);
+
+        let mut headless = HeadlessApp {
+            world,
+            update_dispatcher,
+            hierarchy_dispatcher,
+            time,
+            replay: None,
+        };
+        if let Some(recording) = replay_input {
+            headless.set_replay(recording);
+        }
+        headless
+    }
+
+    /// Opens the window and runs until it is closed.
+    /// Errors while starting up are printed and make it return
+    pub fn run(self) {
+        let App {
+            mut window,
+            loop_settings,
+            vfs_args,
+            asset_root,
+            mut world,
+            update_dispatcher,
+            frame_dispatcher,
+            shaders,
+            textures,
+            scenes,
+            start_scene,
+            hot_reload_interval,
+            mut actions,
+            input_config,
+            mut record_input,
+            mut replay_input,
+        } = self;
+        read_input_flags(&vfs_args, &mut record_input, &mut replay_input);
+
+        let event_loop = EventLoop::new();
+        let window_builder = WindowBuilder::new()
+            .with_title(window.title.clone())
+            .with_resizable(window.resizable)
+            .with_inner_size(Size::new(LogicalSize::new(window.width, window.height)));
+
+        let windowed_context = ContextBuilder::new()
+            .with_gl_profile(glutin::GlProfile::Core)
+            .with_multisampling(window.msaa)
+            .with_depth_buffer(24)
+            .with_vsync(loop_settings.vsync)
+            .with_gl(glutin::GlRequest::Specific(
+                glutin::Api::OpenGl,
+                window.gl_version,
+            ))
+            .build_windowed(window_builder, &event_loop);
+        let windowed_context = match windowed_context {
+            Ok(windowed_context) => windowed_context,
+            Err(err) => {
+                println!("Failed to create the window: {}", err);
+                return;
+            }
+        };
+
+        let windowed_context = match unsafe { windowed_context.make_current() } {
+            Ok(windowed_context) => windowed_context,
+            Err((_, err)) => {
+                println!("Failed to make the gl context current: {}", err);
+                return;
+            }
+        };
+
+        let mut gl = vxl_gl::load(windowed_context.context());
+        gl.clear_color = window.clear_color;
+        gl.enable_depth_test();
+
+        let size = windowed_context.window().inner_size();
+        gl.set_viewport(0, 0, size.width as i32, size.height as i32);
+        world.insert(Screen::new(size.width, size.height));
+
+        println!(
+            "Pixel format of context is {:?}",
+            windowed_context.get_pixel_format()
+        );
+
+        let vfs = match Vfs::from_env_and_args(vfs_args, asset_root) {
+            Ok(vfs) => Arc::new(vfs),
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        };
+        println!("Asset mounts: {:?}", vfs.describe());
+
+        let mut shader_manager = match ShaderManager::new(&gl, &vfs) {
+            Ok(shader_manager) => shader_manager,
+            Err(err) => {
+                println!("{}", err);
+                return;
+            }
+        };
+        for source in &shaders {
+            let paths = source
+                .shaders
+                .iter()
+                .map(|(path, shader_type)| (path.as_str(), *shader_type))
+                .collect();
+            if let Err(err) = shader_manager.add_shader_program(&gl, &source.name, paths) {
+                println!("{}", err);
+            }
+            // Assets registered on the builder outlive scene changes, failed programs
+            // included so hot reloading can still fix them
+            let handle = shader_manager.reserve(&source.name);
+            shader_manager.set_persistent(&handle, true);
+        }
+
+        let mut texture_manager = TextureManager::new(&gl, &vfs);
+        for source in textures {
+            let handle =
+                texture_manager.queue_texture(&gl, &source.path, &source.name, source.descriptor);
+            texture_manager.set_persistent(&handle, true);
+        }
+
+        let mut load_progress = texture_manager.get_load_progress().clone();
+        world.insert(shader_manager);
+        world.insert(texture_manager);
+        let mut assets = Assets {
+            models: ModelLoader::new(&vfs),
+            vox: VoxLoader::new(&vfs),
+            vfs,
+        };
+
+        if let Some(path) = input_config {
+            match load_action_map(&assets.vfs, &path) {
+                Ok(config) => actions.merge(config),
+                Err(err) => println!("{}", err),
+            }
+        }
+        let replay = replay_input.map(|recording| {
+            actions = recording.get_actions().clone();
+            InputReplay::new(recording)
+        });
+        let mut input_driver = InputDriver {
+            recording: record_input.map(|path| (path, InputRecording::new(actions.clone()))),
+            replay,
+        };
+        world.insert(UserInput::with_actions(actions));
+
+        // Physics moves by the velocities every other fixed step system has settled on
+        let mut update_dispatcher = update_dispatcher
+            .with_barrier()
+            .with(PhysicsSys, "physics", &[])
+            .build();
+        update_dispatcher.setup(&mut world);
+
+        let mut render_dispatcher = frame_dispatcher
+            .with_barrier()
+            .with(TransformHierarchySys, "transform_hierarchy", &[])
+            .with(SetCameraTasksSys, "camera_tasks", &["transform_hierarchy"])
+            .with(SetRenderTaskSys, "render_task", &["transform_hierarchy"])
+            .build();
+        render_dispatcher.setup(&mut world);
+
+        if let Some(name) = start_scene {
+            world.write_resource::<Scenes>().request(&name);
+        }
+        let mut hot_reloader = None;
+
+        let mut game_loop = GameLoop::new(loop_settings);
+        world.insert(*game_loop.get_time());
+
+        let mut rfps = 0;
+        let mut rfps_second = 0;
+        let mut window_focused = true;
+        // Cursor state the window has, `None` when it has to be set again
+        let mut window_cursor = None;
+
+        #[cfg(feature = "gamepad")]
+        let mut gamepads = match crate::resource::input::gamepad::Gamepads::new() {
+            Ok(gamepads) => Some(gamepads),
+            Err(err) => {
+                println!("Gamepads are not available: {}", err);
+                None
+            }
+        };
+
+        event_loop.run(move |event, _, control_flow| {
+            match event {
+                Event::LoopDestroyed => return,
+                Event::WindowEvent { event, .. } => match event {
+                    WindowEvent::Resized(size) => {
+                        windowed_context.resize(size);
+                        gl.set_viewport(0, 0, size.width as i32, size.height as i32);
+                        if size.width > 0 && size.height > 0 {
+                            let scale_factor = windowed_context.window().scale_factor();
+                            let logical = size.to_logical::<f32>(scale_factor);
+                            window.width = logical.width;
+                            window.height = logical.height;
+                        }
+                        world.insert(Screen::new(size.width, size.height));
+                    }
+                    WindowEvent::CloseRequested => {
+                        input_driver.finish(game_loop.get_time().get_tick_count());
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    event => {
+                        if let WindowEvent::Focused(focused) = event {
+                            window_focused = focused;
+                            // The platform lets go of the cursor while the window is inactive
+                            window_cursor = None;
+                        }
+                        if let Some(input) = InputEvent::from_window_event(&event) {
+                            let tick = game_loop.get_time().get_tick_count();
+                            input_driver.handle(&world, tick, input);
+                        }
+                    }
+                },
+                // Raw motion arrives even when the cursor is outside of the window
+                Event::DeviceEvent { event, .. } if window_focused => {
+                    if let Some(input) = InputEvent::from_device_event(&event) {
+                        let tick = game_loop.get_time().get_tick_count();
+                        input_driver.handle(&world, tick, input);
+                    }
+                    return;
+                }
+                // Every pending event was handled, time to update and draw
+                Event::MainEventsCleared => (),
+                _ => return,
+            };
+            if *control_flow == ControlFlow::Exit {
+                world.write_resource::<TextureManager>().drop_samplers(&gl);
+                return;
+            }
+
+            let requested_scene = world.write_resource::<Scenes>().take_request();
+            if let Some(name) = requested_scene {
+                match scenes.get(&name) {
+                    Some(scene) => {
+                        world.delete_all();
+                        world.maintain();
+                        assets.unload_unused(&gl, &world);
+
+                        scene(&mut SceneContext {
+                            gl: &gl,
+                            world: &mut world,
+                            assets: &mut assets,
+                            window: &window,
+                        });
+                        world.maintain();
+                        world.write_resource::<Scenes>().set_current(&name);
+                        println!("Scene \"{}\" - Loaded", name);
+
+                        hot_reloader = hot_reload_interval.map(|interval| {
+                            HotReloader::new(
+                                &world.read_resource::<ShaderManager>(),
+                                &world.read_resource::<TextureManager>(),
+                                interval,
+                            )
+                        });
+                    }
+                    None => println!("Scene \"{}\" is not registered", name),
+                }
+            }
+
+            #[cfg(feature = "gamepad")]
+            if let Some(gamepads) = gamepads.as_mut() {
+                let tick = game_loop.get_time().get_tick_count();
+                for input in gamepads.poll() {
+                    input_driver.handle(&world, tick, input);
+                }
+            }
+
+            {
+                let mut texture_manager = world.write_resource::<TextureManager>();
+                for err in texture_manager.upload_queued(&gl) {
+                    println!("{}", err);
+                }
+                if *texture_manager.get_load_progress() != load_progress {
+                    load_progress = texture_manager.get_load_progress().clone();
+                    println!(
+                        "Loading textures: {}/{} {}",
+                        load_progress.loaded,
+                        load_progress.total,
+                        load_progress.current.as_deref().unwrap_or("")
+                    );
+                }
+            }
+
+            if let Some(hot_reloader) = hot_reloader.as_mut() {
+                hot_reloader.update(
+                    &gl,
+                    &mut world.write_resource::<ShaderManager>(),
+                    &mut world.write_resource::<TextureManager>(),
+                );
+            }
+
+            loop {
+                match game_loop.next_step() {
+                    Step::Tick => {
+                        world.insert(*game_loop.get_time());
+                        input_driver.before_step(&world, game_loop.get_time().get_tick_count());
+                        update_dispatcher.dispatch(&world);
+                        world.maintain();
+                        // Edges stay visible until a step saw them, frames without a
+                        // step don't lose them
+                        world.write_resource::<UserInput>().end_step();
+                    }
+                    Step::Render { alpha } => {
+                        world.insert(*game_loop.get_time());
+                        world.insert(Interpolation::new(alpha));
+                        world.insert(Task::default());
+                        render_dispatcher.dispatch(&world);
+                        world.maintain();
+
+                        gl.clear_screen();
+
+                        let task_res = world.read_resource::<Task>();
+                        render_functions::render_simple(&gl, &task_res);
+
+                        windowed_context.swap_buffers().unwrap();
+
+                        let cursor = *world.read_resource::<Cursor>();
+                        if window_focused && window_cursor != Some(cursor) {
+                            let window = windowed_context.window();
+                            if let Err(err) = window.set_cursor_grab(cursor.is_grabbed()) {
+                                println!("Failed to grab the cursor: {}", err);
+                            }
+                            window.set_cursor_visible(!cursor.is_grabbed());
+                            window_cursor = Some(cursor);
+                        }
+
+                        gl.print_error();
+                        rfps += 1;
+
+                        let second = game_loop.get_time().get_elapsed() as u64;
+                        if second != rfps_second {
+                            println!("RFPS: {}", rfps);
+                            rfps = 0;
+                            rfps_second = second;
+                        }
+
+                        *control_flow = ControlFlow::Poll;
+                        break;
+                    }
+                    Step::Wait(duration) => {
+                        *control_flow =
+                            ControlFlow::WaitUntil(std::time::Instant::now() + duration);
+                        break;
+                    }
+                }
+            }
+        });
+    }
+}
+
+fn load_action_map(vfs: &Arc<Vfs>, path: &str) -> Result<ActionMap, Error> {
+    let text = Loader::new(vfs, "config/").load_as_string(path)?;
+
+    ActionMap::parse(&text).map_err(|error| Error::Config {
+        path: PathBuf::from(format!("config/{}", path)),
+        error,
+    })
+}
+
+/// Builder settings win over the flags
+fn read_input_flags(
+    args: &[String],
+    record_input: &mut Option<PathBuf>,
+    replay_input: &mut Option<InputRecording>,
+) {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == RECORD_INPUT_FLAG && record_input.is_none() {
+            *record_input = args.next().map(PathBuf::from);
+        } else if arg == REPLAY_INPUT_FLAG && replay_input.is_none() {
+            let path = match args.next() {
+                Some(path) => path,
+                None => continue,
+            };
+            match InputRecording::load(path) {
+                Ok(recording) => *replay_input = Some(recording),
+                Err(err) => println!("{}: {}", path, err),
+            }
+        }
+    }
+}
+
+/// `App` without a window: steps the simulation as fast as it is asked to, for
+/// replaying recorded input and gameplay tests. `WorldTransform`s are updated after
+/// every step
+pub struct HeadlessApp {
+    world: World,
+    update_dispatcher: Dispatcher<'static, 'static>,
+    hierarchy_dispatcher: Dispatcher<'static, 'static>,
+    time: Time,
+    replay: Option<InputReplay>,
+}
+
+impl HeadlessApp {
+    pub fn get_world(&self) -> &World {
+        &self.world
+    }
+
+    /// For spawning entities and feeding `InputEvent`s by hand
+    pub fn get_world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn get_time(&self) -> &Time {
+        &self.time
+    }
+
+    /// Replays `recording` from the next step on, with the bindings it was made with
+    pub fn set_replay(&mut self, recording: InputRecording) {
+        let actions = recording.get_actions().clone();
+        self.world
+            .write_resource::<UserInput>()
+            .set_actions(actions);
+        self.replay = Some(InputReplay::new(recording));
+    }
+
+    pub fn is_replay_finished(&self) -> bool {
+        let next_tick = self.time.get_tick_count() + 1;
+        match &self.replay {
+            Some(replay) => replay.is_finished(next_tick),
+            None => true,
+        }
+    }
+
+    /// Runs one fixed step
+    pub fn step(&mut self) {
+        self.time.advance_tick();
+        self.world.insert(self.time);
+        if let Some(replay) = self.replay.as_mut() {
+            let tick = self.time.get_tick_count();
+            replay.apply(tick, &mut self.world.write_resource::<UserInput>());
+        }
+
+        self.update_dispatcher.dispatch(&self.world);
+        self.world.maintain();
+        self.world.write_resource::<UserInput>().end_step();
+
+        self.hierarchy_dispatcher.dispatch(&self.world);
+        self.world.maintain();
+    }
+
+    pub fn run_steps(&mut self, steps: u64) {
+        for _ in 0..steps {
+            self.step();
+        }
+    }
+
+    /// Steps until the replay ended, returns how many steps ran
+    pub fn run_replay(&mut self) -> u64 {
+        let mut steps = 0;
+        while !self.is_replay_finished() {
+            self.step();
+            steps += 1;
+        }
+        steps
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        component::character::CharacterSettings,
+        resource::input::{actions::Binding, keys::Key},
+        system::character::CharacterControllerSys,
+    };
+
+    /// Moves every entity one unit along X per step `walk` is held and one unit up every
+    /// time `jump` is pressed
+    struct WalkSys;
+    impl<'a> System<'a> for WalkSys {
+        type SystemData = (WriteStorage<'a, Transform>, Read<'a, UserInput>);
+
+        fn run(&mut self, (mut transform, input): Self::SystemData) {
+            for transform in (&mut transform).join() {
+                let mut offset = vec3(input.get_action_value("walk"), 0.0, 0.0);
+                if input.is_action_just_pressed("jump") {
+                    offset.y = 1.0;
+                }
+                transform.translate_vector(offset);
+            }
+        }
+    }
+
+    fn replay_once(recording: &InputRecording) -> (u64, u64, cgmath::Vector3<f32>) {
+        let mut headless = App::new()
+            .with_vfs_args(vec![])
+            .with_system(WalkSys, "walk", &[])
+            .with_input_replay(recording.clone())
+            .into_headless();
+        let entity = headless
+            .get_world_mut()
+            .create_entity()
+            .with(Transform::default())
+            .build();
+
+        let steps = headless.run_replay();
+        let world = headless.get_world();
+        let position = world
+            .read_storage::<Transform>()
+            .get(entity)
+            .unwrap()
+            .get_position();
+        (steps, headless.get_time().get_tick_count(), position)
+    }
+
+    #[test]
+    fn headless_replay_is_deterministic() {
+        let recording = InputRecording::parse(
+            "vxl-input 1
+            ticks 10
+            bind walk = D
+            bind jump = Space, PadSouth
+            2 KeyPressed D
+            3 KeyPressed Space
+            4 KeyReleased Space
+            6 KeyReleased D
+            8 GamepadButtonPressed 0 PadSouth",
+        )
+        .unwrap();
+
+        // D is held during steps 2 to 5, jump is pressed before steps 3 and 8
+        let result = replay_once(&recording);
+        assert_eq!(result, (10, 10, vec3(4.0, 2.0, 0.0)));
+        assert_eq!(replay_once(&recording), result);
+    }
+
+    #[test]
+    fn headless_steps_run_physics_on_the_voxel_world() {
+        let mut headless = App::new().with_vfs_args(vec![]).into_headless();
+        let world = headless.get_world_mut();
+        world
+            .write_resource::<VoxelWorld>()
+            .set_block(vec3(0, 0, 0), 1);
+        let entity = world
+            .create_entity()
+            .with(Transform::from_position(vec3(0.5, 3.0, 0.5)))
+            .with(Velocity::default())
+            .with(Collider::new(vec3(0.5, 1.0, 0.5)).with_offset(vec3(0.0, 0.5, 0.0)))
+            .with(Gravity::default())
+            .build();
+
+        headless.run_steps(120);
+        let world = headless.get_world();
+        let position = world
+            .read_storage::<Transform>()
+            .get(entity)
+            .unwrap()
+            .get_position();
+        assert!((position.y - 1.0).abs() < 1e-3, "stopped at {:?}", position);
+        assert!(world
+            .read_storage::<Collider>()
+            .get(entity)
+            .unwrap()
+            .is_grounded());
+        assert_eq!(
+            world
+                .read_storage::<Velocity>()
+                .get(entity)
+                .unwrap()
+                .linear
+                .y,
+            0.0
+        );
+    }
+
+    #[test]
+    fn gravity_replaces_the_fall_of_characters() {
+        let actions = ActionMap::new().with_binding("move_forward", Binding::Key(Key::W));
+        let mut headless = App::new()
+            .with_vfs_args(vec![])
+            .with_actions(actions)
+            .with_system(CharacterControllerSys, "character_controller", &[])
+            .into_headless();
+        let settings = CharacterSettings::default();
+        let mut spawn = |x: f32, gravity: Option<Gravity>| {
+            let mut builder = headless
+                .get_world_mut()
+                .create_entity()
+                .with(Transform::from_position(vec3(x, 10.0, 0.0)))
+                .with(Velocity::default())
+                .with(settings.get_collider())
+                .with(CharacterController::new(settings.clone()));
+            if let Some(gravity) = gravity {
+                builder = builder.with(gravity);
+            }
+            builder.build()
+        };
+        let character = spawn(0.0, None);
+        let with_gravity = spawn(5.0, Some(Gravity::new(vec3(0.0, -5.0, 0.0))));
+        headless
+            .get_world_mut()
+            .write_resource::<UserInput>()
+            .handle_event(&InputEvent::KeyPressed(Key::W));
+
+        headless.step();
+        let delta = headless.get_time().get_fixed_delta();
+        let world = headless.get_world();
+        let velocity = world.read_storage::<Velocity>();
+        let linear = |entity| velocity.get(entity).unwrap().linear;
+        assert!((linear(character).y + settings.gravity * delta).abs() < 1e-5);
+        // Only the `PhysicsSys` applies the `Gravity`, the controller still steers
+        assert!((linear(with_gravity).y + 5.0 * delta).abs() < 1e-5);
+        assert!(linear(with_gravity).z < 0.0);
+        assert_eq!(linear(with_gravity).z, linear(character).z);
+    }
+}
@@ -0,0 +1,87 @@
+//! Packs an asset directory into a single `.pak` archive
+//!
+//! Usage: vxl-pack <input dir> <output file> [--compress]
+
+extern crate vxl_3;
+
+use std::{
+    fs,
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use vxl_3::loader::pak;
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Archive paths always use `/`, whatever the platform separator is
+fn archive_path(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let parts: Option<Vec<&str>> = relative.iter().map(|part| part.to_str()).collect();
+    Some(parts?.join("/"))
+}
+
+fn pack(input: &Path, output: &Path, compress: bool) -> io::Result<()> {
+    let mut files = vec![];
+    collect_files(input, &mut files)?;
+    files.sort();
+
+    let mut writer = pak::PakWriter::new(BufWriter::new(fs::File::create(output)?))?;
+    for file in &files {
+        let path = archive_path(input, file).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not valid utf8", file.display()),
+            )
+        })?;
+        let data = fs::read(file)?;
+        writer.add_file(&path, &data, compress)?;
+        println!("{} ({} bytes)", path, data.len());
+    }
+    writer.finish()?;
+
+    // Read everything back so a broken archive never gets shipped
+    let reader = pak::PakReader::open(output)?;
+    for file in &files {
+        let path = archive_path(input, file).unwrap_or_default();
+        let packed = reader
+            .read(&path)
+            .unwrap_or_else(|| Err(io::Error::new(io::ErrorKind::NotFound, path.clone())))?;
+        if packed != fs::read(file)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} differs after packing", path),
+            ));
+        }
+    }
+
+    println!("Packed {} files into {}", files.len(), output.display());
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let compress = args.iter().any(|arg| arg == "--compress");
+    let paths: Vec<&String> = args.iter().filter(|arg| !arg.starts_with("--")).collect();
+
+    if paths.len() != 2 {
+        eprintln!("Usage: vxl-pack <input dir> <output file> [--compress]");
+        std::process::exit(2);
+    }
+
+    if let Err(err) = pack(Path::new(paths[0]), Path::new(paths[1]), compress) {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
@@ -1,10 +1,84 @@
+use cgmath::prelude::*;
 use specs::prelude::*;
 
+use super::{render_target::RenderTarget, transform::WorldTransform};
+use crate::loader::assets::Handle;
+
+/// Layer every entity without `RenderLayers` is on
+pub const DEFAULT_LAYER: u32 = 1;
+/// Mask of a camera that sees every layer
+pub const ALL_LAYERS: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    /// Vertical field of view in degrees
+    Perspective { fovy: f32 },
+    /// Units of the world visible from the bottom to the top, the width follows the aspect
+    Orthographic { height: f32 },
+}
+
+/// Part of the render target a camera draws to, as fractions of its size from the
+/// bottom left
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport::new(0.0, 0.0, 1.0, 1.0)
+    }
+}
+
+impl Viewport {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Viewport {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// `x`, `y`, `width` and `height` in pixels of a target of `dimensions`
+    pub fn to_pixels(&self, dimensions: cgmath::Vector2<u32>) -> (i32, i32, i32, i32) {
+        let (target_width, target_height) = (dimensions.x as f32, dimensions.y as f32);
+        let x = (self.x * target_width).round() as i32;
+        let y = (self.y * target_height).round() as i32;
+        let right = ((self.x + self.width) * target_width).round() as i32;
+        let top = ((self.y + self.height) * target_height).round() as i32;
+        (x, y, (right - x).max(0), (top - y).max(0))
+    }
+}
+
+/// What a camera wipes in its viewport before drawing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clear {
+    /// Color and depth, with the clear color of the window
+    WindowColor,
+    Color(cgmath::Vector3<f32>),
+    /// Only depth, to draw on top of cameras before it, like an overlay
+    Depth,
+    Nothing,
+}
+
+/// Every camera renders, to the screen or to a `RenderTarget`. Cameras with a target
+/// draw first, so the screen can show their textures in the same frame, then each
+/// group by `order`. The aspect follows the size of the viewport
+#[derive(Debug, Clone, PartialEq)]
 pub struct Camera {
-    fovy: f32,
+    projection: Projection,
     aspect: f32,
     near_plane: f32,
     far_plane: f32,
+    target: Option<Handle<RenderTarget>>,
+    viewport: Viewport,
+    layer_mask: u32,
+    clear: Clear,
+    order: i32,
 }
 impl Component for Camera {
     type Storage = HashMapStorage<Self>;
@@ -13,25 +87,147 @@ impl Component for Camera {
 impl Camera {
     pub fn new(fovy: f32, aspect: f32, near_plane: f32, far_plane: f32) -> Camera {
         Camera {
-            fovy,
+            projection: Projection::Perspective { fovy },
             aspect,
             near_plane,
             far_plane,
+            target: None,
+            viewport: Viewport::default(),
+            layer_mask: ALL_LAYERS,
+            clear: Clear::WindowColor,
+            order: 0,
+        }
+    }
+
+    pub fn orthographic(height: f32, aspect: f32, near_plane: f32, far_plane: f32) -> Camera {
+        Camera {
+            projection: Projection::Orthographic { height },
+            ..Camera::new(0.0, aspect, near_plane, far_plane)
         }
     }
 
+    /// `None` renders to the screen
+    pub fn with_target(mut self, target: Option<Handle<RenderTarget>>) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn with_viewport(mut self, viewport: Viewport) -> Self {
+        self.viewport = viewport;
+        self
+    }
+
+    /// Only entities on one of these layers are drawn, see `RenderLayers`
+    pub fn with_layer_mask(mut self, layer_mask: u32) -> Self {
+        self.layer_mask = layer_mask;
+        self
+    }
+
+    pub fn with_clear(mut self, clear: Clear) -> Self {
+        self.clear = clear;
+        self
+    }
+
+    pub fn with_order(mut self, order: i32) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+    }
+    pub fn get_projection(&self) -> Projection {
+        self.projection
+    }
+
+    /// Width divided by height, ignored while it is not a positive number,
+    /// like for a minimized window
+    pub fn set_aspect(&mut self, aspect: f32) {
+        if aspect > 0.0 && aspect.is_finite() {
+            self.aspect = aspect;
+        }
+    }
+    pub fn get_aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    pub fn set_clip_planes(&mut self, near_plane: f32, far_plane: f32) {
+        self.near_plane = near_plane;
+        self.far_plane = far_plane;
+    }
+    pub fn get_near_plane(&self) -> f32 {
+        self.near_plane
+    }
+    pub fn get_far_plane(&self) -> f32 {
+        self.far_plane
+    }
+
+    pub fn set_target(&mut self, target: Option<Handle<RenderTarget>>) {
+        self.target = target;
+    }
+    pub fn get_target(&self) -> Option<&Handle<RenderTarget>> {
+        self.target.as_ref()
+    }
+
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        self.viewport = viewport;
+    }
+    pub fn get_viewport(&self) -> Viewport {
+        self.viewport
+    }
+
+    pub fn set_layer_mask(&mut self, layer_mask: u32) {
+        self.layer_mask = layer_mask;
+    }
+    pub fn get_layer_mask(&self) -> u32 {
+        self.layer_mask
+    }
+
+    pub fn set_clear(&mut self, clear: Clear) {
+        self.clear = clear;
+    }
+    pub fn get_clear(&self) -> Clear {
+        self.clear
+    }
+
+    pub fn set_order(&mut self, order: i32) {
+        self.order = order;
+    }
+    pub fn get_order(&self) -> i32 {
+        self.order
+    }
+
     pub fn get_projection_matrix(&self) -> cgmath::Matrix4<f32> {
-        let mat = cgmath::perspective(
-            cgmath::Deg(self.fovy),
-            self.aspect,
-            self.near_plane,
-            self.far_plane,
-        );
+        match self.projection {
+            Projection::Perspective { fovy } => cgmath::perspective(
+                cgmath::Deg(fovy),
+                self.aspect,
+                self.near_plane,
+                self.far_plane,
+            ),
+            Projection::Orthographic { height } => {
+                let half_height = height / 2.0;
+                let half_width = half_height * self.aspect;
+                cgmath::ortho(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.near_plane,
+                    self.far_plane,
+                )
+            }
+        }
+    }
 
-        mat
+    /// Volume the camera sees when placed at `transform`
+    pub fn get_frustum(&self, transform: &WorldTransform) -> Frustum {
+        Frustum::from_matrix(self.get_projection_matrix() * transform.get_view_matrix())
     }
 }
 
+/// Camera the player looks through and controls, like with the `FlyCameraController`.
+/// Rendering does not depend on it
 pub struct MainCamera;
 impl Component for MainCamera {
     type Storage = NullStorage<Self>;
@@ -41,3 +237,210 @@ impl Default for MainCamera {
         MainCamera
     }
 }
+
+/// Layers an entity is drawn on, as bits. Cameras draw it if their layer mask
+/// shares a bit with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderLayers {
+    mask: u32,
+}
+impl Component for RenderLayers {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl Default for RenderLayers {
+    fn default() -> Self {
+        RenderLayers::new(DEFAULT_LAYER)
+    }
+}
+
+impl RenderLayers {
+    pub fn new(mask: u32) -> Self {
+        RenderLayers { mask }
+    }
+
+    /// Only on layer `index`, 0 to 31
+    pub fn layer(index: u32) -> Self {
+        RenderLayers::new(1 << index.min(31))
+    }
+
+    pub fn get_mask(&self) -> u32 {
+        self.mask
+    }
+}
+
+/// Plane through the points `p` where `normal.dot(p) + distance == 0`,
+/// `normal` is unit length and points to the inside of the frustum
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Plane {
+    pub normal: cgmath::Vector3<f32>,
+    pub distance: f32,
+}
+
+impl Plane {
+    fn from_row(row: cgmath::Vector4<f32>) -> Plane {
+        let normal = row.truncate();
+        let length = normal.magnitude();
+        Plane {
+            normal: normal / length,
+            distance: row.w / length,
+        }
+    }
+
+    /// Positive on the side `normal` points to
+    pub fn get_signed_distance(&self, point: cgmath::Vector3<f32>) -> f32 {
+        self.normal.dot(point) + self.distance
+    }
+}
+
+/// Six planes bounding what a camera sees, in world space, for culling.
+/// Tests are conservative: objects near a corner outside of the frustum can pass
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Left, right, bottom, top, near, far
+    planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Planes of a projection * view matrix, for OpenGL clip space
+    pub fn from_matrix(matrix: cgmath::Matrix4<f32>) -> Frustum {
+        let row = |index: usize| matrix.row(index);
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+
+        Frustum {
+            planes: [
+                Plane::from_row(w + x),
+                Plane::from_row(w - x),
+                Plane::from_row(w + y),
+                Plane::from_row(w - y),
+                Plane::from_row(w + z),
+                Plane::from_row(w - z),
+            ],
+        }
+    }
+
+    pub fn get_planes(&self) -> &[Plane; 6] {
+        &self.planes
+    }
+
+    pub fn contains_point(&self, point: cgmath::Vector3<f32>) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.get_signed_distance(point) >= 0.0)
+    }
+
+    pub fn intersects_sphere(&self, center: cgmath::Vector3<f32>, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.get_signed_distance(center) >= -radius)
+    }
+
+    /// Axis aligned box from `min` to `max`
+    pub fn intersects_aabb(&self, min: cgmath::Vector3<f32>, max: cgmath::Vector3<f32>) -> bool {
+        self.planes.iter().all(|plane| {
+            // Corner furthest along the normal
+            let corner = cgmath::vec3(
+                if plane.normal.x >= 0.0 { max.x } else { min.x },
+                if plane.normal.y >= 0.0 { max.y } else { min.y },
+                if plane.normal.z >= 0.0 { max.z } else { min.z },
+            );
+            plane.get_signed_distance(corner) >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{vec3, Matrix4, Vector3};
+
+    /// Point on each plane, in `Frustum` order, with the unit normal pointing out of it
+    type Boundaries = [(Vector3<f32>, Vector3<f32>); 6];
+
+    fn check_planes(frustum: &Frustum, boundaries: &Boundaries) {
+        for (index, (point, outward)) in boundaries.iter().enumerate() {
+            let (point, outward) = (*point, *outward);
+            let plane = frustum.get_planes()[index];
+            assert!(
+                (plane.normal + outward).magnitude() < 1e-4,
+                "plane {} points to {:?}",
+                index,
+                plane.normal
+            );
+            assert!(plane.get_signed_distance(point).abs() < 1e-3);
+
+            assert!(
+                frustum.contains_point(point - outward * 0.1),
+                "plane {}",
+                index
+            );
+            assert!(
+                !frustum.contains_point(point + outward * 0.1),
+                "plane {}",
+                index
+            );
+
+            assert!(frustum.intersects_sphere(point + outward * 0.5, 0.6));
+            assert!(!frustum.intersects_sphere(point + outward * 0.5, 0.4));
+
+            let half = vec3(0.2, 0.2, 0.2);
+            assert!(frustum.intersects_aabb(point - half, point + half));
+            let inside = point - outward;
+            assert!(frustum.intersects_aabb(inside - half, inside + half));
+            let outside = point + outward;
+            assert!(
+                !frustum.intersects_aabb(outside - half, outside + half),
+                "plane {}",
+                index
+            );
+        }
+    }
+
+    #[test]
+    fn perspective_frustum_planes() {
+        // 90 degrees, so the side planes are at |x| == -z and |y| == -z
+        let camera = Camera::new(90.0, 1.0, 1.0, 10.0);
+        let frustum = camera.get_frustum(&WorldTransform::default());
+        let side = |x: f32, y: f32| vec3(x, y, 1.0).normalize();
+
+        check_planes(
+            &frustum,
+            &[
+                (vec3(-5.0, 0.0, -5.0), side(-1.0, 0.0)),
+                (vec3(5.0, 0.0, -5.0), side(1.0, 0.0)),
+                (vec3(0.0, -5.0, -5.0), side(0.0, -1.0)),
+                (vec3(0.0, 5.0, -5.0), side(0.0, 1.0)),
+                (vec3(0.0, 0.0, -1.0), vec3(0.0, 0.0, 1.0)),
+                (vec3(0.0, 0.0, -10.0), vec3(0.0, 0.0, -1.0)),
+            ],
+        );
+
+        // Behind the camera
+        assert!(!frustum.contains_point(vec3(0.0, 0.0, 5.0)));
+        assert!(!frustum.intersects_sphere(vec3(0.0, 0.0, 5.0), 1.0));
+    }
+
+    #[test]
+    fn orthographic_frustum_planes_follow_the_camera() {
+        // 8 x 4 box moved 10 along x
+        let camera = Camera::orthographic(4.0, 2.0, 1.0, 10.0);
+        let transform = WorldTransform::new(Matrix4::from_translation(vec3(10.0, 0.0, 0.0)));
+        let frustum = camera.get_frustum(&transform);
+
+        check_planes(
+            &frustum,
+            &[
+                (vec3(6.0, 0.0, -5.0), vec3(-1.0, 0.0, 0.0)),
+                (vec3(14.0, 0.0, -5.0), vec3(1.0, 0.0, 0.0)),
+                (vec3(10.0, -2.0, -5.0), vec3(0.0, -1.0, 0.0)),
+                (vec3(10.0, 2.0, -5.0), vec3(0.0, 1.0, 0.0)),
+                (vec3(10.0, 0.0, -1.0), vec3(0.0, 0.0, 1.0)),
+                (vec3(10.0, 0.0, -10.0), vec3(0.0, 0.0, -1.0)),
+            ],
+        );
+
+        // Wider than the frustum but still crossing it
+        assert!(frustum.intersects_aabb(vec3(0.0, -1.0, -6.0), vec3(20.0, 1.0, -4.0)));
+        assert!(!frustum.contains_point(vec3(0.0, 0.0, -5.0)));
+    }
+}
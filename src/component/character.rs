@@ -0,0 +1,190 @@
+use specs::prelude::*;
+
+use super::physics::Collider;
+
+/// Tuning of a `CharacterController`, distances in blocks and times in seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacterSettings {
+    pub walk_speed: f32,
+    pub sprint_speed: f32,
+    pub crouch_speed: f32,
+    pub swim_speed: f32,
+    pub fly_speed: f32,
+    pub fly_sprint_speed: f32,
+    /// How quickly the speed follows the input on the ground, per second squared
+    pub ground_acceleration: f32,
+    /// Same as `ground_acceleration` while in the air, lower for less air control
+    pub air_acceleration: f32,
+    /// Highest point of a jump above the ground
+    pub jump_height: f32,
+    pub gravity: f32,
+    pub terminal_speed: f32,
+    /// Speed a swimmer rises at holding `jump`, or dives at holding `crouch`
+    pub swim_up_speed: f32,
+    /// Speed a swimmer sinks at without input
+    pub swim_sink_speed: f32,
+    pub width: f32,
+    pub height: f32,
+    pub crouch_height: f32,
+    /// Camera height above the feet
+    pub eye_height: f32,
+    pub crouch_eye_height: f32,
+    /// Highest ledge walked up without jumping
+    pub step_height: f32,
+    /// Degrees per unit of raw mouse motion
+    pub mouse_sensitivity: f32,
+    /// Degrees per second with the look actions fully held
+    pub look_speed: f32,
+    pub max_pitch: f32,
+    /// Grab the cursor on a click and release it on `pause`, like `FlyCameraController`
+    pub grab_cursor: bool,
+}
+
+impl Default for CharacterSettings {
+    fn default() -> Self {
+        CharacterSettings {
+            walk_speed: 4.3,
+            sprint_speed: 5.6,
+            crouch_speed: 1.3,
+            swim_speed: 2.0,
+            fly_speed: 10.0,
+            fly_sprint_speed: 20.0,
+            ground_acceleration: 40.0,
+            air_acceleration: 10.0,
+            jump_height: 1.25,
+            gravity: 25.0,
+            terminal_speed: 50.0,
+            swim_up_speed: 3.0,
+            swim_sink_speed: 1.0,
+            width: 0.6,
+            height: 1.8,
+            crouch_height: 1.5,
+            eye_height: 1.62,
+            crouch_eye_height: 1.27,
+            step_height: 1.0,
+            mouse_sensitivity: 0.1,
+            look_speed: 120.0,
+            max_pitch: 89.0,
+            grab_cursor: true,
+        }
+    }
+}
+
+impl CharacterSettings {
+    /// Speed to reach at the start of a jump
+    pub fn get_jump_speed(&self) -> f32 {
+        (2.0 * self.gravity * self.jump_height).sqrt()
+    }
+
+    /// Standing collider, the position of the entity is at its feet
+    pub fn get_collider(&self) -> Collider {
+        Collider::new(cgmath::vec3(self.width, self.height, self.width))
+            .with_offset(cgmath::vec3(0.0, self.height / 2.0, 0.0))
+            .with_step_height(self.step_height)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MovementMode {
+    /// Walks, jumps and swims with gravity
+    Walking,
+    /// Moves freely in every direction, still stopped by blocks
+    Flying,
+    /// Flies through blocks
+    Noclip,
+}
+
+/// First person movement of a character, see `CharacterControllerSys`. The entity
+/// needs a `Velocity` and a `Collider`. The controller applies its own gravity while
+/// walking, a `Gravity` on the entity replaces it.
+/// The `MainCamera` goes on a child at eye height.
+/// Reads the actions `move_forward`, `move_back`, `move_left`, `move_right`, `jump`,
+/// `crouch`, `sprint`, `toggle_fly`, `toggle_noclip`, `pause` and the `look_*` actions
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharacterController {
+    settings: CharacterSettings,
+    mode: MovementMode,
+    crouching: bool,
+    swimming: bool,
+    /// Degrees, turning the entity around Y
+    yaw: f32,
+    /// Degrees, tilting the camera child, positive looks up
+    pitch: f32,
+}
+
+impl Component for CharacterController {
+    type Storage = HashMapStorage<Self>;
+}
+
+impl Default for CharacterController {
+    fn default() -> Self {
+        CharacterController::new(CharacterSettings::default())
+    }
+}
+
+impl CharacterController {
+    pub fn new(settings: CharacterSettings) -> Self {
+        CharacterController {
+            settings,
+            mode: MovementMode::Walking,
+            crouching: false,
+            swimming: false,
+            yaw: 0.0,
+            pitch: 0.0,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: MovementMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn set_settings(&mut self, settings: CharacterSettings) {
+        self.settings = settings;
+    }
+    pub fn get_settings(&self) -> &CharacterSettings {
+        &self.settings
+    }
+
+    pub fn set_mode(&mut self, mode: MovementMode) {
+        self.mode = mode;
+    }
+    pub fn get_mode(&self) -> MovementMode {
+        self.mode
+    }
+
+    /// Stays true under a ceiling too low to stand up
+    pub fn is_crouching(&self) -> bool {
+        self.crouching
+    }
+
+    pub fn is_swimming(&self) -> bool {
+        self.swimming
+    }
+
+    /// Turns the character to `yaw` and the camera to `pitch`, both in degrees
+    pub fn set_look(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-self.settings.max_pitch, self.settings.max_pitch);
+    }
+    pub fn get_yaw(&self) -> f32 {
+        self.yaw
+    }
+    pub fn get_pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    /// Camera height above the feet for the current pose
+    pub fn get_eye_height(&self) -> f32 {
+        if self.crouching {
+            self.settings.crouch_eye_height
+        } else {
+            self.settings.eye_height
+        }
+    }
+
+    pub(crate) fn set_state(&mut self, crouching: bool, swimming: bool) {
+        self.crouching = crouching;
+        self.swimming = swimming;
+    }
+}
@@ -0,0 +1,66 @@
+use specs::prelude::*;
+
+/// Free flying first person movement for the `MainCamera`, see `FlyCameraSys`.
+/// Reads the actions `move_forward`, `move_back`, `move_left`, `move_right`, `move_up`,
+/// `move_down` and `look_up`, `look_down`, `look_left`, `look_right` for gamepad sticks
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlyCameraController {
+    /// Units per second
+    pub move_speed: f32,
+    /// Degrees per unit of raw mouse motion
+    pub mouse_sensitivity: f32,
+    /// Degrees per second with the look actions fully held
+    pub look_speed: f32,
+    /// Degrees up or down the camera stops at, below 90 so it never flips over
+    pub max_pitch: f32,
+    /// Grab the cursor on a click and release it on `pause`. The mouse only looks
+    /// around while the cursor is grabbed
+    pub grab_cursor: bool,
+}
+
+impl Component for FlyCameraController {
+    type Storage = HashMapStorage<Self>;
+}
+
+impl Default for FlyCameraController {
+    fn default() -> Self {
+        FlyCameraController {
+            move_speed: 5.0,
+            mouse_sensitivity: 0.1,
+            look_speed: 120.0,
+            max_pitch: 89.0,
+            grab_cursor: true,
+        }
+    }
+}
+
+impl FlyCameraController {
+    pub fn new() -> Self {
+        FlyCameraController::default()
+    }
+
+    pub fn with_move_speed(mut self, move_speed: f32) -> Self {
+        self.move_speed = move_speed;
+        self
+    }
+
+    pub fn with_mouse_sensitivity(mut self, mouse_sensitivity: f32) -> Self {
+        self.mouse_sensitivity = mouse_sensitivity;
+        self
+    }
+
+    pub fn with_look_speed(mut self, look_speed: f32) -> Self {
+        self.look_speed = look_speed;
+        self
+    }
+
+    pub fn with_max_pitch(mut self, max_pitch: f32) -> Self {
+        self.max_pitch = max_pitch.clamp(0.0, 89.9);
+        self
+    }
+
+    pub fn with_grab_cursor(mut self, grab_cursor: bool) -> Self {
+        self.grab_cursor = grab_cursor;
+        self
+    }
+}
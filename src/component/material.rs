@@ -1,36 +1,198 @@
+use std::{collections::HashMap, sync::Arc};
+
+use cgmath::{vec4, Vector4};
 use specs::prelude::*;
 
-use crate::{loader::shaders::ShaderManager, loader::textures::texture::Texture, vxl_gl::gl};
+use crate::{
+    loader::assets::Handle,
+    loader::shaders::shader_program::{ShaderProgram, UniformError},
+    loader::shaders::ShaderManager,
+    loader::textures::{sampler::Sampler, texture::Texture},
+    vxl_gl::{gl, ActiveUniform, UniformValue},
+};
+
+/// Used for the `vec4 tint` uniform of shaders when the material sets none,
+/// so they show their textures as is
+pub const DEFAULT_TINT: Vector4<f32> = vec4(1.0, 1.0, 1.0, 1.0);
+
+/// Texture bound to a named sampler uniform of the material's shader
+pub struct TextureSlot {
+    name: &'static str,
+    texture: Handle<Texture>,
+    unit: u32,
+    sampler: Option<gl::types::GLuint>,
+}
+
+impl TextureSlot {
+    pub fn get_name(&self) -> &'static str {
+        self.name
+    }
 
+    pub fn get_texture(&self) -> &Handle<Texture> {
+        &self.texture
+    }
+
+    pub fn get_unit(&self) -> u32 {
+        self.unit
+    }
+
+    pub fn get_sampler(&self) -> Option<gl::types::GLuint> {
+        self.sampler
+    }
+}
+
+/// Holds handles to its program and textures, keeping them loaded while the material exists.
+/// Gl ids are looked up when drawing, so reloaded or late loaded assets show up on their own
 pub struct Material {
-    shader_program_id: gl::types::GLuint,
-    texture_id: Option<gl::types::GLuint>,
+    program: Handle<ShaderProgram>,
+    textures: Vec<TextureSlot>,
+    parameters: HashMap<&'static str, UniformValue>,
+    /// Uniforms of the program version the parameters were last checked against
+    checked_uniforms: Option<Arc<HashMap<String, ActiveUniform>>>,
 }
 impl Component for Material {
     type Storage = DenseVecStorage<Self>;
 }
 
 impl Material {
-    pub fn default(shader_loader: &ShaderManager) -> Material {
-        let program = shader_loader.get_shader_program("default");
-
+    pub fn new(program: Handle<ShaderProgram>) -> Material {
         Material {
-            shader_program_id: program.get_id(),
-            texture_id: None,
+            program,
+            textures: vec![],
+            parameters: HashMap::new(),
+            checked_uniforms: None,
+        }
+    }
+
+    /// Material with the "default" program, drawn with the error program while that is not loaded
+    pub fn default(shader_manager: &mut ShaderManager) -> Material {
+        Material::new(shader_manager.reserve("default"))
+    }
+
+    /// Binds `texture` to the sampler uniform `slot` (e.g. "albedo", "normal").
+    /// Every slot gets its own texture unit, setting an existing slot replaces its texture
+    pub fn set_texture(
+        &mut self,
+        slot: &'static str,
+        texture: Handle<Texture>,
+        sampler: Option<&Sampler>,
+    ) {
+        let unit = match self.textures.iter().position(|s| s.name == slot) {
+            Some(index) => index as u32,
+            None => self.textures.len() as u32,
+        };
+        self.set_parameter(slot, UniformValue::Sampler(unit as i32));
+
+        let texture_slot = TextureSlot {
+            name: slot,
+            texture,
+            unit,
+            sampler: sampler.map(|s| s.get_id()),
+        };
+        match self.textures.get_mut(unit as usize) {
+            Some(existing) => *existing = texture_slot,
+            None => self.textures.push(texture_slot),
         }
     }
 
-    pub fn add_texture(&mut self, texture: &Texture) {
-        self.texture_id = Some(texture.get_id());
+    /// Sets a uniform that will be uploaded every time the material is drawn.
+    /// Parameters the program has no matching uniform for are reported once the program
+    /// is loaded and skipped when drawing, see `check_program`
+    pub fn set_parameter(&mut self, name: &'static str, value: UniformValue) {
+        self.parameters.insert(name, value);
+        self.checked_uniforms = None;
+    }
+
+    /// Parameters `program` has no uniform of the same name and type for
+    pub fn check_parameters(&self, program: &ShaderProgram) -> Vec<UniformError> {
+        self.parameters
+            .iter()
+            .filter_map(|(name, value)| program.check_uniform(name, value).err())
+            .collect()
+    }
+
+    /// Same as `check_parameters`, but only the first time the material is checked
+    /// against this version of `program`. Reloading the program or changing a
+    /// parameter makes it check again
+    pub fn check_program(&mut self, program: &ShaderProgram) -> Vec<UniformError> {
+        let uniforms = program.get_uniforms();
+        if matches!(&self.checked_uniforms, Some(checked) if Arc::ptr_eq(checked, &uniforms)) {
+            return vec![];
+        }
+        self.checked_uniforms = Some(uniforms);
+        self.check_parameters(program)
     }
 }
 
 impl Material {
-    pub fn get_program_id(&self) -> gl::types::GLuint {
-        self.shader_program_id
+    pub fn get_program(&self) -> &Handle<ShaderProgram> {
+        &self.program
+    }
+
+    pub fn get_textures(&self) -> &Vec<TextureSlot> {
+        &self.textures
+    }
+
+    pub fn get_parameters(&self) -> &HashMap<&'static str, UniformValue> {
+        &self.parameters
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::assets::AssetManager;
+
+    fn program() -> ShaderProgram {
+        let uniform = |name: &str, gl_type, location| ActiveUniform {
+            name: name.to_owned(),
+            gl_type,
+            size: 1,
+            location,
+        };
+        ShaderProgram::from_uniforms(vec![
+            uniform("tint", gl::FLOAT_VEC4, 0),
+            uniform("albedo", gl::SAMPLER_2D, 1),
+        ])
+    }
+
+    fn material() -> Material {
+        let mut programs = AssetManager::<ShaderProgram>::new();
+        Material::new(programs.reserve("default"))
+    }
+
+    #[test]
+    fn wrong_parameters_are_reported_once_per_program() {
+        let mut material = material();
+        material.set_parameter("tint", UniformValue::Color(DEFAULT_TINT));
+        material.set_parameter("roughness", UniformValue::Float(0.5));
+        material.set_parameter("albedo", UniformValue::Float(1.0));
+
+        let first = program();
+        let mut errors = material.check_program(&first);
+        errors.sort_by_key(|err| format!("{}", err));
+        match errors.as_slice() {
+            [UniformError::UnknownUniform { name }, UniformError::TypeMismatch { name: other, .. }] =>
+            {
+                assert_eq!(name, "roughness");
+                assert_eq!(other, "albedo");
+            }
+            other => panic!("expected two errors, got {:?}", other),
+        }
+        assert!(material.check_program(&first).is_empty());
+
+        // A reloaded program has a new uniform table and is checked again
+        assert_eq!(material.check_program(&program()).len(), 2);
     }
 
-    pub fn get_texture_id(&self) -> Option<gl::types::GLuint> {
-        self.texture_id
+    #[test]
+    fn changed_parameters_are_checked_again() {
+        let mut material = material();
+        let program = program();
+        assert!(material.check_program(&program).is_empty());
+
+        material.set_parameter("roughness", UniformValue::Float(0.5));
+        assert_eq!(material.check_program(&program).len(), 1);
+        assert!(material.check_program(&program).is_empty());
     }
 }
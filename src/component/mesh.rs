@@ -1,16 +1,19 @@
-use crate::vxl_gl::{gl, Gl};
-use specs::prelude::*;
+use crate::{
+    loader::{assets::AssetManager, meshes::MeshData},
+    vxl_gl::{gl, Gl},
+};
 
-/// Component that creates and holds vao and vbos of the mesh
+/// Meshes shared between entities, which reference them with a `Handle<Mesh>` component
+pub type MeshManager = AssetManager<Mesh>;
+
+/// Creates and holds vao and vbos of the mesh
 pub struct Mesh {
     vao_id: gl::types::GLuint,
     vertex_count: i32,
     attrib_arays: Vec<gl::types::GLuint>,
+    vbo_ids: Vec<gl::types::GLuint>,
     has_uvs: bool,
-}
-
-impl Component for Mesh {
-    type Storage = DenseVecStorage<Self>;
+    has_normals: bool,
 }
 
 impl Mesh {
@@ -18,8 +21,8 @@ impl Mesh {
         let vertex_count = indices.len() as i32;
         let vao_id: gl::types::GLuint = gl.create_vao();
         gl.bind_vao(vao_id);
-        gl.create_index_vbo(indices);
-        gl.create_vertex_vbo(vertices);
+        let index_vbo = gl.create_index_vbo(indices);
+        let vertex_vbo = gl.create_vertex_vbo(vertices);
         gl.unbind_vao();
 
         let attrib_arays: Vec<gl::types::GLuint> = vec![0];
@@ -28,17 +31,48 @@ impl Mesh {
             vao_id,
             vertex_count,
             attrib_arays,
+            vbo_ids: vec![index_vbo, vertex_vbo],
             has_uvs: false,
+            has_normals: false,
+        }
+    }
+
+    /// Uploads imported geometry, uvs and normals are only added when the data has them
+    pub fn from_mesh_data(gl: &Gl, data: &MeshData) -> Mesh {
+        let mut mesh = Mesh::from_data(gl, data.positions.clone(), data.indices.clone());
+        if data.has_uvs() {
+            mesh.add_uvs(gl, data.uvs.clone());
         }
+        if data.has_normals() {
+            mesh.add_normals(gl, data.normals.clone());
+        }
+
+        mesh
     }
 
     pub fn add_uvs(&mut self, gl: &Gl, uvs: Vec<cgmath::Vector2<f32>>) {
         self.has_uvs = true;
         self.attrib_arays.push(1);
         gl.bind_vao(self.vao_id);
-        gl.create_uvs_vbo(uvs);
+        self.vbo_ids.push(gl.create_uvs_vbo(uvs));
         gl.unbind_vao();
     }
+
+    pub fn add_normals(&mut self, gl: &Gl, normals: Vec<cgmath::Vector3<f32>>) {
+        self.has_normals = true;
+        self.attrib_arays.push(2);
+        gl.bind_vao(self.vao_id);
+        self.vbo_ids.push(gl.create_normals_vbo(normals));
+        gl.unbind_vao();
+    }
+
+    /// Deletes the vao and vbos, used when the mesh is unloaded
+    pub fn drop_buffers(&self, gl: &Gl) {
+        for vbo_id in &self.vbo_ids {
+            gl.drop_vbo(*vbo_id);
+        }
+        gl.drop_vao(self.vao_id);
+    }
 }
 
 impl Mesh {
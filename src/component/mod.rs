@@ -1,5 +1,9 @@
 pub mod camera;
+pub mod character;
+pub mod fly_camera;
 pub mod material;
 pub mod mesh;
+pub mod physics;
 pub mod player;
+pub mod render_target;
 pub mod transform;
@@ -0,0 +1,181 @@
+use cgmath::{prelude::*, Vector3};
+use specs::prelude::*;
+
+use crate::physics::{collision::MoveResult, Aabb};
+
+/// Units per second the `PhysicsSys` moves the entity by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Velocity {
+    pub linear: Vector3<f32>,
+}
+
+impl Component for Velocity {
+    type Storage = VecStorage<Self>;
+}
+
+impl Default for Velocity {
+    fn default() -> Self {
+        Velocity {
+            linear: Vector3::zero(),
+        }
+    }
+}
+
+impl Velocity {
+    pub fn new(linear: Vector3<f32>) -> Self {
+        Velocity { linear }
+    }
+}
+
+/// Box around the entity that stops at solid blocks. Also tracks what the box touched
+/// during the last step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Collider {
+    size: Vector3<f32>,
+    offset: Vector3<f32>,
+    step_height: f32,
+    enabled: bool,
+    grounded: bool,
+    blocked: [bool; 3],
+    step: f32,
+}
+
+impl Component for Collider {
+    type Storage = VecStorage<Self>;
+}
+
+impl Collider {
+    /// Box of `size` centered on the position of the entity
+    pub fn new(size: Vector3<f32>) -> Self {
+        Collider {
+            size,
+            offset: Vector3::zero(),
+            step_height: 0.0,
+            enabled: true,
+            grounded: false,
+            blocked: [false; 3],
+            step: 0.0,
+        }
+    }
+
+    /// Center of the box relative to the position of the entity
+    pub fn with_offset(mut self, offset: Vector3<f32>) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Highest ledge the box climbs on its own while on the ground, 1 for a block
+    pub fn with_step_height(mut self, step_height: f32) -> Self {
+        self.step_height = step_height.max(0.0);
+        self
+    }
+
+    /// A disabled box moves through blocks and touches nothing
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn set_size(&mut self, size: Vector3<f32>) {
+        self.size = size;
+    }
+    pub fn get_size(&self) -> Vector3<f32> {
+        self.size
+    }
+
+    pub fn set_offset(&mut self, offset: Vector3<f32>) {
+        self.offset = offset;
+    }
+    pub fn get_offset(&self) -> Vector3<f32> {
+        self.offset
+    }
+
+    pub fn set_step_height(&mut self, step_height: f32) {
+        self.step_height = step_height.max(0.0);
+    }
+    pub fn get_step_height(&self) -> f32 {
+        self.step_height
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The box in world space for an entity at `position`
+    pub fn get_aabb(&self, position: Vector3<f32>) -> Aabb {
+        Aabb::from_center(position + self.offset, self.size)
+    }
+
+    /// Stood on a block after the last step
+    pub fn is_grounded(&self) -> bool {
+        self.grounded
+    }
+
+    /// Axes the last move was cut short on, X, Y and Z
+    pub fn get_blocked(&self) -> [bool; 3] {
+        self.blocked
+    }
+
+    /// Height climbed onto a ledge in the last step
+    pub fn get_step(&self) -> f32 {
+        self.step
+    }
+
+    pub(crate) fn set_contacts(&mut self, result: &MoveResult) {
+        self.grounded = result.grounded;
+        self.blocked = result.blocked;
+        self.step = result.step;
+    }
+}
+
+/// Constant acceleration added to the `Velocity`, down by default
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gravity {
+    /// Units per second squared
+    pub acceleration: Vector3<f32>,
+    /// Fastest speed along the acceleration, in units per second
+    pub terminal_speed: f32,
+}
+
+impl Component for Gravity {
+    type Storage = VecStorage<Self>;
+}
+
+impl Default for Gravity {
+    fn default() -> Self {
+        Gravity {
+            acceleration: cgmath::vec3(0.0, -25.0, 0.0),
+            terminal_speed: 50.0,
+        }
+    }
+}
+
+impl Gravity {
+    pub fn new(acceleration: Vector3<f32>) -> Self {
+        Gravity {
+            acceleration,
+            ..Gravity::default()
+        }
+    }
+
+    pub fn with_terminal_speed(mut self, terminal_speed: f32) -> Self {
+        self.terminal_speed = terminal_speed.max(0.0);
+        self
+    }
+
+    /// Adds `delta` seconds of acceleration to `velocity`, keeping it under the terminal speed
+    pub fn apply(&self, velocity: &mut Vector3<f32>, delta: f32) {
+        *velocity += self.acceleration * delta;
+        if self.acceleration.magnitude2() == 0.0 {
+            return;
+        }
+        let direction = self.acceleration.normalize();
+        let speed = velocity.dot(direction);
+        if speed > self.terminal_speed {
+            *velocity -= direction * (speed - self.terminal_speed);
+        }
+    }
+}
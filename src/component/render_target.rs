@@ -0,0 +1,79 @@
+use crate::{
+    loader::{
+        assets::{AssetManager, Handle},
+        textures::{
+            descriptor::TextureDescriptor, sampler::Filter, sampler::Wrap, texture::Texture,
+            TextureManager,
+        },
+        Error,
+    },
+    vxl_gl::{gl, Gl},
+};
+
+/// Offscreen render targets, cameras draw into them through a `Handle<RenderTarget>`
+pub type RenderTargetManager = AssetManager<RenderTarget>;
+
+/// Framebuffer with a color texture and a depth buffer. The texture lives in the
+/// `TextureManager`, so materials can show what a camera sees
+pub struct RenderTarget {
+    fbo_id: gl::types::GLuint,
+    depth_id: gl::types::GLuint,
+    texture: Handle<Texture>,
+    dimensions: cgmath::Vector2<u32>,
+}
+
+impl RenderTarget {
+    /// The color texture is added to `textures` under `name`
+    pub fn new(
+        gl: &Gl,
+        textures: &mut TextureManager,
+        name: &str,
+        dimensions: cgmath::Vector2<u32>,
+    ) -> Result<RenderTarget, Error> {
+        let descriptor = TextureDescriptor::default()
+            .with_filter(Filter::Linear, Filter::Linear)
+            .with_wrap(Wrap::ClampToEdge, Wrap::ClampToEdge)
+            .with_generate_mips(false);
+        let pixels = vec![0; dimensions.x as usize * dimensions.y as usize * 4];
+        let texture = textures.add_texture_from_data(gl, name, dimensions, &pixels, descriptor)?;
+        let texture_id = textures.get_texture(&texture)?.get_id();
+
+        let fbo_id = gl.create_framebuffer();
+        gl.bind_framebuffer(fbo_id);
+        gl.attach_color_texture(texture_id);
+        let depth_id = gl.attach_depth_renderbuffer(dimensions);
+        let status = gl.get_framebuffer_status();
+        gl.unbind_framebuffer();
+
+        let target = RenderTarget {
+            fbo_id,
+            depth_id,
+            texture,
+            dimensions,
+        };
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            target.drop_buffers(gl);
+            return Err(Error::Gl { code: status });
+        }
+
+        Ok(target)
+    }
+
+    /// The color texture is freed by the `TextureManager` once nothing uses it anymore
+    pub fn drop_buffers(&self, gl: &Gl) {
+        gl.drop_renderbuffer(self.depth_id);
+        gl.drop_framebuffer(self.fbo_id);
+    }
+
+    pub fn get_fbo_id(&self) -> gl::types::GLuint {
+        self.fbo_id
+    }
+
+    pub fn get_texture(&self) -> &Handle<Texture> {
+        &self.texture
+    }
+
+    pub fn get_dimensions(&self) -> cgmath::Vector2<u32> {
+        self.dimensions
+    }
+}
@@ -1,12 +1,13 @@
 use cgmath::prelude::*;
 use specs::prelude::*;
 
-/// Component to track and change position of the Entity in the 3d world
-
+/// Component to track and change position of the Entity in the 3d world.
+/// Relative to the `Parent` entity if it has one, see `WorldTransform`
 #[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Transform {
     position: cgmath::Vector3<f32>,
-    rotation: cgmath::Vector3<f32>,
+    rotation: cgmath::Quaternion<f32>,
     scale: cgmath::Vector3<f32>,
 }
 
@@ -18,7 +19,7 @@ impl Component for Transform {
 impl Transform {
     pub fn from_data(
         position: cgmath::Vector3<f32>,
-        rotation: cgmath::Vector3<f32>,
+        rotation: cgmath::Quaternion<f32>,
         scale: cgmath::Vector3<f32>,
     ) -> Transform {
         Transform {
@@ -31,8 +32,7 @@ impl Transform {
     pub fn from_position(position: cgmath::Vector3<f32>) -> Transform {
         Transform {
             position,
-            rotation: cgmath::vec3(0.0, 0.0, 0.0),
-            scale: cgmath::vec3(1.0, 1.0, 1.0),
+            ..Transform::default()
         }
     }
 }
@@ -46,13 +46,19 @@ impl Transform {
         self.position
     }
 
-    pub fn set_rotation(&mut self, rotation: cgmath::Vector3<f32>) {
-        self.rotation = rotation;
+    pub fn set_rotation(&mut self, rotation: cgmath::Quaternion<f32>) {
+        self.rotation = rotation.normalize();
     }
-    pub fn get_rotation(&self) -> cgmath::Vector3<f32> {
+    pub fn get_rotation(&self) -> cgmath::Quaternion<f32> {
         self.rotation
     }
 
+    /// Sets the rotation from angles in degrees, composed as X * Y * Z
+    pub fn set_rotation_euler(&mut self, x: f32, y: f32, z: f32) {
+        self.rotation = cgmath::Quaternion::one();
+        self.rotate(x, y, z);
+    }
+
     pub fn set_scale(&mut self, scale: cgmath::Vector3<f32>) {
         self.scale = scale;
     }
@@ -67,11 +73,11 @@ impl Transform {
 #[allow(dead_code)]
 impl Transform {
     pub fn translate(&mut self, x: f32, y: f32, z: f32) {
-        self.position = self.position + cgmath::vec3(x, y, z);
+        self.position += cgmath::vec3(x, y, z);
     }
 
     pub fn translate_vector(&mut self, vector: cgmath::Vector3<f32>) {
-        self.position = self.position + vector;
+        self.position += vector;
     }
 
     pub fn translate_x(&mut self, value: f32) {
@@ -85,69 +91,371 @@ impl Transform {
     pub fn translate_z(&mut self, value: f32) {
         self.translate(0.0, 0.0, value);
     }
+
+    /// Moves along the local axes, `vector.z` is backwards like in view space
+    pub fn translate_local(&mut self, vector: cgmath::Vector3<f32>) {
+        self.position += self.rotation * vector;
+    }
 }
 
+/// Rotations take degrees and are applied around the local axes,
+/// `rotate_around` rotates around an axis fixed in the parent space
 #[allow(dead_code)]
 impl Transform {
     pub fn rotate(&mut self, x: f32, y: f32, z: f32) {
-        self.rotation = self.rotation + cgmath::vec3(x, y, z);
+        self.rotate_x(x);
+        self.rotate_y(y);
+        self.rotate_z(z);
     }
 
     pub fn rotate_vector(&mut self, vector: cgmath::Vector3<f32>) {
-        self.rotation = self.rotation + vector;
+        self.rotate(vector.x, vector.y, vector.z);
     }
 
     pub fn rotate_x(&mut self, value: f32) {
-        self.rotate(value, 0.0, 0.0);
+        self.rotate_local(cgmath::Vector3::unit_x(), value);
     }
 
     pub fn rotate_y(&mut self, value: f32) {
-        self.rotate(0.0, value, 0.0);
+        self.rotate_local(cgmath::Vector3::unit_y(), value);
     }
 
     pub fn rotate_z(&mut self, value: f32) {
-        self.rotate(0.0, 0.0, value);
+        self.rotate_local(cgmath::Vector3::unit_z(), value);
+    }
+
+    pub fn rotate_local(&mut self, axis: cgmath::Vector3<f32>, degrees: f32) {
+        let rotation = cgmath::Quaternion::from_axis_angle(axis.normalize(), cgmath::Deg(degrees));
+        self.rotation = (self.rotation * rotation).normalize();
+    }
+
+    pub fn rotate_around(&mut self, axis: cgmath::Vector3<f32>, degrees: f32) {
+        let rotation = cgmath::Quaternion::from_axis_angle(axis.normalize(), cgmath::Deg(degrees));
+        self.rotation = (rotation * self.rotation).normalize();
+    }
+
+    /// Turns `forward()` towards `target`, keeping `right()` level with `up`.
+    /// Does nothing if `target` is the position or lies straight along `up`
+    pub fn look_at(&mut self, target: cgmath::Vector3<f32>, up: cgmath::Vector3<f32>) {
+        self.look_to(target - self.position, up);
+    }
+
+    /// Turns `forward()` into `direction`, see `look_at`
+    pub fn look_to(&mut self, direction: cgmath::Vector3<f32>, up: cgmath::Vector3<f32>) {
+        let forward = direction.normalize();
+        let right = forward.cross(up).normalize();
+        if !forward.is_finite() || !right.is_finite() {
+            return;
+        }
+        let up = right.cross(forward);
+
+        self.rotation =
+            cgmath::Quaternion::from(cgmath::Matrix3::from_cols(right, up, -forward)).normalize();
     }
 }
 
+/// Direction helpers, in the parent space. Forward is -Z like the view space of the cameras
 #[allow(dead_code)]
 impl Transform {
+    pub fn forward(&self) -> cgmath::Vector3<f32> {
+        self.rotation * -cgmath::Vector3::unit_z()
+    }
+
+    pub fn right(&self) -> cgmath::Vector3<f32> {
+        self.rotation * cgmath::Vector3::unit_x()
+    }
+
+    pub fn up(&self) -> cgmath::Vector3<f32> {
+        self.rotation * cgmath::Vector3::unit_y()
+    }
+}
+
+impl Transform {
+    /// Translation * rotation * scale, into the parent space
     pub fn get_transform_matrix(&self) -> cgmath::Matrix4<f32> {
-        let mut transform_mat = cgmath::Matrix4::<f32>::identity();
-        transform_mat = transform_mat * cgmath::Matrix4::<f32>::from_translation(self.position);
-        transform_mat =
-            transform_mat * cgmath::Matrix4::<f32>::from_angle_x(cgmath::Deg(self.rotation.x));
-        transform_mat =
-            transform_mat * cgmath::Matrix4::<f32>::from_angle_y(cgmath::Deg(self.rotation.y));
-        transform_mat =
-            transform_mat * cgmath::Matrix4::<f32>::from_angle_z(cgmath::Deg(self.rotation.z));
-        transform_mat = transform_mat
-            * cgmath::Matrix4::<f32>::from_nonuniform_scale(
-                self.scale.x,
-                self.scale.y,
-                self.scale.z,
-            );
-
-        transform_mat
+        cgmath::Matrix4::from_translation(self.position)
+            * cgmath::Matrix4::from(self.rotation)
+            * cgmath::Matrix4::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
     }
 
+    /// Inverse of the translation and rotation, scale is ignored
     pub fn get_view_matrix(&self) -> cgmath::Matrix4<f32> {
-        let mut view_mat = cgmath::Matrix4::<f32>::identity();
-        view_mat = view_mat * cgmath::Matrix4::<f32>::from_angle_x(cgmath::Deg(self.rotation.x));
-        view_mat = view_mat * cgmath::Matrix4::<f32>::from_angle_y(cgmath::Deg(self.rotation.y));
-        view_mat = view_mat * cgmath::Matrix4::<f32>::from_angle_z(cgmath::Deg(self.rotation.z));
-        view_mat = view_mat * cgmath::Matrix4::<f32>::from_translation(self.position * -1.0);
+        cgmath::Matrix4::from(self.rotation.conjugate())
+            * cgmath::Matrix4::from_translation(-self.position)
+    }
+}
 
-        view_mat
+impl Transform {
+    /// State between `previous` (alpha 0) and `self` (alpha 1), rotations are slerped
+    /// along the shorter arc
+    pub fn interpolate(&self, previous: &Transform, alpha: f32) -> Transform {
+        Transform {
+            position: previous.position.lerp(self.position, alpha),
+            rotation: interpolate_rotation(previous.rotation, self.rotation, alpha),
+            scale: previous.scale.lerp(self.scale, alpha),
+        }
     }
 }
 
+/// `q` and `-q` are the same rotation, the target is flipped to the hemisphere of `from`
+/// so the result does not spin the long way round. Nearly parallel rotations are
+/// nlerped, slerp divides by the sine of their angle
+fn interpolate_rotation(
+    from: cgmath::Quaternion<f32>,
+    to: cgmath::Quaternion<f32>,
+    alpha: f32,
+) -> cgmath::Quaternion<f32> {
+    const PARALLEL: f32 = 0.9995;
+
+    let (to, dot) = match from.dot(to) {
+        dot if dot < 0.0 => (-to, -dot),
+        dot => (to, dot),
+    };
+    let rotation = if dot > PARALLEL {
+        from * (1.0 - alpha) + to * alpha
+    } else {
+        from.slerp(to, alpha)
+    };
+    rotation.normalize()
+}
+
 impl Default for Transform {
     fn default() -> Self {
         Transform {
             position: cgmath::vec3(0.0, 0.0, 0.0),
-            rotation: cgmath::vec3(0.0, 0.0, 0.0),
+            rotation: cgmath::Quaternion::one(),
             scale: cgmath::vec3(1.0, 1.0, 1.0),
         }
     }
 }
+
+/// `Transform` at the start of the current fixed step, written by `StorePreviousTransformSys`.
+/// Rendering interpolates from it to the current `Transform`. Removing it makes the next
+/// frame snap to the current state, e.g. after teleporting
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct PreviousTransform {
+    transform: Transform,
+}
+
+impl Component for PreviousTransform {
+    type Storage = VecStorage<Self>;
+}
+
+impl PreviousTransform {
+    pub fn new(transform: Transform) -> Self {
+        PreviousTransform { transform }
+    }
+
+    pub fn get_transform(&self) -> &Transform {
+        &self.transform
+    }
+}
+
+/// Transform of the entity relative to the world, with the transforms of all its
+/// parents applied. Written by `TransformHierarchySys` for every entity with a `Transform`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldTransform {
+    matrix: cgmath::Matrix4<f32>,
+}
+
+impl Component for WorldTransform {
+    type Storage = VecStorage<Self>;
+}
+
+impl WorldTransform {
+    pub fn new(matrix: cgmath::Matrix4<f32>) -> Self {
+        WorldTransform { matrix }
+    }
+
+    pub fn get_matrix(&self) -> cgmath::Matrix4<f32> {
+        self.matrix
+    }
+
+    pub fn get_position(&self) -> cgmath::Vector3<f32> {
+        self.matrix.w.truncate()
+    }
+
+    pub fn forward(&self) -> cgmath::Vector3<f32> {
+        (-self.matrix.z.truncate()).normalize()
+    }
+
+    pub fn right(&self) -> cgmath::Vector3<f32> {
+        self.matrix.x.truncate().normalize()
+    }
+
+    pub fn up(&self) -> cgmath::Vector3<f32> {
+        self.matrix.y.truncate().normalize()
+    }
+
+    /// Inverse of the world matrix, for cameras
+    pub fn get_view_matrix(&self) -> cgmath::Matrix4<f32> {
+        self.matrix
+            .invert()
+            .unwrap_or_else(cgmath::Matrix4::identity)
+    }
+}
+
+impl Default for WorldTransform {
+    fn default() -> Self {
+        WorldTransform {
+            matrix: cgmath::Matrix4::identity(),
+        }
+    }
+}
+
+/// Makes the `Transform` of the entity relative to another entity
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parent {
+    entity: Entity,
+}
+
+impl Component for Parent {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl Parent {
+    pub fn new(entity: Entity) -> Self {
+        Parent { entity }
+    }
+
+    pub fn get_entity(&self) -> Entity {
+        self.entity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, Quaternion};
+
+    fn transform(position: cgmath::Vector3<f32>, rotation: Quaternion<f32>) -> Transform {
+        Transform::from_data(position, rotation, cgmath::vec3(1.0, 1.0, 1.0))
+    }
+
+    fn assert_same_rotation(a: Quaternion<f32>, b: Quaternion<f32>) {
+        assert!(
+            a.dot(b).abs() > 1.0 - 1e-5,
+            "expected {:?} to equal {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn interpolation_takes_the_shorter_arc() {
+        let previous = transform(
+            cgmath::vec3(0.0, 0.0, 0.0),
+            Quaternion::from_angle_y(Deg(10.0)),
+        );
+        // 350 degrees is stored in the other hemisphere than 10
+        let current = transform(
+            cgmath::vec3(0.0, 0.0, 0.0),
+            Quaternion::from_angle_y(Deg(350.0)),
+        );
+        assert!(previous.get_rotation().dot(current.get_rotation()) < 0.0);
+
+        let halfway = current.interpolate(&previous, 0.5).get_rotation();
+        assert_same_rotation(halfway, Quaternion::one());
+        assert!((halfway.magnitude() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn nearly_parallel_rotations_stay_normalized() {
+        let previous = transform(
+            cgmath::vec3(0.0, 0.0, 0.0),
+            Quaternion::from_angle_x(Deg(30.0)),
+        );
+        let current = transform(
+            cgmath::vec3(0.0, 0.0, 0.0),
+            Quaternion::from_angle_x(Deg(30.01)),
+        );
+
+        for &alpha in &[0.0, 0.25, 0.5, 1.0] {
+            let rotation = current.interpolate(&previous, alpha).get_rotation();
+            assert!(rotation.s.is_finite());
+            assert!((rotation.magnitude() - 1.0).abs() < 1e-5);
+            assert_same_rotation(rotation, Quaternion::from_angle_x(Deg(30.0 + 0.01 * alpha)));
+        }
+    }
+
+    #[test]
+    fn interpolation_ends_at_both_states() {
+        let previous = transform(
+            cgmath::vec3(0.0, 0.0, 0.0),
+            Quaternion::from_angle_z(Deg(0.0)),
+        );
+        let current = transform(
+            cgmath::vec3(2.0, 4.0, 0.0),
+            Quaternion::from_angle_z(Deg(90.0)),
+        );
+
+        assert_eq!(
+            current.interpolate(&previous, 0.0).get_position(),
+            previous.get_position()
+        );
+        assert_eq!(
+            current.interpolate(&previous, 0.5).get_position(),
+            cgmath::vec3(1.0, 2.0, 0.0)
+        );
+        assert_same_rotation(
+            current.interpolate(&previous, 0.5).get_rotation(),
+            Quaternion::from_angle_z(Deg(45.0)),
+        );
+        assert_same_rotation(
+            current.interpolate(&previous, 1.0).get_rotation(),
+            current.get_rotation(),
+        );
+    }
+
+    fn assert_close(a: cgmath::Vector3<f32>, b: cgmath::Vector3<f32>) {
+        assert!(
+            (a - b).magnitude() < 1e-5,
+            "expected {:?} to equal {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn default_directions_follow_the_camera_convention() {
+        let transform = Transform::default();
+
+        assert_close(transform.forward(), -cgmath::Vector3::unit_z());
+        assert_close(transform.right(), cgmath::Vector3::unit_x());
+        assert_close(transform.up(), cgmath::Vector3::unit_y());
+    }
+
+    #[test]
+    fn look_at_turns_forward_towards_the_target() {
+        let mut transform = Transform::from_position(cgmath::vec3(1.0, 2.0, 3.0));
+        transform.look_at(cgmath::vec3(5.0, 2.0, 3.0), cgmath::Vector3::unit_y());
+
+        assert_close(transform.forward(), cgmath::Vector3::unit_x());
+        assert_close(transform.right(), cgmath::Vector3::unit_z());
+        assert_close(transform.up(), cgmath::Vector3::unit_y());
+        assert_eq!(transform.get_position(), cgmath::vec3(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn look_at_keeps_right_level() {
+        let mut transform = Transform::default();
+        transform.look_at(cgmath::vec3(0.0, -1.0, -1.0), cgmath::Vector3::unit_y());
+
+        let down = (0.5f32).sqrt();
+        assert_close(transform.forward(), cgmath::vec3(0.0, -down, -down));
+        assert_close(transform.right(), cgmath::Vector3::unit_x());
+        assert_close(transform.up(), cgmath::vec3(0.0, down, -down));
+    }
+
+    #[test]
+    fn look_at_ignores_degenerate_targets() {
+        let mut transform = Transform::default();
+        transform.rotate_y(30.0);
+        let rotation = transform.get_rotation();
+
+        transform.look_at(transform.get_position(), cgmath::Vector3::unit_y());
+        assert_eq!(transform.get_rotation(), rotation);
+        transform.look_at(cgmath::vec3(0.0, 4.0, 0.0), cgmath::Vector3::unit_y());
+        assert_eq!(transform.get_rotation(), rotation);
+    }
+}
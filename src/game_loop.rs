@@ -0,0 +1,381 @@
+use std::{
+    cell::Cell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crate::resource::time::Time;
+
+/// Source of the current time for the `GameLoop`, relative to any fixed origin
+pub trait Clock {
+    fn now(&self) -> Duration;
+}
+
+/// Wall clock time since the clock was created
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        SystemClock::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Clock that only moves when told to, for running the loop headless and deterministic.
+/// Clones share the same time
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<Duration>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        ManualClock::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+
+    pub fn set(&self, now: Duration) {
+        self.now.set(now);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderRate {
+    /// Render after every batch of fixed steps, as often as the loop gets to run
+    Uncapped,
+    /// Render at most this many frames per second
+    Capped(f32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameLoopSettings {
+    /// Fixed steps per second
+    pub tick_rate: f32,
+    /// Fixed steps run at most before a frame is rendered. Time beyond that is dropped,
+    /// so a slow step slows the simulation down instead of piling up more steps
+    pub max_ticks_per_frame: u32,
+    pub render_rate: RenderRate,
+    /// Only read when the window is created, the driver blocks `swap_buffers` on vblank
+    pub vsync: bool,
+}
+
+impl Default for GameLoopSettings {
+    fn default() -> Self {
+        GameLoopSettings {
+            tick_rate: 60.0,
+            max_ticks_per_frame: 5,
+            render_rate: RenderRate::Capped(120.0),
+            vsync: false,
+        }
+    }
+}
+
+impl GameLoopSettings {
+    pub fn with_tick_rate(mut self, tick_rate: f32) -> Self {
+        self.tick_rate = tick_rate;
+        self
+    }
+
+    pub fn with_max_ticks_per_frame(mut self, max_ticks_per_frame: u32) -> Self {
+        self.max_ticks_per_frame = max_ticks_per_frame.max(1);
+        self
+    }
+
+    pub fn with_render_rate(mut self, render_rate: RenderRate) -> Self {
+        self.render_rate = render_rate;
+        self
+    }
+
+    pub fn with_vsync(mut self, vsync: bool) -> Self {
+        self.vsync = vsync;
+        self
+    }
+
+    /// Length of one fixed step
+    pub fn get_tick(&self) -> Duration {
+        Duration::from_secs_f32(1.0 / self.tick_rate.max(f32::EPSILON))
+    }
+}
+
+/// What the caller of `GameLoop::next_step` has to do next
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Step {
+    /// Run one fixed simulation step
+    Tick,
+    /// Render a frame, interpolating transforms by `alpha`
+    Render { alpha: f32 },
+    /// Nothing is due before this much time passed
+    Wait(Duration),
+}
+
+/// Fixed timestep loop: simulation steps run at `tick_rate` out of an accumulator
+/// of real time, frames are rendered in between at the `render_rate`.
+/// Call `next_step` until it returns `Step::Wait`
+pub struct GameLoop<C: Clock = SystemClock> {
+    clock: C,
+    settings: GameLoopSettings,
+    tick: Duration,
+    render_interval: Option<Duration>,
+    time: Time,
+    accumulator: Duration,
+    last_time: Duration,
+    last_render: Option<Duration>,
+    /// Steps run since the clock was last sampled, `None` until it is sampled again
+    frame_ticks: Option<u32>,
+}
+
+impl GameLoop<SystemClock> {
+    pub fn new(settings: GameLoopSettings) -> Self {
+        GameLoop::with_clock(settings, SystemClock::new())
+    }
+}
+
+impl<C: Clock> GameLoop<C> {
+    pub fn with_clock(settings: GameLoopSettings, clock: C) -> Self {
+        let tick = settings.get_tick();
+        let render_interval = match settings.render_rate {
+            RenderRate::Uncapped => None,
+            RenderRate::Capped(rate) => Some(Duration::from_secs_f32(1.0 / rate.max(f32::EPSILON))),
+        };
+        let last_time = clock.now();
+
+        GameLoop {
+            clock,
+            settings,
+            tick,
+            render_interval,
+            time: Time::new(tick),
+            accumulator: Duration::new(0, 0),
+            last_time,
+            last_render: None,
+            frame_ticks: None,
+        }
+    }
+
+    pub fn next_step(&mut self) -> Step {
+        let now = match self.frame_ticks {
+            Some(_) => self.last_time,
+            None => {
+                let now = self.clock.now();
+                self.accumulator += now.saturating_sub(self.last_time);
+                self.last_time = now;
+                self.frame_ticks = Some(0);
+                now
+            }
+        };
+
+        let frame_ticks = self.frame_ticks.unwrap_or_default();
+        if self.accumulator >= self.tick {
+            if frame_ticks < self.settings.max_ticks_per_frame {
+                self.accumulator -= self.tick;
+                self.frame_ticks = Some(frame_ticks + 1);
+                self.time.advance_tick();
+                return Step::Tick;
+            }
+            // Too far behind, give up on catching up
+            self.accumulator =
+                Duration::from_nanos((self.accumulator.as_nanos() % self.tick.as_nanos()) as u64);
+        }
+        self.frame_ticks = None;
+
+        let since_render = self.last_render.map(|last| now.saturating_sub(last));
+        let wait = match (self.render_interval, since_render) {
+            (Some(interval), Some(since_render)) if since_render < interval => {
+                Some(interval - since_render)
+            }
+            _ => None,
+        };
+
+        match wait {
+            Some(render_wait) => Step::Wait(render_wait.min(self.tick - self.accumulator)),
+            None => {
+                self.time.set_frame(since_render.unwrap_or_default(), now);
+                self.last_render = Some(now);
+                Step::Render {
+                    alpha: self.get_alpha(),
+                }
+            }
+        }
+    }
+
+    /// Part of a fixed step accumulated since the last one ran, at least 0 and below 1
+    pub fn get_alpha(&self) -> f32 {
+        // Close to a full step the ratio would round up to 1 in f32
+        const BELOW_ONE: f32 = 1.0 - f32::EPSILON / 2.0;
+
+        let alpha = self.accumulator.as_nanos() as f64 / self.tick.as_nanos() as f64;
+        (alpha as f32).min(BELOW_ONE)
+    }
+
+    pub fn get_time(&self) -> &Time {
+        &self.time
+    }
+
+    pub fn get_settings(&self) -> &GameLoopSettings {
+        &self.settings
+    }
+
+    pub fn get_clock(&self) -> &C {
+        &self.clock
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(millis: f64) -> Duration {
+        Duration::from_secs_f64(millis / 1000.0)
+    }
+
+    fn manual_loop(settings: GameLoopSettings) -> (GameLoop<ManualClock>, ManualClock) {
+        let clock = ManualClock::new();
+        (GameLoop::with_clock(settings, clock.clone()), clock)
+    }
+
+    /// 8 ticks per second make a step of exactly 125ms
+    fn uncapped() -> GameLoopSettings {
+        GameLoopSettings::default()
+            .with_tick_rate(8.0)
+            .with_render_rate(RenderRate::Uncapped)
+    }
+
+    fn assert_render(step: Step, expected: f32) {
+        match step {
+            Step::Render { alpha } => assert!(
+                (alpha - expected).abs() < 1e-4,
+                "expected alpha {}, got {}",
+                expected,
+                alpha
+            ),
+            other => panic!("expected Render, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn accumulated_time_runs_whole_ticks() {
+        let (mut game_loop, clock) = manual_loop(uncapped());
+        assert_eq!(game_loop.get_settings().get_tick(), millis(125.0));
+
+        clock.advance(millis(3.0 * 125.0 + 10.0));
+        for _ in 0..3 {
+            assert_eq!(game_loop.next_step(), Step::Tick);
+        }
+        assert_render(game_loop.next_step(), 10.0 / 125.0);
+        assert_eq!(game_loop.get_time().get_tick_count(), 3);
+
+        // The remainder carries over into the next frame
+        clock.advance(millis(120.0));
+        assert_eq!(game_loop.next_step(), Step::Tick);
+        assert_render(game_loop.next_step(), 5.0 / 125.0);
+        assert_eq!(game_loop.get_time().get_tick_count(), 4);
+    }
+
+    #[test]
+    fn time_beyond_the_catch_up_cap_is_dropped() {
+        let (mut game_loop, clock) = manual_loop(uncapped().with_max_ticks_per_frame(5));
+
+        clock.advance(millis(10.0 * 125.0 + 30.0));
+        for _ in 0..5 {
+            assert_eq!(game_loop.next_step(), Step::Tick);
+        }
+        assert_render(game_loop.next_step(), 30.0 / 125.0);
+
+        // The five steps that did not fit are gone, not run in the next frame
+        assert_render(game_loop.next_step(), 30.0 / 125.0);
+        assert_eq!(game_loop.get_time().get_tick_count(), 5);
+    }
+
+    #[test]
+    fn alpha_stays_below_one() {
+        let settings = GameLoopSettings::default().with_render_rate(RenderRate::Uncapped);
+        let (mut game_loop, clock) = manual_loop(settings.clone());
+        let tick = game_loop.get_settings().get_tick();
+
+        let mut renders = 0;
+        for frame in 0..1000 {
+            clock.advance(millis(1.0 + (frame % 13) as f64 * 1.7));
+            loop {
+                match game_loop.next_step() {
+                    Step::Tick => (),
+                    Step::Render { alpha } => {
+                        assert!((0.0..1.0).contains(&alpha), "alpha {} out of range", alpha);
+                        renders += 1;
+                        break;
+                    }
+                    Step::Wait(wait) => panic!("uncapped loop waited {:?}", wait),
+                }
+            }
+        }
+        assert_eq!(renders, 1000);
+
+        // One nanosecond short of a step
+        let (mut game_loop, clock) = manual_loop(settings);
+        clock.advance(tick - Duration::from_nanos(1));
+        match game_loop.next_step() {
+            Step::Render { alpha } => assert!(alpha > 0.99 && alpha < 1.0, "alpha {}", alpha),
+            other => panic!("expected Render, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn capped_rendering_waits_for_the_next_frame_or_tick() {
+        // Frames every 62.5ms, steps every 125ms
+        let (mut game_loop, clock) =
+            manual_loop(uncapped().with_render_rate(RenderRate::Capped(16.0)));
+
+        assert_render(game_loop.next_step(), 0.0);
+        assert_eq!(game_loop.next_step(), Step::Wait(millis(62.5)));
+        clock.advance(millis(40.0));
+        assert_eq!(game_loop.next_step(), Step::Wait(millis(22.5)));
+        clock.advance(millis(22.5));
+        assert_render(game_loop.next_step(), 62.5 / 125.0);
+
+        // The next step is due before the next frame
+        clock.advance(millis(60.0));
+        assert_eq!(game_loop.next_step(), Step::Wait(millis(2.5)));
+        clock.advance(millis(2.5));
+        assert_eq!(game_loop.next_step(), Step::Tick);
+        assert_render(game_loop.next_step(), 0.0);
+    }
+
+    #[test]
+    fn tick_waits_are_shorter_than_frame_waits_when_due_first() {
+        // Frames every 250ms, steps every 125ms
+        let (mut game_loop, clock) =
+            manual_loop(uncapped().with_render_rate(RenderRate::Capped(4.0)));
+
+        assert_render(game_loop.next_step(), 0.0);
+        assert_eq!(game_loop.next_step(), Step::Wait(millis(125.0)));
+        clock.advance(millis(125.0));
+        assert_eq!(game_loop.next_step(), Step::Tick);
+        assert_eq!(game_loop.next_step(), Step::Wait(millis(125.0)));
+        clock.advance(millis(125.0));
+        assert_eq!(game_loop.next_step(), Step::Tick);
+        assert_render(game_loop.next_step(), 0.0);
+    }
+}
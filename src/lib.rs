@@ -0,0 +1,18 @@
+extern crate cgmath;
+extern crate glutin;
+extern crate image;
+extern crate rand;
+extern crate specs;
+
+pub mod app;
+pub mod component;
+pub mod game_loop;
+pub mod loader;
+pub mod physics;
+pub mod render_functions;
+pub mod resource;
+pub mod system;
+pub mod voxel;
+pub mod vxl_gl;
+
+pub use app::{App, Assets, HeadlessApp, SceneContext, WindowSettings};
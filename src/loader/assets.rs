@@ -0,0 +1,357 @@
+use std::{collections::HashMap, fmt, hash, marker::PhantomData, sync::Arc};
+
+use specs::prelude::*;
+
+use super::Error;
+
+/// Typed key of an asset inside an `AssetManager`. Every live handle counts as a reference
+/// to the asset, assets nobody holds a handle to can be dropped with `unload_unused`
+pub struct Handle<T> {
+    index: usize,
+    refs: Arc<()>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle {
+            index: self.index,
+            refs: Arc::clone(&self.refs),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.refs, &other.refs)
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> hash::Hash for Handle<T> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Handle({})", self.index)
+    }
+}
+
+/// Entities reference shared assets, like meshes, through handles
+impl<T: Send + Sync + 'static> Component for Handle<T> {
+    type Storage = DenseVecStorage<Self>;
+}
+
+struct Entry<T> {
+    name: String,
+    asset: Option<T>,
+    refs: Arc<()>,
+    persistent: bool,
+}
+
+/// Named assets of one type, addressed by `Handle<T>` or by name.
+/// An entry can exist before its asset is loaded, so names coming from config
+/// or world files can be resolved to handles and loaded lazily
+pub struct AssetManager<T> {
+    entries: Vec<Option<Entry<T>>>,
+    names: HashMap<String, usize>,
+}
+
+impl<T> Default for AssetManager<T> {
+    fn default() -> Self {
+        AssetManager {
+            entries: vec![],
+            names: HashMap::new(),
+        }
+    }
+}
+
+impl<T> AssetManager<T> {
+    pub fn new() -> Self {
+        AssetManager::default()
+    }
+
+    fn entry(&self, handle: &Handle<T>) -> Option<&Entry<T>> {
+        self.entries
+            .get(handle.index)?
+            .as_ref()
+            .filter(|entry| Arc::ptr_eq(&entry.refs, &handle.refs))
+    }
+
+    fn entry_mut(&mut self, handle: &Handle<T>) -> Option<&mut Entry<T>> {
+        self.entries
+            .get_mut(handle.index)?
+            .as_mut()
+            .filter(|entry| Arc::ptr_eq(&entry.refs, &handle.refs))
+    }
+
+    fn handle_at(&self, index: usize) -> Option<Handle<T>> {
+        let entry = self.entries.get(index)?.as_ref()?;
+        Some(Handle {
+            index,
+            refs: Arc::clone(&entry.refs),
+            marker: PhantomData,
+        })
+    }
+
+    /// Handle to the entry called `name`, creating an empty entry if there is none
+    pub fn reserve(&mut self, name: &str) -> Handle<T> {
+        if let Some(handle) = self.get_handle(name) {
+            return handle;
+        }
+
+        let entry = Entry {
+            name: name.to_owned(),
+            asset: None,
+            refs: Arc::new(()),
+            persistent: false,
+        };
+        let index = match self.entries.iter().position(Option::is_none) {
+            Some(index) => {
+                self.entries[index] = Some(entry);
+                index
+            }
+            None => {
+                self.entries.push(Some(entry));
+                self.entries.len() - 1
+            }
+        };
+        self.names.insert(name.to_owned(), index);
+
+        Handle {
+            index,
+            refs: Arc::clone(&self.entries[index].as_ref().unwrap().refs),
+            marker: PhantomData,
+        }
+    }
+
+    /// Stores `asset` in the entry of `handle`, returning the asset it replaces
+    pub fn set(&mut self, handle: &Handle<T>, asset: T) -> Option<T> {
+        self.entry_mut(handle)?.asset.replace(asset)
+    }
+
+    /// Handle to the asset called `name`, calling `load` first if it is not loaded yet
+    pub fn get_or_load<F>(&mut self, name: &str, load: F) -> Result<Handle<T>, Error>
+    where
+        F: FnOnce(&str) -> Result<T, Error>,
+    {
+        let handle = self.reserve(name);
+        if !self.is_loaded(&handle) {
+            let asset = load(name)?;
+            self.set(&handle, asset);
+        }
+
+        Ok(handle)
+    }
+
+    pub fn get_handle(&self, name: &str) -> Option<Handle<T>> {
+        self.handle_at(*self.names.get(name)?)
+    }
+
+    pub fn get(&self, handle: &Handle<T>) -> Option<&T> {
+        self.entry(handle)?.asset.as_ref()
+    }
+
+    pub fn get_mut(&mut self, handle: &Handle<T>) -> Option<&mut T> {
+        self.entry_mut(handle)?.asset.as_mut()
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&T> {
+        self.entries
+            .get(*self.names.get(name)?)?
+            .as_ref()?
+            .asset
+            .as_ref()
+    }
+
+    pub fn get_name(&self, handle: &Handle<T>) -> Option<&str> {
+        self.entry(handle).map(|entry| entry.name.as_str())
+    }
+
+    pub fn is_loaded(&self, handle: &Handle<T>) -> bool {
+        self.get(handle).is_some()
+    }
+
+    /// Number of live handles to the asset, `handle` included
+    pub fn get_ref_count(&self, handle: &Handle<T>) -> usize {
+        self.entry(handle)
+            .map_or(0, |entry| Arc::strong_count(&entry.refs) - 1)
+    }
+
+    /// Persistent assets survive `unload_unused` even when nobody holds a handle to them
+    pub fn set_persistent(&mut self, handle: &Handle<T>, persistent: bool) {
+        if let Some(entry) = self.entry_mut(handle) {
+            entry.persistent = persistent;
+        }
+    }
+
+    /// Takes the asset out of its entry, the entry stays so the asset can be loaded again
+    pub fn unload(&mut self, handle: &Handle<T>) -> Option<T> {
+        self.entry_mut(handle)?.asset.take()
+    }
+
+    /// Removes every non persistent entry without handles and returns the assets they held,
+    /// so resources living outside of the manager (gl objects) can be freed by the caller
+    pub fn unload_unused(&mut self) -> Vec<(String, T)> {
+        let mut unloaded = vec![];
+        for slot in self.entries.iter_mut() {
+            let unused = match slot {
+                Some(entry) => !entry.persistent && Arc::strong_count(&entry.refs) == 1,
+                None => false,
+            };
+            if !unused {
+                continue;
+            }
+
+            if let Some(entry) = slot.take() {
+                self.names.remove(&entry.name);
+                if let Some(asset) = entry.asset {
+                    unloaded.push((entry.name, asset));
+                }
+            }
+        }
+
+        unloaded
+    }
+
+    /// Handles and names of every entry, loaded or not
+    pub fn handles(&self) -> Vec<(Handle<T>, &str)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                let entry = entry.as_ref()?;
+                Some((self.handle_at(index)?, entry.name.as_str()))
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_entries_load_later() {
+        let mut assets = AssetManager::new();
+        let handle = assets.reserve("stone");
+
+        assert!(!assets.is_loaded(&handle));
+        assert_eq!(assets.get(&handle), None);
+        assert_eq!(assets.reserve("stone"), handle);
+        assert_eq!(assets.len(), 1);
+
+        assert_eq!(assets.set(&handle, 1), None);
+        assert!(assets.is_loaded(&handle));
+        assert_eq!(assets.get_by_name("stone"), Some(&1));
+        assert_eq!(assets.set(&handle, 2), Some(1));
+        assert_eq!(assets.get(&handle), Some(&2));
+        assert_eq!(assets.get_name(&handle), Some("stone"));
+    }
+
+    #[test]
+    fn get_or_load_only_loads_once() {
+        let mut assets = AssetManager::new();
+        let handle = assets.get_or_load("stone", |_| Ok(1)).unwrap();
+        let again = assets
+            .get_or_load("stone", |_| panic!("stone is already loaded"))
+            .unwrap();
+
+        assert_eq!(handle, again);
+        assert_eq!(assets.get(&again), Some(&1));
+    }
+
+    #[test]
+    fn unload_unused_keeps_assets_with_handles() {
+        let mut assets = AssetManager::new();
+        let kept = assets.reserve("kept");
+        assets.set(&kept, 1);
+        let dropped = assets.reserve("dropped");
+        assets.set(&dropped, 2);
+        let copy = dropped.clone();
+        assert_eq!(assets.get_ref_count(&dropped), 2);
+
+        drop(dropped);
+        assert!(assets.unload_unused().is_empty());
+
+        drop(copy);
+        assert_eq!(assets.unload_unused(), vec![("dropped".to_owned(), 2)]);
+        assert_eq!(assets.get_handle("dropped"), None);
+        assert_eq!(assets.get(&kept), Some(&1));
+        assert_eq!(assets.len(), 1);
+    }
+
+    #[test]
+    fn unload_unused_keeps_persistent_assets() {
+        let mut assets = AssetManager::new();
+        let handle = assets.reserve("missing");
+        assets.set(&handle, 1);
+        assets.set_persistent(&handle, true);
+        drop(handle);
+
+        assert!(assets.unload_unused().is_empty());
+        let handle = assets.get_handle("missing").unwrap();
+        assert_eq!(assets.get(&handle), Some(&1));
+
+        assets.set_persistent(&handle, false);
+        drop(handle);
+        assert_eq!(assets.unload_unused(), vec![("missing".to_owned(), 1)]);
+    }
+
+    #[test]
+    fn unloaded_entries_can_be_loaded_again() {
+        let mut assets = AssetManager::new();
+        let handle = assets.reserve("stone");
+        assets.set(&handle, 1);
+
+        assert_eq!(assets.unload(&handle), Some(1));
+        assert!(!assets.is_loaded(&handle));
+        assert_eq!(assets.get_handle("stone"), Some(handle.clone()));
+
+        assets.set(&handle, 2);
+        assert_eq!(assets.get(&handle), Some(&2));
+    }
+
+    #[test]
+    fn freed_slots_are_reused_without_reviving_old_handles() {
+        let mut assets = AssetManager::new();
+        let old = assets.reserve("old");
+        assets.set(&old, 1);
+        let stale = Handle::<i32> {
+            index: old.get_index(),
+            refs: Arc::new(()),
+            marker: PhantomData,
+        };
+        let index = old.get_index();
+        drop(old);
+        assets.unload_unused();
+
+        let new = assets.reserve("new");
+        assets.set(&new, 2);
+
+        assert_eq!(new.get_index(), index);
+        assert_eq!(assets.get(&new), Some(&2));
+        assert_eq!(assets.get(&stale), None);
+        assert_eq!(assets.get_name(&stale), None);
+        assert_eq!(assets.get_ref_count(&stale), 0);
+    }
+}
@@ -0,0 +1,163 @@
+use std::{
+    sync::{mpsc, Arc, Mutex},
+    thread,
+};
+
+use super::Error;
+
+/// How far the queued assets got, for loading screens
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadProgress {
+    pub loaded: usize,
+    pub total: usize,
+    /// Asset a worker started on most recently, `None` once everything is loaded
+    pub current: Option<String>,
+}
+
+impl LoadProgress {
+    pub fn is_done(&self) -> bool {
+        self.loaded >= self.total
+    }
+
+    /// Loaded part between 0 and 1, 1 when nothing was queued
+    pub fn get_fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            self.loaded as f32 / self.total as f32
+        }
+    }
+}
+
+type Job<T> = (String, Box<dyn FnOnce() -> Result<T, Error> + Send>);
+
+enum Message<T> {
+    Started(String),
+    Finished(String, Result<T, Error>),
+}
+
+/// Runs loading jobs (file reads, decoding) on worker threads.
+/// Results are collected on the main thread with `poll`, where gl objects can be created
+pub struct BackgroundLoader<T> {
+    jobs: Option<mpsc::Sender<Job<T>>>,
+    /// Only `poll` reads it, the lock makes the loader `Sync` so its owner can be a resource
+    results: Mutex<mpsc::Receiver<Message<T>>>,
+    results_sender: mpsc::Sender<Message<T>>,
+    workers: Vec<thread::JoinHandle<()>>,
+    worker_count: usize,
+    progress: LoadProgress,
+}
+
+impl<T: Send + 'static> BackgroundLoader<T> {
+    /// Workers are only spawned once the first job is queued
+    pub fn new(worker_count: usize) -> Self {
+        let (results_sender, results) = mpsc::channel();
+        BackgroundLoader {
+            jobs: None,
+            results: Mutex::new(results),
+            results_sender,
+            workers: vec![],
+            worker_count: worker_count.max(1),
+            progress: LoadProgress::default(),
+        }
+    }
+
+    fn start_workers(&mut self) -> &mpsc::Sender<Job<T>> {
+        if self.jobs.is_none() {
+            let (sender, receiver) = mpsc::channel::<Job<T>>();
+            let receiver = Arc::new(Mutex::new(receiver));
+
+            for index in 0..self.worker_count {
+                let receiver = Arc::clone(&receiver);
+                let results = self.results_sender.clone();
+                let worker = thread::Builder::new()
+                    .name(format!("asset-loader-{}", index))
+                    .spawn(move || run_worker(&receiver, &results))
+                    .expect("failed to spawn asset loader thread");
+                self.workers.push(worker);
+            }
+            self.jobs = Some(sender);
+        }
+
+        self.jobs.as_ref().unwrap()
+    }
+
+    pub fn queue<F>(&mut self, name: &str, job: F)
+    where
+        F: FnOnce() -> Result<T, Error> + Send + 'static,
+    {
+        self.progress.total += 1;
+        let job: Job<T> = (name.to_owned(), Box::new(job));
+        if let Err(mpsc::SendError((name, job))) = self.start_workers().send(job) {
+            // Workers are gone, loading on the main thread still beats losing the asset
+            let result = job();
+            let _ = self.results_sender.send(Message::Finished(name, result));
+        }
+    }
+
+    /// Results finished since the previous poll, in the order they finished
+    pub fn poll(&mut self) -> Vec<(String, Result<T, Error>)> {
+        let mut finished = vec![];
+        let results = match self.results.get_mut() {
+            Ok(results) => results,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        while let Ok(message) = results.try_recv() {
+            match message {
+                Message::Started(name) => self.progress.current = Some(name),
+                Message::Finished(name, result) => {
+                    self.progress.loaded += 1;
+                    finished.push((name, result));
+                }
+            }
+        }
+        if self.progress.is_done() {
+            self.progress.current = None;
+        }
+
+        finished
+    }
+
+    pub fn get_progress(&self) -> &LoadProgress {
+        &self.progress
+    }
+}
+
+fn run_worker<T>(jobs: &Mutex<mpsc::Receiver<Job<T>>>, results: &mpsc::Sender<Message<T>>) {
+    loop {
+        let job = match jobs.lock() {
+            Ok(jobs) => jobs.recv(),
+            Err(poisoned) => poisoned.into_inner().recv(),
+        };
+        let (name, job) = match job {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+
+        if results.send(Message::Started(name.clone())).is_err() {
+            return;
+        }
+        let result = job();
+        if results.send(Message::Finished(name, result)).is_err() {
+            return;
+        }
+    }
+}
+
+impl<T> Drop for BackgroundLoader<T> {
+    fn drop(&mut self) {
+        // Closing the job channel lets the workers run out of work and exit
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Worker count used by the asset managers, leaving a core for the main thread
+pub fn default_worker_count() -> usize {
+    thread::available_parallelism()
+        .map(|count| count.get().saturating_sub(1))
+        .unwrap_or(1)
+        .clamp(1, 4)
+}
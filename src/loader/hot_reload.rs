@@ -0,0 +1,155 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use super::{
+    assets::Handle,
+    shaders::{shader_program::ShaderProgram, ShaderManager},
+    textures::TextureManager,
+};
+use crate::vxl_gl::Gl;
+
+/// Polls modification times of a set of files
+#[derive(Default)]
+pub struct FileWatcher {
+    files: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+impl FileWatcher {
+    pub fn watch(&mut self, path: PathBuf) {
+        let modified = modified_time(&path);
+        self.files.entry(path).or_insert(modified);
+    }
+
+    /// Watches exactly `paths`. Files that were already watched keep their modification time
+    pub fn set_watched(&mut self, paths: Vec<PathBuf>) {
+        self.files.retain(|path, _| paths.contains(path));
+        for path in paths {
+            self.watch(path);
+        }
+    }
+
+    pub fn unwatch_all(&mut self) {
+        self.files.clear();
+    }
+
+    /// Returns files whose modification time changed since the previous poll
+    pub fn poll(&mut self) -> Vec<PathBuf> {
+        let mut changed = vec![];
+        for (path, last_modified) in self.files.iter_mut() {
+            let modified = modified_time(path);
+            if modified != *last_modified {
+                *last_modified = modified;
+                changed.push(path.clone());
+            }
+        }
+
+        changed
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Recompiles shader programs and re-uploads textures when their files change on disk.
+/// A program that fails to compile keeps its previous version. Materials pick up the new
+/// program by themselves and their parameters are checked against it when next drawn
+pub struct HotReloader {
+    shader_files: FileWatcher,
+    texture_files: FileWatcher,
+    interval: Duration,
+    last_poll: Instant,
+}
+
+impl HotReloader {
+    pub fn new(
+        shader_manager: &ShaderManager,
+        texture_manager: &TextureManager,
+        interval: Duration,
+    ) -> Self {
+        let mut reloader = HotReloader {
+            shader_files: FileWatcher::default(),
+            texture_files: FileWatcher::default(),
+            interval,
+            last_poll: Instant::now(),
+        };
+        reloader.watch_shaders(shader_manager);
+        reloader.watch_textures(texture_manager);
+
+        reloader
+    }
+
+    fn watch_shaders(&mut self, shader_manager: &ShaderManager) {
+        self.shader_files.unwatch_all();
+        for (_, path) in shader_manager.get_watched_files() {
+            self.shader_files.watch(path);
+        }
+    }
+
+    fn watch_textures(&mut self, texture_manager: &TextureManager) {
+        let paths = texture_manager
+            .get_watched_files()
+            .into_iter()
+            .map(|(_, path)| path)
+            .collect();
+        self.texture_files.set_watched(paths);
+    }
+
+    pub fn update(
+        &mut self,
+        gl: &Gl,
+        shader_manager: &mut ShaderManager,
+        texture_manager: &mut TextureManager,
+    ) {
+        if self.last_poll.elapsed() < self.interval {
+            return;
+        }
+        self.last_poll = Instant::now();
+
+        let changed_shaders = self.shader_files.poll();
+        if !changed_shaders.is_empty() {
+            self.reload_shaders(gl, &changed_shaders, shader_manager);
+        }
+
+        // Scenes load and unload textures at any time
+        self.watch_textures(texture_manager);
+        let changed_textures = self.texture_files.poll();
+        for (handle, path) in texture_manager.get_watched_files() {
+            if !changed_textures.contains(&path) {
+                continue;
+            }
+
+            let name = texture_manager.get_name(&handle).to_owned();
+            match texture_manager.reload_texture(gl, &handle) {
+                Ok(()) => println!("Texture \"{}\" - Reloaded", name),
+                Err(err) => println!("Texture \"{}\" - Reload failed: {}", name, err),
+            }
+        }
+    }
+
+    fn reload_shaders(&mut self, gl: &Gl, changed: &[PathBuf], shader_manager: &mut ShaderManager) {
+        let mut programs: Vec<Handle<ShaderProgram>> = vec![];
+        for (handle, path) in shader_manager.get_watched_files() {
+            if changed.contains(&path) && !programs.contains(&handle) {
+                programs.push(handle);
+            }
+        }
+
+        for handle in programs {
+            let name = shader_manager.get_name(&handle).to_owned();
+            match shader_manager.reload_program(gl, &handle) {
+                Ok(true) => println!("Shader \"{}\" - Reloaded", name),
+                Ok(false) => (),
+                Err(err) => println!("{}", err),
+            }
+        }
+
+        // Includes may have been added or removed
+        self.watch_shaders(shader_manager);
+    }
+}
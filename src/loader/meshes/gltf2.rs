@@ -0,0 +1,366 @@
+//! glTF 2.0, both `.gltf` json with external or embedded buffers and binary `.glb`.
+//! Meshes are instanced through the default scene with node transforms baked into
+//! the vertices. Materials, skins, animations and morph targets are reported as unsupported
+
+use cgmath::{Matrix, Matrix4, SquareMatrix};
+
+use super::{MeshData, MeshError, Model};
+use crate::loader::Error;
+
+pub fn parse<F>(bytes: &[u8], read_file: F) -> Result<Model, MeshError>
+where
+    F: Fn(&str) -> Result<Vec<u8>, Error>,
+{
+    let gltf::Gltf { document, blob } = gltf::Gltf::from_slice(bytes).map_err(MeshError::Gltf)?;
+
+    let mut buffers: Vec<Vec<u8>> = Vec::with_capacity(document.buffers().len());
+    for buffer in document.buffers() {
+        let data = match buffer.source() {
+            gltf::buffer::Source::Bin => blob.clone().ok_or_else(|| {
+                MeshError::Unsupported("glb buffer without a binary chunk".to_owned())
+            })?,
+            gltf::buffer::Source::Uri(uri) => read_uri(uri, &read_file)?,
+        };
+        if data.len() < buffer.length() {
+            return Err(MeshError::Unsupported(format!(
+                "buffer {} is shorter than declared",
+                buffer.index()
+            )));
+        }
+        buffers.push(data);
+    }
+
+    let mut model = Model::default();
+    if document.skins().next().is_some() {
+        model.skip("gltf skins".to_owned());
+    }
+    if document.animations().next().is_some() {
+        model.skip("gltf animations".to_owned());
+    }
+    if document.materials().next().is_some() {
+        model.skip("gltf materials".to_owned());
+    }
+    for extension in document.extensions_used() {
+        model.skip(format!("gltf extension {}", extension));
+    }
+
+    match document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+    {
+        Some(scene) => {
+            for node in scene.nodes() {
+                add_node(&mut model, &buffers, &node, Matrix4::identity());
+            }
+        }
+        // Without scenes the meshes are not placed anywhere, take them as they are
+        None => {
+            for mesh in document.meshes() {
+                add_mesh(&mut model, &buffers, &mesh, Matrix4::identity());
+            }
+        }
+    }
+
+    Ok(model)
+}
+
+fn read_uri<F>(uri: &str, read_file: &F) -> Result<Vec<u8>, MeshError>
+where
+    F: Fn(&str) -> Result<Vec<u8>, Error>,
+{
+    if let Some(data) = uri.strip_prefix("data:") {
+        return match data.split_once(";base64,") {
+            Some((_, encoded)) => base64::decode(encoded).map_err(|error| {
+                MeshError::Unsupported(format!("malformed base64 data uri: {}", error))
+            }),
+            None => Err(MeshError::Unsupported(
+                "data uri without base64 encoding".to_owned(),
+            )),
+        };
+    }
+    if uri.contains("://") {
+        return Err(MeshError::Unsupported(format!("remote buffer {}", uri)));
+    }
+
+    let path = decode_uri(uri)?;
+    read_file(&path).map_err(|error| MeshError::Unsupported(format!("buffer {}: {}", path, error)))
+}
+
+/// Resolves the `%XX` escapes of a relative uri, as used for file names with spaces.
+/// Images are referenced the same way, but they are not loaded
+fn decode_uri(uri: &str) -> Result<String, MeshError> {
+    let malformed = || MeshError::Unsupported(format!("malformed uri {}", uri));
+
+    let mut bytes = Vec::with_capacity(uri.len());
+    let mut rest = uri.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte != b'%' {
+            bytes.push(byte);
+            rest = tail;
+            continue;
+        }
+        let hex = tail
+            .get(..2)
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .ok_or_else(malformed)?;
+        let hex = std::str::from_utf8(hex).map_err(|_| malformed())?;
+        bytes.push(u8::from_str_radix(hex, 16).map_err(|_| malformed())?);
+        rest = &tail[2..];
+    }
+
+    String::from_utf8(bytes).map_err(|_| malformed())
+}
+
+fn add_node(model: &mut Model, buffers: &[Vec<u8>], node: &gltf::Node, parent: Matrix4<f32>) {
+    let transform = parent * Matrix4::from(node.transform().matrix());
+    if let Some(mesh) = node.mesh() {
+        add_mesh(model, buffers, &mesh, transform);
+    }
+    for child in node.children() {
+        add_node(model, buffers, &child, transform);
+    }
+}
+
+/// Merges every primitive of `mesh` into one `MeshData`, transformed by `transform`
+fn add_mesh(model: &mut Model, buffers: &[Vec<u8>], mesh: &gltf::Mesh, transform: Matrix4<f32>) {
+    let name = mesh
+        .name()
+        .map(str::to_owned)
+        .unwrap_or_else(|| format!("mesh{}", mesh.index()));
+    let normal_matrix = transform
+        .invert()
+        .map(|inverse| inverse.transpose())
+        .unwrap_or(transform);
+
+    let mut data = MeshData::new(&name);
+    let mut has_uvs = false;
+    let mut has_normals = false;
+
+    for primitive in mesh.primitives() {
+        if primitive.morph_targets().next().is_some() {
+            model.skip("gltf morph targets".to_owned());
+        }
+
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+        let positions: Vec<cgmath::Vector3<f32>> = match reader.read_positions() {
+            Some(positions) => positions
+                .map(|[x, y, z]| (transform * cgmath::vec4(x, y, z, 1.0)).truncate())
+                .collect(),
+            None => {
+                model.skip("gltf primitives without readable positions".to_owned());
+                continue;
+            }
+        };
+        let count = positions.len();
+
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..count as u32).collect(),
+        };
+        let indices = match triangulate(primitive.mode(), indices) {
+            Some(indices) => indices,
+            None => {
+                model.skip(format!("gltf primitive mode {:?}", primitive.mode()));
+                continue;
+            }
+        };
+        if indices.iter().any(|index| *index as usize >= count) {
+            model.skip("gltf primitives with out of range indices".to_owned());
+            continue;
+        }
+
+        let uvs: Vec<cgmath::Vector2<f32>> = match reader.read_tex_coords(0) {
+            Some(uvs) => {
+                has_uvs = true;
+                uvs.into_f32().map(|[u, v]| cgmath::vec2(u, v)).collect()
+            }
+            None => vec![cgmath::vec2(0.0, 0.0); count],
+        };
+        let normals: Vec<cgmath::Vector3<f32>> = match reader.read_normals() {
+            Some(normals) => {
+                has_normals = true;
+                normals
+                    .map(|[x, y, z]| {
+                        let normal = (normal_matrix * cgmath::vec4(x, y, z, 0.0)).truncate();
+                        cgmath::InnerSpace::normalize(normal)
+                    })
+                    .collect()
+            }
+            None => vec![cgmath::vec3(0.0, 0.0, 0.0); count],
+        };
+        if uvs.len() != count || normals.len() != count {
+            model.skip("gltf attributes with mismatched counts".to_owned());
+            continue;
+        }
+
+        let offset = data.positions.len() as u32;
+        data.positions.extend(positions);
+        data.uvs.extend(uvs);
+        data.normals.extend(normals);
+        data.indices
+            .extend(indices.into_iter().map(|index| index + offset));
+    }
+
+    if !has_uvs {
+        data.uvs.clear();
+    }
+    if !has_normals {
+        data.normals.clear();
+    }
+    model.push_mesh(data);
+}
+
+/// Converts strips and fans into a triangle list, `None` for points and lines
+fn triangulate(mode: gltf::mesh::Mode, indices: Vec<u32>) -> Option<Vec<u32>> {
+    use gltf::mesh::Mode;
+
+    match mode {
+        Mode::Triangles => Some(indices),
+        Mode::TriangleStrip => Some(
+            (0..indices.len().saturating_sub(2))
+                .flat_map(|i| {
+                    // Every other triangle of a strip is wound the other way
+                    if i % 2 == 0 {
+                        [indices[i], indices[i + 1], indices[i + 2]]
+                    } else {
+                        [indices[i + 1], indices[i], indices[i + 2]]
+                    }
+                })
+                .collect(),
+        ),
+        Mode::TriangleFan => Some(
+            (1..indices.len().saturating_sub(1))
+                .flat_map(|i| [indices[0], indices[i], indices[i + 1]])
+                .collect(),
+        ),
+        Mode::Points | Mode::Lines | Mode::LineLoop | Mode::LineStrip => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Triangle at (0,0,0), (1,0,0) and (0,1,0) with u16 indices
+    const TRIANGLE_BASE64: &str = "AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAAAAABAAIA";
+
+    /// One triangle mesh named "tri", placed by a node at every x offset in `nodes`
+    fn document(buffer_uri: &str, nodes: &[f32]) -> String {
+        let node_list: Vec<String> = nodes
+            .iter()
+            .map(|x| format!(r#"{{"mesh": 0, "translation": [{}, 0, 0]}}"#, x))
+            .collect();
+        let node_indices: Vec<String> = (0..nodes.len()).map(|i| i.to_string()).collect();
+        format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "scene": 0,
+                "scenes": [{{"nodes": [{}]}}],
+                "nodes": [{}],
+                "meshes": [{{"name": "tri", "primitives": [{{"attributes": {{"POSITION": 0}}, "indices": 1}}]}}],
+                "accessors": [
+                    {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                      "min": [0, 0, 0], "max": [1, 1, 0]}},
+                    {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}}
+                ],
+                "bufferViews": [
+                    {{"buffer": 0, "byteOffset": 0, "byteLength": 36}},
+                    {{"buffer": 0, "byteOffset": 36, "byteLength": 6}}
+                ],
+                "buffers": [{{"byteLength": 42, "uri": "{}"}}]
+            }}"#,
+            node_indices.join(", "),
+            node_list.join(", "),
+            buffer_uri
+        )
+    }
+
+    fn no_files(path: &str) -> Result<Vec<u8>, Error> {
+        Err(Error::MissingAsset {
+            name: path.to_owned(),
+        })
+    }
+
+    #[test]
+    fn reads_embedded_buffers_and_bakes_node_transforms() {
+        let uri = format!("data:application/octet-stream;base64,{}", TRIANGLE_BASE64);
+        let model = parse(document(&uri, &[2.0]).as_bytes(), no_files).unwrap();
+
+        assert_eq!(model.meshes.len(), 1);
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.name, "tri");
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+        assert_eq!(
+            mesh.positions,
+            vec![
+                cgmath::vec3(2.0, 0.0, 0.0),
+                cgmath::vec3(3.0, 0.0, 0.0),
+                cgmath::vec3(2.0, 1.0, 0.0),
+            ]
+        );
+        assert!(!mesh.has_uvs() && !mesh.has_normals());
+    }
+
+    #[test]
+    fn instances_of_a_mesh_get_unique_names() {
+        let uri = format!("data:application/octet-stream;base64,{}", TRIANGLE_BASE64);
+        let model = parse(document(&uri, &[0.0, 1.0, 2.0, 3.0]).as_bytes(), no_files).unwrap();
+
+        let names: Vec<&str> = model.meshes.iter().map(|mesh| mesh.name.as_str()).collect();
+        assert_eq!(names, vec!["tri", "tri.1", "tri.2", "tri.3"]);
+        assert_eq!(model.meshes[3].positions[0], cgmath::vec3(3.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn decodes_buffer_uris() {
+        let bytes = base64::decode(TRIANGLE_BASE64).unwrap();
+        let read_file = |path: &str| match path {
+            "tri angle é.bin" => Ok(bytes.clone()),
+            _ => no_files(path),
+        };
+        let model = parse(
+            document("tri%20angle%20%C3%A9.bin", &[0.0]).as_bytes(),
+            read_file,
+        )
+        .unwrap();
+        assert_eq!(model.meshes[0].positions.len(), 3);
+
+        for uri in &["tri%2", "tri%zz.bin", "tri%+1.bin", "tri%FF.bin"] {
+            match parse(document(uri, &[0.0]).as_bytes(), read_file) {
+                Err(MeshError::Unsupported(message)) => {
+                    assert!(message.contains("malformed uri"), "{}", message)
+                }
+                other => panic!("expected a malformed uri for {}, got {:?}", uri, other),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_remote_and_short_buffers() {
+        let remote = parse(
+            document("https://example.com/tri.bin", &[0.0]).as_bytes(),
+            no_files,
+        );
+        assert!(matches!(remote, Err(MeshError::Unsupported(_))));
+
+        let short = base64::encode(&base64::decode(TRIANGLE_BASE64).unwrap()[..40]);
+        let uri = format!("data:application/octet-stream;base64,{}", short);
+        let short = parse(document(&uri, &[0.0]).as_bytes(), no_files);
+        assert!(matches!(short, Err(MeshError::Unsupported(_))));
+    }
+
+    #[test]
+    fn triangulates_strips_and_fans() {
+        use gltf::mesh::Mode;
+
+        assert_eq!(
+            triangulate(Mode::TriangleStrip, vec![0, 1, 2, 3]),
+            Some(vec![0, 1, 2, 2, 1, 3])
+        );
+        assert_eq!(
+            triangulate(Mode::TriangleFan, vec![0, 1, 2, 3]),
+            Some(vec![0, 1, 2, 0, 2, 3])
+        );
+        assert_eq!(triangulate(Mode::Lines, vec![0, 1]), None);
+    }
+}
@@ -0,0 +1,278 @@
+use std::{fmt, path::PathBuf, sync::Arc};
+
+use super::{assets::Handle, vfs::Vfs, Error, Loader};
+use crate::{
+    component::mesh::{Mesh, MeshManager},
+    vxl_gl::Gl,
+};
+
+pub mod gltf2;
+pub mod obj;
+
+/// Triangle mesh on the cpu side, before it is uploaded into a `Mesh`.
+/// `uvs` and `normals` are either empty or hold one entry per position
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshData {
+    pub name: String,
+    pub positions: Vec<cgmath::Vector3<f32>>,
+    pub uvs: Vec<cgmath::Vector2<f32>>,
+    pub normals: Vec<cgmath::Vector3<f32>>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn new(name: &str) -> Self {
+        MeshData {
+            name: name.to_owned(),
+            ..MeshData::default()
+        }
+    }
+
+    pub fn has_uvs(&self) -> bool {
+        !self.uvs.is_empty()
+    }
+
+    pub fn has_normals(&self) -> bool {
+        !self.normals.is_empty()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn get_triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
+
+/// Every mesh of a model file, plus the features of the file that were skipped
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Model {
+    pub meshes: Vec<MeshData>,
+    /// Human readable description of every unsupported feature the file uses
+    pub unsupported: Vec<String>,
+}
+
+impl Model {
+    fn skip(&mut self, feature: String) {
+        if !self.unsupported.contains(&feature) {
+            self.unsupported.push(feature);
+        }
+    }
+
+    /// Adds `data`, renamed to `<name>.<n>` with the lowest free `n` if another mesh
+    /// already has its name. Empty meshes are dropped
+    fn push_mesh(&mut self, mut data: MeshData) {
+        if data.is_empty() {
+            return;
+        }
+        let is_free = |name: &str| self.meshes.iter().all(|mesh| mesh.name != name);
+        if !is_free(&data.name) {
+            let name = (1..)
+                .map(|n| format!("{}.{}", data.name, n))
+                .find(|name| is_free(name))
+                .unwrap();
+            data.name = name;
+        }
+        self.meshes.push(data);
+    }
+}
+
+#[derive(Debug)]
+pub enum MeshError {
+    Parse {
+        line: usize,
+        message: String,
+    },
+    Gltf(gltf::Error),
+    /// The file relies on something that can not be skipped
+    Unsupported(String),
+    UnknownFormat {
+        extension: String,
+    },
+    NoGeometry,
+    /// Two meshes of the model would be stored under the same name
+    DuplicateMesh {
+        name: String,
+    },
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            MeshError::Gltf(error) => write!(f, "{}", error),
+            MeshError::Unsupported(feature) => write!(f, "unsupported: {}", feature),
+            MeshError::UnknownFormat { extension } => {
+                write!(f, "unknown model format \"{}\"", extension)
+            }
+            MeshError::NoGeometry => write!(f, "file contains no triangles"),
+            MeshError::DuplicateMesh { name } => write!(f, "more than one mesh is named {}", name),
+        }
+    }
+}
+
+/// Parses a model file by its extension (`obj`, `gltf` or `glb`) without touching gl.
+/// `read_file` resolves files the model references, like glTF `.bin` buffers,
+/// relative to the directory of `path`
+pub fn parse_model<F>(path: &str, bytes: &[u8], read_file: F) -> Result<Model, MeshError>
+where
+    F: Fn(&str) -> Result<Vec<u8>, Error>,
+{
+    let extension = path
+        .rsplit('.')
+        .next()
+        .filter(|extension| !extension.contains('/'))
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    let directory = match path.rfind('/') {
+        Some(end) => &path[..=end],
+        None => "",
+    };
+    let read_relative = |relative: &str| read_file(&format!("{}{}", directory, relative));
+
+    let model = match extension.as_str() {
+        "obj" => obj::parse(bytes)?,
+        "gltf" | "glb" => gltf2::parse(bytes, read_relative)?,
+        _ => return Err(MeshError::UnknownFormat { extension }),
+    };
+
+    if model.meshes.iter().all(MeshData::is_empty) {
+        return Err(MeshError::NoGeometry);
+    }
+
+    Ok(model)
+}
+
+/// Imports models from the `models/` directory into the `MeshManager`
+pub struct ModelLoader {
+    loader: Loader,
+}
+
+impl ModelLoader {
+    pub fn new(vfs: &Arc<Vfs>) -> Self {
+        ModelLoader {
+            loader: Loader::new(vfs, "models/"),
+        }
+    }
+
+    /// Reads and parses the model at `path`, without uploading anything
+    pub fn import(&self, path: &str) -> Result<Model, Error> {
+        let bytes = self.loader.load_as_bytes(path)?;
+
+        parse_model(path, &bytes, |file| self.loader.load_as_bytes(file)).map_err(|error| {
+            Error::Mesh {
+                path: PathBuf::from(path),
+                error,
+            }
+        })
+    }
+
+    /// Imports the model at `path` and uploads each of its meshes as `<path>#<mesh name>`.
+    /// Skipped features are reported, meshes that are already loaded from an earlier
+    /// call are reused. Fails without uploading anything if two meshes share a name
+    pub fn load(
+        &self,
+        gl: &Gl,
+        meshes: &mut MeshManager,
+        path: &str,
+    ) -> Result<Vec<Handle<Mesh>>, Error> {
+        let model = self.import(path)?;
+        for feature in &model.unsupported {
+            println!("Model \"{}\" - Skipped {}", path, feature);
+        }
+
+        let mut names: Vec<&str> = vec![];
+        for data in &model.meshes {
+            if names.contains(&data.name.as_str()) {
+                return Err(Error::Mesh {
+                    path: PathBuf::from(path),
+                    error: MeshError::DuplicateMesh {
+                        name: data.name.clone(),
+                    },
+                });
+            }
+            names.push(&data.name);
+        }
+
+        let handles = model
+            .meshes
+            .iter()
+            .filter(|data| !data.is_empty())
+            .map(|data| {
+                let handle = meshes.reserve(&format!("{}#{}", path, data.name));
+                if !meshes.is_loaded(&handle) {
+                    meshes.set(&handle, Mesh::from_mesh_data(gl, data));
+                }
+                handle
+            })
+            .collect();
+        println!("Model \"{}\" - Loaded", path);
+
+        Ok(handles)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_files(path: &str) -> Result<Vec<u8>, Error> {
+        Err(Error::MissingAsset {
+            name: path.to_owned(),
+        })
+    }
+
+    #[test]
+    fn picks_the_parser_by_extension() {
+        let triangle = b"v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n";
+        assert!(parse_model("props/Triangle.OBJ", triangle, no_files).is_ok());
+
+        for path in &["triangle.fbx", "triangle", "models.d/triangle"] {
+            match parse_model(path, triangle, no_files) {
+                Err(MeshError::UnknownFormat { .. }) => {}
+                other => panic!("expected an unknown format for {}, got {:?}", path, other),
+            }
+        }
+    }
+
+    #[test]
+    fn models_without_triangles_are_errors() {
+        let result = parse_model("points.obj", b"v 0 0 0\np 1\n", no_files);
+        assert!(matches!(result, Err(MeshError::NoGeometry)));
+    }
+
+    #[test]
+    fn gltf_buffers_are_read_next_to_the_model() {
+        let document =
+            r#"{"asset": {"version": "2.0"}, "buffers": [{"byteLength": 1, "uri": "data.bin"}]}"#;
+        let read_file = |path: &str| match path {
+            "props/lamp/data.bin" => Ok(vec![0]),
+            _ => no_files(path),
+        };
+
+        // The buffer is found, the file just has no meshes
+        let result = parse_model("props/lamp/lamp.gltf", document.as_bytes(), read_file);
+        assert!(matches!(result, Err(MeshError::NoGeometry)));
+        let result = parse_model("lamp.gltf", document.as_bytes(), read_file);
+        assert!(matches!(result, Err(MeshError::Unsupported(_))));
+    }
+
+    #[test]
+    fn duplicate_names_get_the_lowest_free_suffix() {
+        let mesh = |name: &str| MeshData {
+            indices: vec![0, 0, 0],
+            ..MeshData::new(name)
+        };
+        let mut model = Model::default();
+        model.push_mesh(mesh("a.2"));
+        for _ in 0..3 {
+            model.push_mesh(mesh("a"));
+        }
+        model.push_mesh(MeshData::new("empty"));
+
+        let names: Vec<&str> = model.meshes.iter().map(|mesh| mesh.name.as_str()).collect();
+        assert_eq!(names, vec!["a.2", "a", "a.1", "a.3"]);
+    }
+}
@@ -0,0 +1,317 @@
+//! Wavefront OBJ: `v`, `vt`, `vn` and polygonal `f` elements, split into meshes by `o`/`g`.
+//! Materials, free-form geometry, lines and points are reported as unsupported
+
+use std::collections::HashMap;
+
+use super::{MeshData, MeshError, Model};
+
+/// Position, uv and normal index of one face corner, all 0 based
+type Corner = (usize, Option<usize>, Option<usize>);
+
+struct MeshBuilder {
+    data: MeshData,
+    corners: HashMap<Corner, u32>,
+    has_uvs: bool,
+    has_normals: bool,
+}
+
+impl MeshBuilder {
+    fn new(name: &str) -> Self {
+        MeshBuilder {
+            data: MeshData::new(name),
+            corners: HashMap::new(),
+            has_uvs: false,
+            has_normals: false,
+        }
+    }
+
+    /// Unifies the separate OBJ index streams into one vertex per distinct corner
+    fn add_corner(
+        &mut self,
+        corner: Corner,
+        positions: &[cgmath::Vector3<f32>],
+        uvs: &[cgmath::Vector2<f32>],
+        normals: &[cgmath::Vector3<f32>],
+    ) -> u32 {
+        if let Some(index) = self.corners.get(&corner) {
+            return *index;
+        }
+
+        let (position, uv, normal) = corner;
+        let index = self.data.positions.len() as u32;
+        self.data.positions.push(positions[position]);
+        self.data
+            .uvs
+            .push(uv.map_or(cgmath::vec2(0.0, 0.0), |uv| uvs[uv]));
+        self.data
+            .normals
+            .push(normal.map_or(cgmath::vec3(0.0, 0.0, 0.0), |normal| normals[normal]));
+        self.has_uvs |= uv.is_some();
+        self.has_normals |= normal.is_some();
+        self.corners.insert(corner, index);
+
+        index
+    }
+
+    fn finish(mut self) -> MeshData {
+        if !self.has_uvs {
+            self.data.uvs.clear();
+        }
+        if !self.has_normals {
+            self.data.normals.clear();
+        }
+        self.data
+    }
+}
+
+pub fn parse(bytes: &[u8]) -> Result<Model, MeshError> {
+    let text = String::from_utf8_lossy(bytes);
+
+    let mut model = Model::default();
+    let mut positions: Vec<cgmath::Vector3<f32>> = vec![];
+    let mut uvs: Vec<cgmath::Vector2<f32>> = vec![];
+    let mut normals: Vec<cgmath::Vector3<f32>> = vec![];
+    let mut mesh = MeshBuilder::new("default");
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let mut parts = line.split_whitespace();
+        let keyword = match parts.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        let arguments: Vec<&str> = parts.collect();
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats::<3>(&arguments, line_number)?;
+                positions.push(cgmath::vec3(x, y, z));
+            }
+            "vt" => {
+                // v defaults to 0, a third coordinate is ignored
+                let [u] = parse_floats::<1>(&arguments, line_number)?;
+                let v = match arguments.len() {
+                    1 => 0.0,
+                    _ => parse_floats::<2>(&arguments, line_number)?[1],
+                };
+                // OBJ puts the uv origin at the bottom left, textures are uploaded top row first
+                uvs.push(cgmath::vec2(u, 1.0 - v));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats::<3>(&arguments, line_number)?;
+                normals.push(cgmath::vec3(x, y, z));
+            }
+            "f" => {
+                if arguments.len() < 3 {
+                    return Err(MeshError::Parse {
+                        line: line_number,
+                        message: "face with less than 3 vertices".to_owned(),
+                    });
+                }
+
+                let mut face = Vec::with_capacity(arguments.len());
+                for argument in &arguments {
+                    let corner = parse_corner(
+                        argument,
+                        (positions.len(), uvs.len(), normals.len()),
+                        line_number,
+                    )?;
+                    face.push(mesh.add_corner(corner, &positions, &uvs, &normals));
+                }
+
+                // Convex polygons are split into a triangle fan
+                for i in 1..face.len() - 1 {
+                    mesh.data
+                        .indices
+                        .extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                }
+            }
+            "o" | "g" => {
+                let name = arguments.join(" ");
+                if mesh.data.is_empty() {
+                    mesh.data.name = name;
+                } else {
+                    model.push_mesh(mesh.finish());
+                    mesh = MeshBuilder::new(&name);
+                }
+            }
+            // Smoothing groups only matter for normal generation, which is not done
+            "s" => (),
+            "mtllib" | "usemtl" => model.skip("obj materials (mtllib/usemtl)".to_owned()),
+            "l" => model.skip("obj line elements (l)".to_owned()),
+            "p" => model.skip("obj point elements (p)".to_owned()),
+            "vp" | "cstype" | "deg" | "bmat" | "step" | "curv" | "curv2" | "surf" | "parm"
+            | "trim" | "hole" | "scrv" | "sp" | "end" => {
+                model.skip("obj free-form geometry".to_owned())
+            }
+            other => model.skip(format!("obj statement \"{}\"", other)),
+        }
+    }
+
+    model.push_mesh(mesh.finish());
+
+    Ok(model)
+}
+
+fn parse_floats<const N: usize>(arguments: &[&str], line: usize) -> Result<[f32; N], MeshError> {
+    let mut values = [0.0; N];
+    if arguments.len() < N {
+        return Err(MeshError::Parse {
+            line,
+            message: format!("expected {} numbers, found {}", N, arguments.len()),
+        });
+    }
+
+    for (value, argument) in values.iter_mut().zip(arguments) {
+        *value = argument.parse().map_err(|_| MeshError::Parse {
+            line,
+            message: format!("\"{}\" is not a number", argument),
+        })?;
+    }
+
+    Ok(values)
+}
+
+/// Parses `v`, `v/vt`, `v//vn` or `v/vt/vn`, resolving negative (relative) indices
+fn parse_corner(
+    argument: &str,
+    counts: (usize, usize, usize),
+    line: usize,
+) -> Result<Corner, MeshError> {
+    let mut parts = argument.split('/');
+    let position = parse_index(parts.next(), counts.0, line)?.ok_or(MeshError::Parse {
+        line,
+        message: format!("face vertex \"{}\" has no position", argument),
+    })?;
+    let uv = parse_index(parts.next(), counts.1, line)?;
+    let normal = parse_index(parts.next(), counts.2, line)?;
+
+    Ok((position, uv, normal))
+}
+
+fn parse_index(part: Option<&str>, count: usize, line: usize) -> Result<Option<usize>, MeshError> {
+    let part = match part {
+        Some(part) if !part.is_empty() => part,
+        _ => return Ok(None),
+    };
+
+    let index: i64 = part.parse().map_err(|_| MeshError::Parse {
+        line,
+        message: format!("\"{}\" is not an index", part),
+    })?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(MeshError::Parse {
+            line,
+            message: format!("index {} is out of range", index),
+        });
+    }
+
+    Ok(Some(resolved as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_str(text: &str) -> Result<Model, MeshError> {
+        parse(text.as_bytes())
+    }
+
+    #[test]
+    fn shares_vertices_of_identical_corners() {
+        let model = parse_str(
+            "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\nvn 0 0 1\n\
+             f 1/1/1 2/2/1 3/3/1 4/4/1 # quad\n",
+        )
+        .unwrap();
+
+        assert_eq!(model.meshes.len(), 1);
+        let mesh = &model.meshes[0];
+        assert_eq!(mesh.name, "default");
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        // The uv origin moves to the top left
+        assert_eq!(mesh.uvs[0], cgmath::vec2(0.0, 1.0));
+        assert_eq!(mesh.uvs[2], cgmath::vec2(1.0, 0.0));
+        assert_eq!(mesh.normals, vec![cgmath::vec3(0.0, 0.0, 1.0); 4]);
+    }
+
+    #[test]
+    fn accepts_texture_coordinates_with_one_to_three_components() {
+        let model =
+            parse_str("v 0 0 0\nvt 0.25\nvt 0.5 0.25\nvt 0.75 0.5 1\nf 1/1 1/2 1/3\n").unwrap();
+        let mesh = &model.meshes[0];
+
+        assert_eq!(
+            mesh.uvs,
+            vec![
+                cgmath::vec2(0.25, 1.0),
+                cgmath::vec2(0.5, 0.75),
+                cgmath::vec2(0.75, 0.5),
+            ]
+        );
+        assert!(!mesh.has_normals());
+    }
+
+    #[test]
+    fn resolves_negative_indices() {
+        let model = parse_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nf -3 -2 -1\n").unwrap();
+        assert_eq!(model.meshes[0].indices, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn groups_with_the_same_name_get_unique_names() {
+        let model = parse_str(
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\n\
+             g part\nf 1 2 3\ng part\nf 1 2 3\ng empty\ng part\nf 1 2 3\no part.1\nf 1 2 3\n",
+        )
+        .unwrap();
+
+        let names: Vec<&str> = model.meshes.iter().map(|mesh| mesh.name.as_str()).collect();
+        assert_eq!(names, vec!["part", "part.1", "part.2", "part.1.1"]);
+    }
+
+    #[test]
+    fn reports_unsupported_statements_once() {
+        let model = parse_str(
+            "mtllib a.mtl\nusemtl red\nv 0 0 0\nv 1 0 0\nv 0 1 0\nl 1 2\nl 2 3\nf 1 2 3\nfoo\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            model.unsupported,
+            vec![
+                "obj materials (mtllib/usemtl)",
+                "obj line elements (l)",
+                "obj statement \"foo\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_malformed_lines() {
+        let line_of = |text: &str| match parse_str(text) {
+            Err(MeshError::Parse { line, .. }) => line,
+            other => panic!("expected a parse error, got {:?}", other),
+        };
+
+        assert_eq!(line_of("v 0 0\n"), 1);
+        assert_eq!(line_of("v 0 0 0\nvt\n"), 2);
+        assert_eq!(line_of("v 0 0 0\nvt 0 x\n"), 2);
+        assert_eq!(line_of("v 0 0 0\nv 1 0 0\nf 1 2\n"), 3);
+        assert_eq!(line_of("v 0 0 0\nf 1 2 3\n"), 2);
+        assert_eq!(line_of("v 0 0 0\nf 1/1 1 1\n"), 2);
+        assert_eq!(line_of("v 0 0 0\nf 0 1 1\n"), 2);
+    }
+}
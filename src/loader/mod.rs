@@ -1,50 +1,153 @@
-use std::{
-    ffi::CString,
-    fs,
-    io::{self, Read},
-    path::PathBuf,
-};
+use std::{ffi::CString, fmt, io, path::PathBuf, sync::Arc};
 
+use crate::{resource::input::actions::ParseError, vxl_gl::gl};
+use meshes::MeshError;
+use shaders::error::ShaderError;
+use vfs::Vfs;
+use vox::VoxError;
+
+pub mod assets;
+pub mod background;
+pub mod hot_reload;
+pub mod meshes;
+pub mod pak;
 pub mod shaders;
 pub mod textures;
+pub mod vfs;
+pub mod vox;
 
 #[derive(Debug)]
 pub enum Error {
-    Io(io::Error),
-    FileContainsNil,
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    FileContainsNil {
+        path: PathBuf,
+    },
+    Utf8 {
+        path: PathBuf,
+        error: std::string::FromUtf8Error,
+    },
+    Zip {
+        path: PathBuf,
+        error: zip::result::ZipError,
+    },
+    Decode {
+        path: PathBuf,
+        error: image::ImageError,
+    },
+    MissingAsset {
+        name: String,
+    },
+    MissingArgument {
+        flag: String,
+    },
+    Mesh {
+        path: PathBuf,
+        error: MeshError,
+    },
+    Vox {
+        path: PathBuf,
+        error: VoxError,
+    },
+    Config {
+        path: PathBuf,
+        error: ParseError,
+    },
+    Shader(ShaderError),
+    Gl {
+        code: gl::types::GLenum,
+    },
+}
+
+impl From<ShaderError> for Error {
+    fn from(other: ShaderError) -> Self {
+        Error::Shader(other)
+    }
 }
 
-impl From<io::Error> for Error {
-    fn from(other: io::Error) -> Self {
-        Error::Io(other)
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            Error::FileContainsNil { path } => {
+                write!(f, "{}: file contains a nil byte", path.display())
+            }
+            Error::Utf8 { path, error } => {
+                write!(f, "{}: file is not valid utf-8: {}", path.display(), error)
+            }
+            Error::Zip { path, error } => {
+                write!(f, "{}: failed to open archive: {}", path.display(), error)
+            }
+            Error::Decode { path, error } => {
+                write!(f, "{}: failed to decode: {}", path.display(), error)
+            }
+            Error::MissingAsset { name } => write!(f, "asset \"{}\" is not loaded", name),
+            Error::MissingArgument { flag } => write!(f, "{} needs a value", flag),
+            Error::Mesh { path, error } => {
+                write!(f, "{}: failed to import: {}", path.display(), error)
+            }
+            Error::Vox { path, error } => {
+                write!(f, "{}: failed to import: {}", path.display(), error)
+            }
+            Error::Config { path, error } => write!(f, "{}: {}", path.display(), error),
+            Error::Shader(error) => write!(f, "{}", error),
+            Error::Gl { code } => write!(f, "GL error 0x{:X}", code),
+        }
     }
 }
 
+/// Reads assets of one kind (shaders, images, ...) through the virtual file system
+#[derive(Clone)]
 pub struct Loader {
-    root_path: PathBuf,
+    vfs: Arc<Vfs>,
+    content_path: &'static str,
 }
 impl Loader {
-    pub fn new(content_path: &'static str) -> Loader {
-        let path = std::env::current_exe().unwrap();
-        let path_parent = path.parent().unwrap();
-        let root_path = path_parent.join("res/").join(content_path);
+    pub fn new(vfs: &Arc<Vfs>, content_path: &'static str) -> Loader {
+        Loader {
+            vfs: Arc::clone(vfs),
+            content_path,
+        }
+    }
 
-        Loader { root_path }
+    fn virtual_path(&self, asset_path: &str) -> String {
+        format!("{}{}", self.content_path, asset_path)
     }
 
-    pub fn load_as_cstring(&self, asset_path: &'static str) -> CString {
-        let mut file = fs::File::open(self.root_path.join(asset_path)).unwrap();
-        let mut buffer = Vec::with_capacity(file.metadata().unwrap().len() as usize + 1);
+    /// Real file backing the asset, `None` if it is served from an archive
+    pub fn get_path(&self, asset_path: &str) -> Option<PathBuf> {
+        self.vfs.system_path(&self.virtual_path(asset_path))
+    }
 
-        file.read_to_end(&mut buffer);
-        if buffer.iter().find(|i| **i == 0).is_some() {
-            panic!(Error::FileContainsNil);
-        }
+    pub fn load_as_bytes(&self, asset_path: &str) -> Result<Vec<u8>, Error> {
+        self.vfs.read(&self.virtual_path(asset_path))
+    }
 
-        unsafe { CString::from_vec_unchecked(buffer) }
+    pub fn load_as_cstring(&self, asset_path: &str) -> Result<CString, Error> {
+        let buffer = self.load_as_bytes(asset_path)?;
+
+        CString::new(buffer).map_err(|_| Error::FileContainsNil {
+            path: PathBuf::from(self.virtual_path(asset_path)),
+        })
     }
 
-    pub fn load_as_image(&self, asset_path: &'static str) -> image::DynamicImage {
-        image::open(self.root_path.join(asset_path)).unwrap()
+    pub fn load_as_string(&self, asset_path: &str) -> Result<String, Error> {
+        let buffer = self.load_as_bytes(asset_path)?;
+
+        String::from_utf8(buffer).map_err(|error| Error::Utf8 {
+            path: PathBuf::from(self.virtual_path(asset_path)),
+            error,
+        })
+    }
+
+    pub fn load_as_image(&self, asset_path: &str) -> Result<image::DynamicImage, Error> {
+        let buffer = self.load_as_bytes(asset_path)?;
+
+        image::load_from_memory(&buffer).map_err(|error| Error::Decode {
+            path: PathBuf::from(self.virtual_path(asset_path)),
+            error,
+        })
     }
 }
@@ -0,0 +1,407 @@
+//! `.pak` asset archive: a header, the file blobs and an index at the end.
+//! All integers are little endian.
+//!
+//! ```text
+//! header  magic "VXLPAK\0\0" | version u32 | entry count u32 | index offset u64
+//! data    file blobs, deflate compressed or stored as is
+//! index   per entry: path length u16 | path utf8 | offset u64 | stored size u64
+//!         | size u64 | compression u8 | crc32 of the uncompressed content u32
+//! ```
+//!
+//! The module only depends on std, flate2 and crc32fast, `vxl-pack` writes archives with it.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::Path,
+    sync::Mutex,
+};
+
+pub const MAGIC: &[u8; 8] = b"VXLPAK\0\0";
+pub const VERSION: u32 = 1;
+const HEADER_SIZE: u64 = 8 + 4 + 4 + 8;
+/// Smallest index entry, the one with an empty path
+const MIN_ENTRY_SIZE: u64 = 2 + 8 + 8 + 8 + 1 + 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Deflate,
+}
+
+impl Compression {
+    fn to_byte(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Deflate => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> io::Result<Self> {
+        match byte {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Deflate),
+            _ => Err(invalid_data(format!("unknown compression {}", byte))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PakEntry {
+    pub offset: u64,
+    pub stored_size: u64,
+    pub size: u64,
+    pub compression: Compression,
+    pub crc32: u32,
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(data);
+    hasher.finalize()
+}
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+/// Writes an archive. Files are streamed into `writer`, the index is written by `finish`
+pub struct PakWriter<W: Write + Seek> {
+    writer: W,
+    index: Vec<(String, PakEntry)>,
+    position: u64,
+}
+
+impl<W: Write + Seek> PakWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        // Header is rewritten with the real values by `finish`
+        write_header(&mut writer, 0, 0)?;
+
+        Ok(PakWriter {
+            writer,
+            index: vec![],
+            position: HEADER_SIZE,
+        })
+    }
+
+    /// Adds a file. With `compress` the content is deflated, unless that does not make it smaller
+    pub fn add_file(&mut self, path: &str, data: &[u8], compress: bool) -> io::Result<()> {
+        if path.len() > u16::MAX as usize {
+            return Err(invalid_data(format!("path is too long: {}", path)));
+        }
+        if self.index.iter().any(|(existing, _)| existing == path) {
+            return Err(invalid_data(format!("duplicate path: {}", path)));
+        }
+
+        let mut compression = Compression::None;
+        let mut stored: Vec<u8> = vec![];
+        if compress {
+            let mut encoder =
+                flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::best());
+            encoder.write_all(data)?;
+            let deflated = encoder.finish()?;
+            if deflated.len() < data.len() {
+                compression = Compression::Deflate;
+                stored = deflated;
+            }
+        }
+        let stored = if compression == Compression::None {
+            data
+        } else {
+            &stored[..]
+        };
+
+        self.writer.write_all(stored)?;
+        self.index.push((
+            path.to_owned(),
+            PakEntry {
+                offset: self.position,
+                stored_size: stored.len() as u64,
+                size: data.len() as u64,
+                compression,
+                crc32: checksum(data),
+            },
+        ));
+        self.position += stored.len() as u64;
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        let index_offset = self.position;
+        for (path, entry) in &self.index {
+            self.writer.write_all(&(path.len() as u16).to_le_bytes())?;
+            self.writer.write_all(path.as_bytes())?;
+            self.writer.write_all(&entry.offset.to_le_bytes())?;
+            self.writer.write_all(&entry.stored_size.to_le_bytes())?;
+            self.writer.write_all(&entry.size.to_le_bytes())?;
+            self.writer.write_all(&[entry.compression.to_byte()])?;
+            self.writer.write_all(&entry.crc32.to_le_bytes())?;
+        }
+
+        self.writer.seek(SeekFrom::Start(0))?;
+        write_header(&mut self.writer, self.index.len() as u32, index_offset)?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+fn write_header<W: Write>(writer: &mut W, entry_count: u32, index_offset: u64) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&entry_count.to_le_bytes())?;
+    writer.write_all(&index_offset.to_le_bytes())
+}
+
+fn read_u16<R: Read>(reader: &mut R) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Reads files out of an archive, verifying their checksum
+pub struct PakReader<R: Read + Seek> {
+    reader: Mutex<R>,
+    index: HashMap<String, PakEntry>,
+}
+
+impl PakReader<fs::File> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        PakReader::new(fs::File::open(path)?)
+    }
+}
+
+impl<R: Read + Seek> PakReader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a pak archive"));
+        }
+
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "unsupported pak version {}, expected {}",
+                version, VERSION
+            )));
+        }
+
+        let entry_count = read_u32(&mut reader)?;
+        let index_offset = read_u64(&mut reader)?;
+
+        // Sizes are checked against the file before anything is allocated for them
+        let length = reader.seek(SeekFrom::End(0))?;
+        if index_offset < HEADER_SIZE || index_offset > length {
+            return Err(invalid_data("index offset is out of bounds"));
+        }
+        if u64::from(entry_count) > (length - index_offset) / MIN_ENTRY_SIZE {
+            return Err(invalid_data("entry count does not fit in the index"));
+        }
+        reader.seek(SeekFrom::Start(index_offset))?;
+
+        let mut index = HashMap::with_capacity(entry_count as usize);
+        for _ in 0..entry_count {
+            let path_len = read_u16(&mut reader)?;
+            let mut path = vec![0; path_len as usize];
+            reader.read_exact(&mut path)?;
+            let path = String::from_utf8(path).map_err(invalid_data)?;
+
+            let entry = PakEntry {
+                offset: read_u64(&mut reader)?,
+                stored_size: read_u64(&mut reader)?,
+                size: read_u64(&mut reader)?,
+                compression: {
+                    let mut byte = [0; 1];
+                    reader.read_exact(&mut byte)?;
+                    Compression::from_byte(byte[0])?
+                },
+                crc32: read_u32(&mut reader)?,
+            };
+            let in_bounds = entry.offset >= HEADER_SIZE
+                && entry
+                    .offset
+                    .checked_add(entry.stored_size)
+                    .is_some_and(|end| end <= index_offset);
+            if !in_bounds {
+                return Err(invalid_data(format!("{} is out of bounds", path)));
+            }
+            index.insert(path, entry);
+        }
+
+        Ok(PakReader {
+            reader: Mutex::new(reader),
+            index,
+        })
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.index.contains_key(path)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (&String, &PakEntry)> {
+        self.index.iter()
+    }
+
+    /// Reads the file at `path`, `None` if the archive does not contain it
+    pub fn read(&self, path: &str) -> Option<io::Result<Vec<u8>>> {
+        let entry = self.index.get(path)?;
+        Some(self.read_entry(entry))
+    }
+
+    fn read_entry(&self, entry: &PakEntry) -> io::Result<Vec<u8>> {
+        let mut stored = vec![0; entry.stored_size as usize];
+        {
+            let mut reader = match self.reader.lock() {
+                Ok(reader) => reader,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            reader.seek(SeekFrom::Start(entry.offset))?;
+            reader.read_exact(&mut stored)?;
+        }
+
+        let data = match entry.compression {
+            Compression::None => stored,
+            Compression::Deflate => {
+                // A byte more than expected is enough to tell the size is wrong
+                let mut data = vec![];
+                flate2::read::DeflateDecoder::new(&stored[..])
+                    .take(entry.size.saturating_add(1))
+                    .read_to_end(&mut data)?;
+                data
+            }
+        };
+
+        if data.len() as u64 != entry.size || checksum(&data) != entry.crc32 {
+            return Err(invalid_data("checksum mismatch"));
+        }
+
+        Ok(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const TEXT: &[u8] = b"the same line over and over, the same line over and over, the same line";
+
+    fn archive() -> Vec<u8> {
+        let mut writer = PakWriter::new(Cursor::new(vec![])).unwrap();
+        writer.add_file("stored.txt", TEXT, false).unwrap();
+        writer.add_file("deflated.txt", TEXT, true).unwrap();
+        // Deflate would make it bigger, so it is stored even when asked to compress
+        writer.add_file("tiny.bin", &[7], true).unwrap();
+        writer.add_file("empty", &[], false).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn error_of<T>(result: io::Result<T>) -> io::Error {
+        match result {
+            Ok(_) => panic!("expected an error"),
+            Err(error) => error,
+        }
+    }
+
+    #[test]
+    fn round_trips_stored_and_deflated_entries() {
+        let reader = PakReader::new(Cursor::new(archive())).unwrap();
+
+        let compression = |path: &str| reader.index[path].compression;
+        assert_eq!(compression("stored.txt"), Compression::None);
+        assert_eq!(compression("deflated.txt"), Compression::Deflate);
+        assert_eq!(compression("tiny.bin"), Compression::None);
+        assert!(reader.index["deflated.txt"].stored_size < TEXT.len() as u64);
+
+        assert_eq!(reader.read("stored.txt").unwrap().unwrap(), TEXT);
+        assert_eq!(reader.read("deflated.txt").unwrap().unwrap(), TEXT);
+        assert_eq!(reader.read("tiny.bin").unwrap().unwrap(), vec![7]);
+        assert_eq!(reader.read("empty").unwrap().unwrap(), Vec::<u8>::new());
+        assert!(reader.read("missing").is_none());
+        assert_eq!(reader.entries().count(), 4);
+    }
+
+    #[test]
+    fn rejects_duplicate_paths() {
+        let mut writer = PakWriter::new(Cursor::new(vec![])).unwrap();
+        writer.add_file("a", TEXT, false).unwrap();
+        assert!(writer.add_file("a", TEXT, true).is_err());
+    }
+
+    #[test]
+    fn detects_corrupted_content() {
+        for path in &["stored.txt", "deflated.txt"] {
+            let mut data = archive();
+            let entry = PakReader::new(Cursor::new(data.clone())).unwrap().index[*path].clone();
+            let last = (entry.offset + entry.stored_size - 1) as usize;
+            data[last] ^= 0xff;
+
+            let reader = PakReader::new(Cursor::new(data)).unwrap();
+            let error = error_of(reader.read(path).unwrap());
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{}", path);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_index() {
+        let data = archive();
+        for cut in 1..=MIN_ENTRY_SIZE as usize {
+            let truncated = data[..data.len() - cut].to_vec();
+            assert!(
+                PakReader::new(Cursor::new(truncated)).is_err(),
+                "cut {}",
+                cut
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_counts_and_sizes_beyond_the_file() {
+        let data = archive();
+        let index_offset = read_u64(&mut &data[16..24]).unwrap();
+
+        let mut huge_count = data.clone();
+        huge_count[12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(PakReader::new(Cursor::new(huge_count)).is_err());
+
+        let mut bad_offset = data.clone();
+        bad_offset[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(PakReader::new(Cursor::new(bad_offset)).is_err());
+
+        // Stored size of the first entry, right after its path
+        let mut huge_size = data;
+        let stored_size = index_offset as usize + 2 + "stored.txt".len() + 8;
+        huge_size[stored_size..stored_size + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(PakReader::new(Cursor::new(huge_size)).is_err());
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let error = error_of(PakReader::new(Cursor::new(
+            b"PK\x03\x04 not a pak".to_vec(),
+        )));
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let mut data = archive();
+        data[8..12].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert!(PakReader::new(Cursor::new(data)).is_err());
+    }
+}
@@ -0,0 +1,259 @@
+use std::fmt;
+
+use super::preprocessor::PreprocessError;
+use crate::vxl_gl::gl;
+
+/// Single message of a driver info log, with its location mapped back to the source file
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    Preprocess {
+        program: String,
+        path: String,
+        error: PreprocessError,
+    },
+    SourceContainsNil {
+        program: String,
+        path: String,
+    },
+    Compile {
+        program: String,
+        stage: gl::types::GLenum,
+        path: String,
+        log: Vec<LogEntry>,
+    },
+    Link {
+        program: String,
+        log: Vec<LogEntry>,
+    },
+}
+
+impl ShaderError {
+    pub fn get_program(&self) -> &str {
+        match self {
+            ShaderError::Preprocess { program, .. }
+            | ShaderError::SourceContainsNil { program, .. }
+            | ShaderError::Compile { program, .. }
+            | ShaderError::Link { program, .. } => program,
+        }
+    }
+}
+
+pub fn stage_name(stage: gl::types::GLenum) -> &'static str {
+    match stage {
+        gl::VERTEX_SHADER => "vertex",
+        gl::FRAGMENT_SHADER => "fragment",
+        _ => "unknown",
+    }
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, "{}:{}: {}", file, line, self.message),
+            (Some(file), None) => write!(f, "{}: {}", file, self.message),
+            (None, Some(line)) => write!(f, "line {}: {}", line, self.message),
+            _ => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Preprocess {
+                program,
+                path,
+                error,
+            } => write!(
+                f,
+                "Shader \"{}\": failed to preprocess {}: {}",
+                program, path, error
+            ),
+            ShaderError::SourceContainsNil { program, path } => {
+                write!(f, "Shader \"{}\": {} contains a nil byte", program, path)
+            }
+            ShaderError::Compile {
+                program,
+                stage,
+                path,
+                log,
+            } => {
+                write!(
+                    f,
+                    "Shader \"{}\": failed to compile {} stage {}",
+                    program,
+                    stage_name(*stage),
+                    path
+                )?;
+                for entry in log {
+                    write!(f, "\n  {}", entry)?;
+                }
+                Ok(())
+            }
+            ShaderError::Link { program, log } => {
+                write!(f, "Shader \"{}\": failed to link", program)?;
+                for entry in log {
+                    write!(f, "\n  {}", entry)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parses a driver info log, mapping source string indices back through `files`.
+/// Understands the common `0:12(5): error`, `0(12) : error` and `ERROR: 0:12: ...` layouts
+pub fn parse_info_log(log: &str, files: &[String]) -> Vec<LogEntry> {
+    log.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| parse_log_line(line, files))
+        .collect()
+}
+
+fn parse_log_line(line: &str, files: &[String]) -> LogEntry {
+    let unprefixed = ["ERROR:", "WARNING:"]
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))
+        .map(|rest| rest.trim_start())
+        .unwrap_or(line);
+
+    match parse_location(unprefixed) {
+        Some((file_index, line_number, rest)) => LogEntry {
+            file: files.get(file_index).cloned(),
+            line: Some(line_number),
+            message: rest
+                .trim_start_matches(|c: char| c == ':' || c.is_whitespace())
+                .to_owned(),
+        },
+        None => LogEntry {
+            file: None,
+            line: None,
+            message: line.to_owned(),
+        },
+    }
+}
+
+/// Splits `<file>:<line>` or `<file>(<line>)` off the start of a log line
+fn parse_location(text: &str) -> Option<(usize, usize, &str)> {
+    let file_end = text.find(|c: char| !c.is_ascii_digit())?;
+    if file_end == 0 {
+        return None;
+    }
+    let file_index = text[..file_end].parse().ok()?;
+    let rest = &text[file_end..];
+
+    let (line_text, rest) = if let Some(rest) = rest.strip_prefix(':') {
+        let end = rest.find(|c: char| !c.is_ascii_digit())?;
+        (&rest[..end], &rest[end..])
+    } else if let Some(rest) = rest.strip_prefix('(') {
+        let end = rest.find(')')?;
+        (&rest[..end], &rest[end + 1..])
+    } else {
+        return None;
+    };
+    let line_number = line_text.parse().ok()?;
+
+    // Mesa appends the column as `(5)` after the line
+    let rest = match rest.strip_prefix('(') {
+        Some(column) => column
+            .find(')')
+            .map(|end| &column[end + 1..])
+            .unwrap_or(rest),
+        None => rest,
+    };
+
+    Some((file_index, line_number, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn files() -> Vec<String> {
+        vec!["main.frag".to_owned(), "lib/light.glsl".to_owned()]
+    }
+
+    #[test]
+    fn parses_mesa_layout() {
+        let log = parse_info_log("0:12(5): error: `color' undeclared\n", &files());
+
+        assert_eq!(
+            log,
+            vec![LogEntry {
+                file: Some("main.frag".to_owned()),
+                line: Some(12),
+                message: "error: `color' undeclared".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_nvidia_layout() {
+        let log = parse_info_log("1(7) : error C1008: undefined variable \"n\"", &files());
+
+        assert_eq!(
+            log,
+            vec![LogEntry {
+                file: Some("lib/light.glsl".to_owned()),
+                line: Some(7),
+                message: "error C1008: undefined variable \"n\"".to_owned(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_prefixed_layout() {
+        let log = parse_info_log(
+            "ERROR: 1:3: 'x' : undeclared identifier\nWARNING: 0:40: unused\n\n",
+            &files(),
+        );
+
+        assert_eq!(
+            log,
+            vec![
+                LogEntry {
+                    file: Some("lib/light.glsl".to_owned()),
+                    line: Some(3),
+                    message: "'x' : undeclared identifier".to_owned(),
+                },
+                LogEntry {
+                    file: Some("main.frag".to_owned()),
+                    line: Some(40),
+                    message: "unused".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_line_of_unknown_files() {
+        let log = parse_info_log("5:2(1): error: broken", &files());
+
+        assert_eq!(log[0].file, None);
+        assert_eq!(log[0].line, Some(2));
+        assert_eq!(log[0].to_string(), "line 2: error: broken");
+    }
+
+    #[test]
+    fn keeps_lines_without_a_location() {
+        let log = parse_info_log("Link failed: no main function", &files());
+
+        assert_eq!(
+            log,
+            vec![LogEntry {
+                file: None,
+                line: None,
+                message: "Link failed: no main function".to_owned(),
+            }]
+        );
+        assert_eq!(log[0].to_string(), "Link failed: no main function");
+    }
+}
@@ -1,76 +1,361 @@
+use std::{cell::RefCell, collections::HashMap, ffi::CString, sync::Arc};
+
+use error::ShaderError;
+use preprocessor::Preprocessor;
 use shader::Shader;
+use shader_program::ShaderProgram;
 
 use crate::vxl_gl::{gl, Gl};
 
-use super::Loader;
+use super::{
+    assets::{AssetManager, Handle},
+    vfs::Vfs,
+    Error, Loader,
+};
 
+pub mod error;
+pub mod preprocessor;
 pub mod shader;
 pub mod shader_program;
 
-pub struct ShaderLoader<'a> {
-    gl: &'a Gl,
-    programs: std::collections::HashMap<&'static str, shader_program::ShaderProgram>,
+/// Program used in place of shaders that failed to load, paints everything magenta
+pub const ERROR_PROGRAM_NAME: &str = "error";
+const ERROR_VERTEX_SHADER: &str = "#version 330 core
+
+layout (location = 0) in vec3 Position;
+
+uniform mat4 trans_mat;
+uniform mat4 proj_mat;
+uniform mat4 view_mat;
+
+void main()
+{
+    gl_Position = proj_mat * view_mat * trans_mat * vec4(Position, 1.0);
+}
+";
+const ERROR_FRAGMENT_SHADER: &str = "#version 330 core
+
+out vec4 Color;
+
+void main()
+{
+    Color = vec4(1.0, 0.0, 1.0, 1.0);
+}
+";
+
+pub struct ShaderManager {
+    programs: AssetManager<ShaderProgram>,
+    sources: HashMap<String, ProgramSource>,
     loader: Loader,
+    error_program: Handle<ShaderProgram>,
 }
 
-impl<'a> ShaderLoader<'a> {
-    pub fn new(gl: &'a Gl) -> ShaderLoader {
-        let loader = Loader::new("shaders/");
+impl ShaderManager {
+    /// Fails only if the built-in error shader can not be compiled
+    pub fn new(gl: &Gl, vfs: &Arc<Vfs>) -> Result<ShaderManager, Error> {
+        let loader = Loader::new(vfs, "shaders/");
 
-        ShaderLoader {
+        let mut error_source = ProgramSource {
+            shaders: vec![
+                ("error.vert.glsl".to_owned(), gl::VERTEX_SHADER),
+                ("error.frag.glsl".to_owned(), gl::FRAGMENT_SHADER),
+            ],
+            defines: vec![],
+            files: vec![],
+        };
+        let error_program = compile_program(
             gl,
-            programs: std::collections::HashMap::new(),
+            |path: &str| match path {
+                "error.vert.glsl" => Ok(ERROR_VERTEX_SHADER.to_owned()),
+                _ => Ok(ERROR_FRAGMENT_SHADER.to_owned()),
+            },
+            ERROR_PROGRAM_NAME,
+            &mut error_source,
+        )?;
+
+        let mut programs = AssetManager::new();
+        let error_handle = programs.reserve(ERROR_PROGRAM_NAME);
+        programs.set(&error_handle, error_program);
+        programs.set_persistent(&error_handle, true);
+
+        Ok(ShaderManager {
+            programs,
+            sources: HashMap::new(),
             loader,
-        }
+            error_program: error_handle,
+        })
     }
 
     pub fn add_shader_program(
         &mut self,
-        program_name: &'static str,
-        shaders: Vec<(&'static str, gl::types::GLenum)>,
-    ) -> &mut Self {
-        let mut built_shaders: Vec<Shader> = Vec::with_capacity(shaders.len());
-
-        for (asset_path, shader_type) in shaders {
-            let shader_source = self.loader.load_as_cstring(asset_path);
-            let shader = Shader::from_source(self.gl, &shader_source, shader_type);
+        gl: &Gl,
+        program_name: &str,
+        shaders: Vec<(&str, gl::types::GLenum)>,
+    ) -> Result<Handle<ShaderProgram>, Error> {
+        self.add_shader_program_with_defines(gl, program_name, shaders, &[])
+    }
 
-            built_shaders.push(shader);
+    /// Same as `add_shader_program`, but injects `defines` into every stage,
+    /// so one source can be compiled into several feature permutations.
+    /// A program that is already loaded under `program_name` is returned as is.
+    /// On failure the name stays reserved and hot reloading retries it, materials
+    /// using it draw with the error program until then
+    pub fn add_shader_program_with_defines(
+        &mut self,
+        gl: &Gl,
+        program_name: &str,
+        shaders: Vec<(&str, gl::types::GLenum)>,
+        defines: &[(&str, &str)],
+    ) -> Result<Handle<ShaderProgram>, Error> {
+        let handle = self.programs.reserve(program_name);
+        if self.programs.is_loaded(&handle) {
+            return Ok(handle);
         }
 
-        let program = shader_program::ShaderProgram::from_shaders(self.gl, &built_shaders);
-        self.programs.entry(program_name).or_insert(program);
+        let mut source = ProgramSource {
+            shaders: shaders
+                .into_iter()
+                .map(|(path, stage)| (path.to_owned(), stage))
+                .collect(),
+            defines: defines
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            files: vec![],
+        };
 
+        let loader = &self.loader;
+        let program = compile_program(
+            gl,
+            |path: &str| loader.load_as_string(path),
+            program_name,
+            &mut source,
+        );
+        // A program that failed is still watched, it loads once its files are fixed
+        self.sources.insert(program_name.to_owned(), source);
+        self.programs.set(&handle, program?);
         println!("Shader \"{}\" - Loaded", program_name);
 
-        self
+        Ok(handle)
+    }
+
+    /// Loads a program named at runtime, e.g. by a config or world file.
+    /// Its stages are expected at `<name>/<name>.vert.glsl` and `<name>/<name>.frag.glsl`
+    pub fn get_or_load(
+        &mut self,
+        gl: &Gl,
+        program_name: &str,
+    ) -> Result<Handle<ShaderProgram>, Error> {
+        let vertex = format!("{0}/{0}.vert.glsl", program_name);
+        let fragment = format!("{0}/{0}.frag.glsl", program_name);
+        self.add_shader_program(
+            gl,
+            program_name,
+            vec![
+                (&vertex, gl::VERTEX_SHADER),
+                (&fragment, gl::FRAGMENT_SHADER),
+            ],
+        )
+    }
+}
+
+/// Everything needed to compile a program again when one of its files changes
+#[derive(Clone)]
+pub struct ProgramSource {
+    shaders: Vec<(String, gl::types::GLenum)>,
+    defines: Vec<(String, String)>,
+    /// Every file read while preprocessing, including `#include`d ones
+    files: Vec<String>,
+}
+
+/// Preprocesses, compiles and links every stage of `source`, recording the files it was built
+/// from. The files are recorded on failure too, so fixing any of them can trigger a reload
+fn compile_program<F>(
+    gl: &Gl,
+    read_file: F,
+    program_name: &str,
+    source: &mut ProgramSource,
+) -> Result<ShaderProgram, ShaderError>
+where
+    F: Fn(&str) -> Result<String, Error>,
+{
+    let mut built_shaders: Vec<Shader> = Vec::with_capacity(source.shaders.len());
+    // Stages after a failing one are never read, they are watched all the same
+    let files = RefCell::new(
+        source
+            .shaders
+            .iter()
+            .map(|(path, _)| path.to_owned())
+            .collect::<Vec<_>>(),
+    );
+
+    let mut preprocessor = Preprocessor::new(|path: &str| {
+        files.borrow_mut().push(path.to_owned());
+        read_file(path)
+    });
+    for (name, value) in &source.defines {
+        preprocessor.define(name, value);
     }
 
-    pub fn finish(&mut self) -> ShaderManager {
-        let mut hash_copy: std::collections::HashMap<&'static str, shader_program::ShaderProgram> =
-            std::collections::HashMap::new();
+    let result = source
+        .shaders
+        .iter()
+        .try_for_each(|(asset_path, shader_type)| {
+            let shader_type = *shader_type;
+            let preprocessed =
+                preprocessor
+                    .process(asset_path)
+                    .map_err(|error| ShaderError::Preprocess {
+                        program: program_name.to_owned(),
+                        path: asset_path.to_owned(),
+                        error,
+                    })?;
 
-        self.programs.iter().for_each(|(key, value)| {
-            hash_copy
-                .entry(key)
-                .or_insert(shader_program::ShaderProgram::from(value));
+            let shader_source = CString::new(preprocessed.source.as_str()).map_err(|_| {
+                ShaderError::SourceContainsNil {
+                    program: program_name.to_owned(),
+                    path: asset_path.to_owned(),
+                }
+            })?;
+            let shader = Shader::from_source(gl, &shader_source, shader_type).map_err(|log| {
+                ShaderError::Compile {
+                    program: program_name.to_owned(),
+                    stage: shader_type,
+                    path: asset_path.to_owned(),
+                    log: error::parse_info_log(&log, &preprocessed.files),
+                }
+            })?;
+
+            built_shaders.push(shader);
+            Ok(())
         });
-        ShaderManager::new(hash_copy)
+
+    let program = result.and_then(|_| {
+        ShaderProgram::from_shaders(gl, &built_shaders).map_err(|log| ShaderError::Link {
+            program: program_name.to_owned(),
+            log: error::parse_info_log(&log, &[]),
+        })
+    });
+
+    for shader in &built_shaders {
+        gl.drop_shader(shader.get_id());
     }
+
+    let mut files = files.into_inner();
+    files.sort();
+    files.dedup();
+    source.files = files;
+
+    program
 }
 
-pub struct ShaderManager {
-    programs: std::collections::HashMap<&'static str, shader_program::ShaderProgram>,
+impl ShaderManager {
+    pub fn get_handle(&self, program_name: &str) -> Option<Handle<ShaderProgram>> {
+        self.programs.get_handle(program_name)
+    }
+
+    pub fn get_name(&self, handle: &Handle<ShaderProgram>) -> &str {
+        self.programs.get_name(handle).unwrap_or_default()
+    }
+
+    /// Handle to `program_name`, whether it is loaded or not. Drawing falls back to the
+    /// error program until it is loaded
+    pub fn reserve(&mut self, program_name: &str) -> Handle<ShaderProgram> {
+        self.programs.reserve(program_name)
+    }
+
+    /// Same as `get_handle`, but falls back to the error shader
+    pub fn get_handle_or_error(&self, program_name: &str) -> Handle<ShaderProgram> {
+        self.programs
+            .get_handle(program_name)
+            .filter(|handle| self.programs.is_loaded(handle))
+            .unwrap_or_else(|| self.error_program.clone())
+    }
+
+    pub fn get_shader_program(
+        &self,
+        handle: &Handle<ShaderProgram>,
+    ) -> Result<&ShaderProgram, Error> {
+        self.programs
+            .get(handle)
+            .ok_or_else(|| Error::MissingAsset {
+                name: self
+                    .programs
+                    .get_name(handle)
+                    .unwrap_or_default()
+                    .to_owned(),
+            })
+    }
+
+    /// Same as `get_shader_program`, but falls back to the error shader
+    pub fn get_shader_program_or_error(&self, handle: &Handle<ShaderProgram>) -> &ShaderProgram {
+        self.programs
+            .get(handle)
+            .or_else(|| self.programs.get(&self.error_program))
+            .expect("error program is persistent")
+    }
+
+    /// Persistent programs stay loaded without any material using them
+    pub fn set_persistent(&mut self, handle: &Handle<ShaderProgram>, persistent: bool) {
+        self.programs.set_persistent(handle, persistent);
+    }
+
+    /// Deletes every program no material holds a handle to anymore
+    pub fn unload_unused(&mut self, gl: &Gl) {
+        for (name, program) in self.programs.unload_unused() {
+            gl.drop_program(program.get_id());
+            self.sources.remove(&name);
+            println!("Shader \"{}\" - Unloaded", name);
+        }
+    }
 }
 
+/// Hot reloading
 impl ShaderManager {
-    pub fn new(
-        programs: std::collections::HashMap<&'static str, shader_program::ShaderProgram>,
-    ) -> ShaderManager {
-        ShaderManager { programs }
+    /// Absolute paths of every file a program was built from
+    pub fn get_watched_files(&self) -> Vec<(Handle<ShaderProgram>, std::path::PathBuf)> {
+        self.programs
+            .handles()
+            .into_iter()
+            .filter_map(|(handle, name)| Some((handle, self.sources.get(name)?)))
+            .flat_map(|(handle, source)| {
+                source
+                    .files
+                    .iter()
+                    .filter_map(move |file| Some((handle.clone(), self.loader.get_path(file)?)))
+            })
+            .collect()
     }
 
-    pub fn get_shader_program(&self, program_name: &'static str) -> &shader_program::ShaderProgram {
-        &self.programs.get(program_name).unwrap()
+    /// Recompiles the program from its files and drops the old one. The old program is
+    /// kept if compilation fails. Returns `false` for programs not built from files
+    pub fn reload_program(
+        &mut self,
+        gl: &Gl,
+        handle: &Handle<ShaderProgram>,
+    ) -> Result<bool, Error> {
+        let program_name = match self.programs.get_name(handle) {
+            Some(name) => name.to_owned(),
+            None => return Ok(false),
+        };
+        let source = match self.sources.get_mut(&program_name) {
+            Some(source) => source,
+            None => return Ok(false),
+        };
+
+        let loader = &self.loader;
+        // Files are recorded even when compilation fails, so fixing any of them triggers
+        // the next reload
+        let program = compile_program(
+            gl,
+            |path: &str| loader.load_as_string(path),
+            &program_name,
+            source,
+        )?;
+
+        if let Some(old) = self.programs.set(handle, program) {
+            gl.drop_program(old.get_id());
+        }
+        Ok(true)
     }
 }
@@ -0,0 +1,404 @@
+use crate::loader::Error;
+
+use std::fmt;
+
+/// Errors produced while expanding `#include` directives
+#[derive(Debug)]
+pub enum PreprocessError {
+    Read { path: String, error: Box<Error> },
+    IncludeCycle { chain: Vec<String> },
+    MalformedInclude { path: String, line: usize },
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreprocessError::Read { path, error } => {
+                write!(f, "failed to read {}: {}", path, error)
+            }
+            PreprocessError::IncludeCycle { chain } => {
+                write!(f, "include cycle {}", chain.join(" -> "))
+            }
+            PreprocessError::MalformedInclude { path, line } => write!(
+                f,
+                "{}:{}: malformed #include, expected #include \"path\"",
+                path, line
+            ),
+        }
+    }
+}
+
+/// Fully expanded shader source. `#line` directives reference files by their index in `files`
+#[derive(Debug, Clone)]
+pub struct PreprocessedSource {
+    pub source: String,
+    pub files: Vec<String>,
+}
+
+impl PreprocessedSource {
+    /// Resolves the file index used in `#line` directives back to the file path
+    pub fn get_file(&self, index: usize) -> Option<&str> {
+        self.files.get(index).map(|file| file.as_str())
+    }
+}
+
+/// Expands `#include "path"` directives, injects `#define`s right after `#version`
+/// and emits `#line` directives so driver errors point at the original file and line.
+/// Include paths are relative to the including file, a leading `/` makes them relative
+/// to the asset root. Every file is included at most once, later includes of the same
+/// file are skipped. Files are fetched through `read_file`, keeping the preprocessor
+/// free of any io
+pub struct Preprocessor<F>
+where
+    F: Fn(&str) -> Result<String, Error>,
+{
+    read_file: F,
+    defines: Vec<(String, String)>,
+}
+
+impl<F> Preprocessor<F>
+where
+    F: Fn(&str) -> Result<String, Error>,
+{
+    pub fn new(read_file: F) -> Self {
+        Preprocessor {
+            read_file,
+            defines: vec![],
+        }
+    }
+
+    pub fn define(&mut self, name: &str, value: &str) -> &mut Self {
+        self.defines.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn process(&self, path: &str) -> Result<PreprocessedSource, PreprocessError> {
+        let mut output = PreprocessedSource {
+            source: String::new(),
+            files: vec![],
+        };
+        let mut stack = vec![];
+
+        self.expand(path, &mut stack, &mut output, true)?;
+
+        Ok(output)
+    }
+
+    fn expand(
+        &self,
+        path: &str,
+        stack: &mut Vec<String>,
+        output: &mut PreprocessedSource,
+        is_root: bool,
+    ) -> Result<(), PreprocessError> {
+        if stack.iter().any(|included| included == path) {
+            let mut chain = stack.clone();
+            chain.push(path.to_owned());
+            return Err(PreprocessError::IncludeCycle { chain });
+        }
+        if output.files.iter().any(|included| included == path) {
+            return Ok(());
+        }
+
+        let source = (self.read_file)(path).map_err(|error| PreprocessError::Read {
+            path: path.to_owned(),
+            error: Box::new(error),
+        })?;
+
+        let file_index = output.files.len();
+        output.files.push(path.to_owned());
+        stack.push(path.to_owned());
+
+        // GLSL wants `#version` before anything else, so the root file's one is moved to the
+        // top even when comments or blank lines precede it
+        let version_line = if is_root {
+            source
+                .lines()
+                .position(|line| line.trim_start().starts_with("#version"))
+        } else {
+            None
+        };
+        match version_line {
+            Some(index) => {
+                output
+                    .source
+                    .push_str(source.lines().nth(index).unwrap_or_default());
+                output.source.push('\n');
+                let first_line = if index == 0 { 2 } else { 1 };
+                self.write_header(output, file_index, first_line, true);
+            }
+            None => self.write_header(output, file_index, 1, is_root),
+        }
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let trimmed = line.trim_start();
+
+            if trimmed.starts_with("#version") {
+                if index > 0 && version_line == Some(index) {
+                    output
+                        .source
+                        .push_str(&format!("#line {} {}\n", line_number + 1, file_index));
+                }
+                continue;
+            }
+
+            if let Some(rest) = trimmed.strip_prefix("#include") {
+                let include_path =
+                    parse_include_path(rest).ok_or_else(|| PreprocessError::MalformedInclude {
+                        path: path.to_owned(),
+                        line: line_number,
+                    })?;
+
+                let include_path = resolve_include(path, include_path);
+                self.expand(&include_path, stack, output, false)?;
+                output
+                    .source
+                    .push_str(&format!("#line {} {}\n", line_number + 1, file_index));
+                continue;
+            }
+
+            output.source.push_str(line);
+            output.source.push('\n');
+        }
+
+        stack.pop();
+
+        Ok(())
+    }
+
+    fn write_header(
+        &self,
+        output: &mut PreprocessedSource,
+        file_index: usize,
+        line: usize,
+        with_defines: bool,
+    ) {
+        if with_defines {
+            for (name, value) in &self.defines {
+                output
+                    .source
+                    .push_str(&format!("#define {} {}\n", name, value));
+            }
+        }
+        output
+            .source
+            .push_str(&format!("#line {} {}\n", line, file_index));
+    }
+}
+
+fn parse_include_path(rest: &str) -> Option<&str> {
+    let rest = rest.trim();
+    let rest = rest.strip_prefix('"')?;
+    let end = rest.find('"')?;
+
+    if rest[end + 1..].trim().is_empty() && end > 0 {
+        Some(&rest[..end])
+    } else {
+        None
+    }
+}
+
+/// Path of `include` as seen from the file at `from`, with `.` and `..` folded in
+fn resolve_include(from: &str, include: &str) -> String {
+    let mut parts: Vec<&str> = match include.strip_prefix('/') {
+        Some(_) => vec![],
+        None => from.split('/').collect(),
+    };
+    // Drop the file name of the including file
+    parts.pop();
+
+    for part in include.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn files(files: &[(&str, &str)]) -> impl Fn(&str) -> Result<String, Error> {
+        let files: HashMap<String, String> = files
+            .iter()
+            .map(|(path, source)| (path.to_string(), source.to_string()))
+            .collect();
+        move |path: &str| {
+            files.get(path).cloned().ok_or_else(|| Error::MissingAsset {
+                name: path.to_owned(),
+            })
+        }
+    }
+
+    /// The source without `#line` directives
+    fn code(source: &PreprocessedSource) -> Vec<&str> {
+        source
+            .source
+            .lines()
+            .filter(|line| !line.starts_with("#line"))
+            .collect()
+    }
+
+    #[test]
+    fn expands_nested_includes_relative_to_the_including_file() {
+        let preprocessor = Preprocessor::new(files(&[
+            ("shaders/main.frag", "#include \"lib/a.glsl\"\nmain"),
+            ("shaders/lib/a.glsl", "#include \"b.glsl\"\na"),
+            ("shaders/lib/b.glsl", "#include \"../common.glsl\"\nb"),
+            ("shaders/common.glsl", "#include \"/root.glsl\"\ncommon"),
+            ("root.glsl", "root"),
+        ]));
+        let output = preprocessor.process("shaders/main.frag").unwrap();
+
+        assert_eq!(code(&output), vec!["root", "common", "b", "a", "main"]);
+        assert_eq!(
+            output.files,
+            vec![
+                "shaders/main.frag",
+                "shaders/lib/a.glsl",
+                "shaders/lib/b.glsl",
+                "shaders/common.glsl",
+                "root.glsl",
+            ]
+        );
+    }
+
+    #[test]
+    fn includes_shared_files_once() {
+        let preprocessor = Preprocessor::new(files(&[
+            (
+                "main.frag",
+                "#include \"a.glsl\"\n#include \"b.glsl\"\nmain",
+            ),
+            ("a.glsl", "#include \"common.glsl\"\na"),
+            ("b.glsl", "#include \"./common.glsl\"\nb"),
+            ("common.glsl", "common"),
+        ]));
+        let output = preprocessor.process("main.frag").unwrap();
+
+        assert_eq!(code(&output), vec!["common", "a", "b", "main"]);
+        assert_eq!(output.files.len(), 4);
+    }
+
+    #[test]
+    fn detects_include_cycles() {
+        let preprocessor = Preprocessor::new(files(&[
+            ("main.frag", "#include \"a.glsl\""),
+            ("a.glsl", "#include \"b.glsl\""),
+            ("b.glsl", "#include \"a.glsl\""),
+        ]));
+
+        match preprocessor.process("main.frag") {
+            Err(PreprocessError::IncludeCycle { chain }) => {
+                assert_eq!(chain, vec!["main.frag", "a.glsl", "b.glsl", "a.glsl"])
+            }
+            other => panic!("expected a cycle, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reports_missing_and_malformed_includes() {
+        let preprocessor = Preprocessor::new(files(&[
+            ("missing.frag", "#include \"nowhere.glsl\""),
+            ("malformed.frag", "void main()\n#include nowhere.glsl"),
+        ]));
+
+        match preprocessor.process("missing.frag") {
+            Err(PreprocessError::Read { path, .. }) => assert_eq!(path, "nowhere.glsl"),
+            other => panic!("expected a read error, got {:?}", other),
+        }
+        match preprocessor.process("malformed.frag") {
+            Err(PreprocessError::MalformedInclude { path, line }) => {
+                assert_eq!((path.as_str(), line), ("malformed.frag", 2))
+            }
+            other => panic!("expected a malformed include, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn line_directives_map_back_to_files_and_lines() {
+        let preprocessor = Preprocessor::new(files(&[
+            (
+                "main.frag",
+                "#version 330\nfirst\n#include \"lib.glsl\"\nlast",
+            ),
+            ("lib.glsl", "one\ntwo"),
+        ]));
+        let output = preprocessor.process("main.frag").unwrap();
+
+        assert_eq!(
+            output.source.lines().collect::<Vec<_>>(),
+            vec![
+                "#version 330",
+                "#line 2 0",
+                "first",
+                "#line 1 1",
+                "one",
+                "two",
+                "#line 4 0",
+                "last",
+            ]
+        );
+        assert_eq!(output.get_file(1), Some("lib.glsl"));
+        assert_eq!(output.get_file(2), None);
+    }
+
+    #[test]
+    fn injects_defines_after_version() {
+        let mut preprocessor = Preprocessor::new(files(&[
+            ("versioned.frag", "\n#version 330 core\nmain"),
+            ("plain.frag", "main"),
+        ]));
+        preprocessor.define("LIGHTS", "4").define("FOG", "1");
+
+        let versioned = preprocessor.process("versioned.frag").unwrap();
+        assert_eq!(
+            versioned.source.lines().collect::<Vec<_>>(),
+            vec![
+                "#version 330 core",
+                "#define LIGHTS 4",
+                "#define FOG 1",
+                "#line 1 0",
+                "",
+                "#line 3 0",
+                "main",
+            ]
+        );
+
+        let plain = preprocessor.process("plain.frag").unwrap();
+        assert_eq!(
+            plain.source.lines().collect::<Vec<_>>(),
+            vec!["#define LIGHTS 4", "#define FOG 1", "#line 1 0", "main"]
+        );
+    }
+
+    #[test]
+    fn moves_version_above_leading_comments() {
+        let mut preprocessor = Preprocessor::new(files(&[(
+            "commented.frag",
+            "// Lit surface\n/* shared by every mesh */\n#version 330 core\nmain",
+        )]));
+        preprocessor.define("LIGHTS", "4");
+        let output = preprocessor.process("commented.frag").unwrap();
+
+        assert_eq!(
+            output.source.lines().collect::<Vec<_>>(),
+            vec![
+                "#version 330 core",
+                "#define LIGHTS 4",
+                "#line 1 0",
+                "// Lit surface",
+                "/* shared by every mesh */",
+                "#line 4 0",
+                "main",
+            ]
+        );
+    }
+}
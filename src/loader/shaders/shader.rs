@@ -7,11 +7,19 @@ pub struct Shader {
 }
 
 impl Shader {
-    pub fn from_source(gl: &Gl, source: &CString, shader_type: gl::types::GLenum) -> Shader {
+    /// Compiles the shader, returning the driver info log on failure
+    pub fn from_source(
+        gl: &Gl,
+        source: &CString,
+        shader_type: gl::types::GLenum,
+    ) -> Result<Shader, String> {
         let id = gl.create_shader(shader_type);
-        gl.compile_shader(id, source);
+        if let Err(log) = gl.compile_shader(id, source) {
+            gl.drop_shader(id);
+            return Err(log);
+        }
 
-        Shader { id }
+        Ok(Shader { id })
     }
 }
 
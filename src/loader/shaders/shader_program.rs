@@ -1,23 +1,71 @@
-use crate::vxl_gl::{gl, Gl};
+use std::{collections::HashMap, fmt, sync::Arc};
+
+use crate::vxl_gl::{gl, ActiveUniform, Gl, UniformValue};
 
 use super::shader::Shader;
 
+#[derive(Debug)]
+pub enum UniformError {
+    UnknownUniform {
+        name: String,
+    },
+    TypeMismatch {
+        name: String,
+        expected: gl::types::GLenum,
+        found: &'static str,
+    },
+}
+
+impl fmt::Display for UniformError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UniformError::UnknownUniform { name } => {
+                write!(f, "the program has no uniform \"{}\"", name)
+            }
+            UniformError::TypeMismatch {
+                name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "uniform \"{}\" has gl type {:#x}, got a {}",
+                name, expected, found
+            ),
+        }
+    }
+}
+
 pub struct ShaderProgram {
     id: gl::types::GLuint,
+    uniforms: Arc<HashMap<String, ActiveUniform>>,
 }
 
 impl ShaderProgram {
-    pub fn from_shaders(gl: &Gl, shaders: &Vec<Shader>) -> ShaderProgram {
+    /// Links the program, returning the driver info log on failure
+    pub fn from_shaders(gl: &Gl, shaders: &[Shader]) -> Result<ShaderProgram, String> {
         let id: gl::types::GLuint = gl.create_shader_program();
         for shader in shaders {
             gl.attach_shader(id, shader.get_id());
         }
-        gl.link_program(id);
+        let linked = gl.link_program(id);
         for shader in shaders {
             gl.detach_shader(id, shader.get_id());
         }
+        if let Err(log) = linked {
+            gl.drop_program(id);
+            return Err(log);
+        }
+
+        let uniforms = gl
+            .get_active_uniforms(id)
+            .into_iter()
+            .map(|uniform| (uniform.name.clone(), uniform))
+            .collect();
 
-        ShaderProgram { id }
+        Ok(ShaderProgram {
+            id,
+            uniforms: Arc::new(uniforms),
+        })
     }
 }
 
@@ -25,10 +73,49 @@ impl ShaderProgram {
     pub fn get_id(&self) -> gl::types::GLuint {
         self.id
     }
+
+    /// Uniforms reflected from the program after linking
+    pub fn get_uniforms(&self) -> Arc<HashMap<String, ActiveUniform>> {
+        Arc::clone(&self.uniforms)
+    }
+
+    pub fn check_uniform(&self, name: &str, value: &UniformValue) -> Result<(), UniformError> {
+        check_uniform(&self.uniforms, name, value)
+    }
+
+    /// Program that was never linked, for testing code that only reads the uniforms
+    #[cfg(test)]
+    pub(crate) fn from_uniforms(uniforms: Vec<ActiveUniform>) -> ShaderProgram {
+        let uniforms = uniforms
+            .into_iter()
+            .map(|uniform| (uniform.name.clone(), uniform))
+            .collect();
+        ShaderProgram {
+            id: 0,
+            uniforms: Arc::new(uniforms),
+        }
+    }
 }
 
-impl From<&ShaderProgram> for ShaderProgram {
-    fn from(val: &ShaderProgram) -> Self {
-        ShaderProgram { id: val.get_id() }
+/// Validates `value` against the reflected uniform table of a program
+pub fn check_uniform(
+    uniforms: &HashMap<String, ActiveUniform>,
+    name: &str,
+    value: &UniformValue,
+) -> Result<(), UniformError> {
+    let uniform = uniforms
+        .get(name)
+        .ok_or_else(|| UniformError::UnknownUniform {
+            name: name.to_owned(),
+        })?;
+
+    if value.matches_gl_type(uniform.gl_type) {
+        Ok(())
+    } else {
+        Err(UniformError::TypeMismatch {
+            name: name.to_owned(),
+            expected: uniform.gl_type,
+            found: value.type_name(),
+        })
     }
 }
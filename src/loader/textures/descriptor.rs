@@ -0,0 +1,87 @@
+use super::sampler::{Filter, MipFilter, Wrap};
+
+/// Describes how an image is uploaded into a GL texture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureDescriptor {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub mip_filter: MipFilter,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    /// Upload as `SRGB8_ALPHA8` so sampling returns linear colors
+    pub srgb: bool,
+    pub generate_mips: bool,
+    pub max_lod: f32,
+    /// Size of a square atlas tile in pixels. When set, mips are generated on the cpu
+    /// and stop at one pixel per tile, so neighbouring tiles never bleed into each other
+    pub atlas_tile_size: Option<u32>,
+}
+
+impl Default for TextureDescriptor {
+    fn default() -> Self {
+        TextureDescriptor {
+            min_filter: Filter::Nearest,
+            mag_filter: Filter::Nearest,
+            mip_filter: MipFilter::Nearest,
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            srgb: false,
+            generate_mips: true,
+            max_lod: 1000.0,
+            atlas_tile_size: None,
+        }
+    }
+}
+
+impl TextureDescriptor {
+    /// Descriptor for a block atlas made of `tile_size`x`tile_size` tiles
+    pub fn atlas(tile_size: u32) -> Self {
+        TextureDescriptor {
+            wrap_s: Wrap::ClampToEdge,
+            wrap_t: Wrap::ClampToEdge,
+            atlas_tile_size: Some(tile_size),
+            ..TextureDescriptor::default()
+        }
+    }
+
+    pub fn with_filter(mut self, min_filter: Filter, mag_filter: Filter) -> Self {
+        self.min_filter = min_filter;
+        self.mag_filter = mag_filter;
+        self
+    }
+
+    pub fn with_mip_filter(mut self, mip_filter: MipFilter) -> Self {
+        self.mip_filter = mip_filter;
+        self
+    }
+
+    pub fn with_wrap(mut self, wrap_s: Wrap, wrap_t: Wrap) -> Self {
+        self.wrap_s = wrap_s;
+        self.wrap_t = wrap_t;
+        self
+    }
+
+    pub fn with_srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+
+    pub fn with_generate_mips(mut self, generate_mips: bool) -> Self {
+        self.generate_mips = generate_mips;
+        self
+    }
+
+    pub fn with_max_lod(mut self, max_lod: f32) -> Self {
+        self.max_lod = max_lod;
+        self
+    }
+
+    /// Mip filter that is actually applied, mips are ignored when they are not generated
+    pub fn effective_mip_filter(&self) -> MipFilter {
+        if self.generate_mips {
+            self.mip_filter
+        } else {
+            MipFilter::None
+        }
+    }
+}
@@ -0,0 +1,177 @@
+/// Single level of a cpu generated mip chain, tightly packed rgba8
+pub struct MipLevel {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// Builds the mip chain (excluding level 0) of an atlas made of `tile_size`x`tile_size` tiles.
+/// Every texel is averaged only from texels of its own tile and the chain stops once a tile
+/// is a single pixel, so lower levels never mix colors of neighbouring tiles
+pub fn generate_atlas_mips(width: u32, height: u32, data: &[u8], tile_size: u32) -> Vec<MipLevel> {
+    let mut levels = vec![];
+    if tile_size < 2 || !tile_size.is_power_of_two() {
+        return levels;
+    }
+
+    let mut current = MipLevel {
+        width,
+        height,
+        data: data.to_vec(),
+    };
+    let mut tile = tile_size;
+
+    while tile > 1 && current.width >= 2 && current.height >= 2 {
+        current = downsample(&current);
+        tile /= 2;
+        levels.push(MipLevel {
+            width: current.width,
+            height: current.height,
+            data: current.data.clone(),
+        });
+    }
+
+    levels
+}
+
+/// 2x2 box filter weighted by alpha, fully transparent texels do not darken the result
+fn downsample(level: &MipLevel) -> MipLevel {
+    let width = (level.width / 2).max(1);
+    let height = (level.height / 2).max(1);
+    let mut data = Vec::with_capacity((width * height * 4) as usize);
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut color = [0u32; 3];
+            let mut alpha = 0u32;
+            let mut fallback = [0u32; 3];
+
+            for (dx, dy) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+                let sx = (x * 2 + dx).min(level.width - 1);
+                let sy = (y * 2 + dy).min(level.height - 1);
+                let offset = ((sy * level.width + sx) * 4) as usize;
+                let texel = &level.data[offset..offset + 4];
+                let a = texel[3] as u32;
+
+                for channel in 0..3 {
+                    color[channel] += texel[channel] as u32 * a;
+                    fallback[channel] += texel[channel] as u32;
+                }
+                alpha += a;
+            }
+
+            for channel in 0..3 {
+                let value = color[channel]
+                    .checked_div(alpha)
+                    .unwrap_or(fallback[channel] / 4);
+                data.push(value as u8);
+            }
+            data.push((alpha / 4) as u8);
+        }
+    }
+
+    MipLevel {
+        width,
+        height,
+        data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Atlas of `tile_size` tiles in a row, every tile filled with one of `colors`
+    fn atlas(tile_size: u32, colors: &[[u8; 4]]) -> (u32, u32, Vec<u8>) {
+        let width = tile_size * colors.len() as u32;
+        let mut data = vec![];
+        for _ in 0..tile_size {
+            for x in 0..width {
+                data.extend_from_slice(&colors[(x / tile_size) as usize]);
+            }
+        }
+        (width, tile_size, data)
+    }
+
+    fn texel(level: &MipLevel, x: u32, y: u32) -> [u8; 4] {
+        let offset = ((y * level.width + x) * 4) as usize;
+        let mut texel = [0; 4];
+        texel.copy_from_slice(&level.data[offset..offset + 4]);
+        texel
+    }
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    #[test]
+    fn chain_stops_at_one_pixel_tiles() {
+        let (width, height, data) = atlas(8, &[RED, BLUE]);
+        let levels = generate_atlas_mips(width, height, &data, 8);
+
+        let sizes: Vec<_> = levels.iter().map(|l| (l.width, l.height)).collect();
+        assert_eq!(sizes, vec![(8, 4), (4, 2), (2, 1)]);
+        for level in &levels {
+            assert_eq!(level.data.len(), (level.width * level.height * 4) as usize);
+        }
+    }
+
+    #[test]
+    fn tiles_do_not_bleed_into_each_other() {
+        let (width, height, data) = atlas(4, &[RED, BLUE, RED]);
+        let levels = generate_atlas_mips(width, height, &data, 4);
+
+        for level in &levels {
+            let tile = level.width / 3;
+            for y in 0..level.height {
+                for x in 0..level.width {
+                    let expected = if x / tile == 1 { BLUE } else { RED };
+                    assert_eq!(
+                        texel(level, x, y),
+                        expected,
+                        "{}x{} at {},{}",
+                        level.width,
+                        level.height,
+                        x,
+                        y
+                    );
+                }
+            }
+        }
+        // The last level holds exactly one texel per tile
+        let last = levels.last().unwrap();
+        assert_eq!((last.width, last.height), (3, 1));
+    }
+
+    #[test]
+    fn non_power_of_two_tiles_get_no_mips() {
+        let (width, height, data) = atlas(3, &[RED, BLUE]);
+        assert!(generate_atlas_mips(width, height, &data, 3).is_empty());
+
+        let (width, height, data) = atlas(6, &[RED, BLUE]);
+        assert!(generate_atlas_mips(width, height, &data, 6).is_empty());
+    }
+
+    #[test]
+    fn single_texel_tiles_get_no_mips() {
+        let (width, height, data) = atlas(1, &[RED, BLUE]);
+        assert!(generate_atlas_mips(width, height, &data, 1).is_empty());
+    }
+
+    #[test]
+    fn transparent_texels_do_not_darken() {
+        let clear = [0, 0, 0, 0];
+        let data: Vec<u8> = [RED, clear, clear, RED].concat();
+        let levels = generate_atlas_mips(2, 2, &data, 2);
+
+        assert_eq!(levels.len(), 1);
+        assert_eq!(texel(&levels[0], 0, 0), [255, 0, 0, 127]);
+    }
+
+    #[test]
+    fn fully_transparent_texels_keep_their_color() {
+        let data: Vec<u8> = [[200, 100, 0, 0]; 4].concat();
+        let levels = generate_atlas_mips(2, 2, &data, 2);
+
+        assert_eq!(texel(&levels[0], 0, 0), [200, 100, 0, 0]);
+    }
+}
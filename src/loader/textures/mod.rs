@@ -1,72 +1,410 @@
-use crate::vxl_gl::Gl;
+use crate::vxl_gl::{gl, Gl};
 
-use super::Loader;
+use std::{collections::HashMap, sync::Arc};
 
+use super::{
+    assets::{AssetManager, Handle},
+    background::{self, BackgroundLoader, LoadProgress},
+    vfs::Vfs,
+    Error, Loader,
+};
+use descriptor::TextureDescriptor;
+use sampler::{Sampler, SamplerSettings};
+use texture::Texture;
+
+pub mod descriptor;
+pub mod mipmap;
+pub mod sampler;
 pub mod texture;
 
-pub struct TextureLoader<'a> {
-    gl: &'a Gl,
+/// Name the missing texture checkerboard is stored under
+pub const MISSING_TEXTURE_NAME: &str = "missing";
+
+/// Rgba8 pixels decoded off the main thread
+type DecodedImage = (cgmath::Vector2<u32>, Vec<u8>);
+
+pub struct TextureManager {
+    textures: AssetManager<Texture>,
+    /// Image path of every texture, to load it again on changes
+    sources: HashMap<String, String>,
     loader: Loader,
-    textures: std::collections::HashMap<&'static str, texture::Texture>,
+    missing_texture: Handle<Texture>,
+    queue: BackgroundLoader<DecodedImage>,
+    /// One sampler object per distinct settings, shared by every material
+    samplers: Vec<Sampler>,
 }
 
-impl<'a> TextureLoader<'a> {
-    pub fn new(gl: &'a Gl) -> Self {
-        let loader = Loader::new("images/");
-        TextureLoader {
-            gl,
-            loader,
-            textures: std::collections::HashMap::new(),
+impl TextureManager {
+    pub fn new(gl: &Gl, vfs: &Arc<Vfs>) -> Self {
+        let mut textures = AssetManager::new();
+        let missing_texture = textures.reserve(MISSING_TEXTURE_NAME);
+        textures.set(&missing_texture, create_missing_texture(gl));
+        textures.set_persistent(&missing_texture, true);
+
+        TextureManager {
+            textures,
+            sources: HashMap::new(),
+            loader: Loader::new(vfs, "images/"),
+            missing_texture,
+            queue: BackgroundLoader::new(background::default_worker_count()),
+            samplers: Vec::new(),
         }
     }
 
-    pub fn add_texture(&mut self, path: &'static str, texture_name: &'static str) -> &mut Self {
-        let dyn_image = self.loader.load_as_image(path);
+    pub fn add_texture(
+        &mut self,
+        gl: &Gl,
+        path: &str,
+        texture_name: &str,
+    ) -> Result<Handle<Texture>, Error> {
+        self.add_texture_with_descriptor(gl, path, texture_name, TextureDescriptor::default())
+    }
 
-        let img = match dyn_image {
-            image::DynamicImage::ImageRgba8(img) => img,
-            img => img.to_rgba(),
-        };
+    /// Loads the image at `path` under `texture_name`.
+    /// A texture that is already loaded under `texture_name` is returned as is
+    pub fn add_texture_with_descriptor(
+        &mut self,
+        gl: &Gl,
+        path: &str,
+        texture_name: &str,
+        descriptor: TextureDescriptor,
+    ) -> Result<Handle<Texture>, Error> {
+        let handle = self.textures.reserve(texture_name);
+        if self.textures.is_loaded(&handle) {
+            return Ok(handle);
+        }
+
+        let (dimensions, img_data) = decode_rgba(self.loader.load_as_image(path)?);
 
-        let dim_touple = img.dimensions();
-        let dimensions = cgmath::vec2(dim_touple.0, dim_touple.1);
-        let img_data = img.into_raw();
+        self.add_texture_from_data(gl, texture_name, dimensions, &img_data, descriptor)?;
+        self.sources
+            .insert(texture_name.to_owned(), path.to_owned());
 
-        let tex_id = self.gl.create_texture();
-        self.gl.bind_texture(tex_id);
+        Ok(handle)
+    }
 
-        self.gl.set_texture_data(dimensions, img_data);
-        self.gl.generate_mipmap();
-        self.gl.unbind_texture();
+    /// Creates a texture from rgba8 pixels generated at runtime, like voxel palettes.
+    /// These have no file behind them, so they are not hot reloaded
+    pub fn add_texture_from_data(
+        &mut self,
+        gl: &Gl,
+        texture_name: &str,
+        dimensions: cgmath::Vector2<u32>,
+        data: &[u8],
+        descriptor: TextureDescriptor,
+    ) -> Result<Handle<Texture>, Error> {
+        let handle = self.textures.reserve(texture_name);
 
-        self.textures
-            .entry(texture_name)
-            .or_insert(texture::Texture::new(dimensions, tex_id));
+        gl.clear_errors();
+        let tex_id = gl.create_texture();
+        gl.bind_texture(tex_id);
+
+        upload_texture(gl, dimensions, data, &descriptor);
+        gl.unbind_texture();
 
-        self
+        if let Some(code) = gl.get_error() {
+            gl.drop_texture(tex_id);
+            return Err(Error::Gl { code });
+        }
+
+        if let Some(old) = self
+            .textures
+            .set(&handle, Texture::new(dimensions, tex_id, descriptor))
+        {
+            gl.drop_texture(old.get_id());
+        }
+
+        Ok(handle)
     }
 
-    pub fn finish(&mut self) -> TextureManager {
-        let mut textures: std::collections::HashMap<&'static str, texture::Texture> =
-            std::collections::HashMap::new();
+    /// Same as `add_texture_with_descriptor`, but the image is read and decoded on a worker
+    /// thread. The texture is created right away showing the missing checkerboard,
+    /// `upload_queued` fills in the pixels once decoding finished
+    pub fn queue_texture(
+        &mut self,
+        gl: &Gl,
+        path: &str,
+        texture_name: &str,
+        descriptor: TextureDescriptor,
+    ) -> Handle<Texture> {
+        let handle = self.textures.reserve(texture_name);
+        if self.textures.is_loaded(&handle) {
+            return handle;
+        }
 
-        self.textures.iter().for_each(|(key, value)| {
-            textures.entry(key).or_insert(texture::Texture::from(value));
+        let (dimensions, placeholder) = missing_texture_data();
+        let tex_id = gl.create_texture();
+        gl.bind_texture(tex_id);
+        upload_texture(
+            gl,
+            dimensions,
+            &placeholder,
+            &descriptor.with_generate_mips(false),
+        );
+        gl.unbind_texture();
+
+        self.textures
+            .set(&handle, Texture::new(dimensions, tex_id, descriptor));
+        self.sources
+            .insert(texture_name.to_owned(), path.to_owned());
+
+        let loader = self.loader.clone();
+        let path = path.to_owned();
+        self.queue.queue(texture_name, move || {
+            Ok(decode_rgba(loader.load_as_image(&path)?))
         });
-        TextureManager::new(textures)
+
+        handle
+    }
+
+    /// Uploads the queued textures that finished decoding since the last call,
+    /// call it once per frame. Textures that failed keep showing the checkerboard
+    pub fn upload_queued(&mut self, gl: &Gl) -> Vec<Error> {
+        let mut errors = vec![];
+        for (name, result) in self.queue.poll() {
+            let texture = match self
+                .textures
+                .get_handle(&name)
+                .and_then(|handle| self.textures.get_mut(&handle))
+            {
+                Some(texture) => texture,
+                // Unloaded while it was decoding
+                None => continue,
+            };
+
+            let uploaded = result.and_then(|(dimensions, data)| {
+                replace_texture_data(gl, texture, dimensions, &data)
+            });
+            if let Err(err) = uploaded {
+                errors.push(err);
+            }
+        }
+
+        errors
+    }
+
+    pub fn get_load_progress(&self) -> &LoadProgress {
+        self.queue.get_progress()
+    }
+
+    /// Loads a texture named at runtime, e.g. by a config or world file, using its path as name
+    pub fn get_or_load(&mut self, gl: &Gl, path: &str) -> Result<Handle<Texture>, Error> {
+        self.add_texture(gl, path, path)
     }
 }
 
-pub struct TextureManager {
-    textures: std::collections::HashMap<&'static str, texture::Texture>,
+/// Magenta and black checkerboard used in place of textures that failed to load
+fn create_missing_texture(gl: &Gl) -> Texture {
+    let (dimensions, data) = missing_texture_data();
+    let descriptor = TextureDescriptor::default().with_generate_mips(false);
+    let tex_id = gl.create_texture();
+    gl.bind_texture(tex_id);
+    upload_texture(gl, dimensions, &data, &descriptor);
+    gl.unbind_texture();
+
+    Texture::new(dimensions, tex_id, descriptor)
+}
+
+fn missing_texture_data() -> DecodedImage {
+    const SIZE: u32 = 8;
+    let mut data = Vec::with_capacity((SIZE * SIZE * 4) as usize);
+    for y in 0..SIZE {
+        for x in 0..SIZE {
+            if (x / 4 + y / 4) % 2 == 0 {
+                data.extend_from_slice(&[255, 0, 255, 255]);
+            } else {
+                data.extend_from_slice(&[0, 0, 0, 255]);
+            }
+        }
+    }
+
+    (cgmath::vec2(SIZE, SIZE), data)
+}
+
+fn decode_rgba(dyn_image: image::DynamicImage) -> DecodedImage {
+    let img = match dyn_image {
+        image::DynamicImage::ImageRgba8(img) => img,
+        img => img.to_rgba(),
+    };
+
+    let dim_touple = img.dimensions();
+    (cgmath::vec2(dim_touple.0, dim_touple.1), img.into_raw())
 }
 
+/// Uploads rgba8 pixels with their mip chain into the currently bound texture
+/// and applies the sampling parameters of `descriptor`
+pub fn upload_texture(
+    gl: &Gl,
+    dimensions: cgmath::Vector2<u32>,
+    data: &[u8],
+    descriptor: &TextureDescriptor,
+) {
+    let internal_format = if descriptor.srgb {
+        gl::SRGB8_ALPHA8
+    } else {
+        gl::RGBA8
+    };
+    gl.set_texture_data(0, internal_format, dimensions, data);
+
+    if descriptor.generate_mips {
+        match descriptor.atlas_tile_size {
+            Some(tile_size) => {
+                let levels =
+                    mipmap::generate_atlas_mips(dimensions.x, dimensions.y, data, tile_size);
+                for (index, level) in levels.iter().enumerate() {
+                    gl.set_texture_data(
+                        index as i32 + 1,
+                        internal_format,
+                        cgmath::vec2(level.width, level.height),
+                        &level.data,
+                    );
+                }
+                gl.set_texture_parameter(gl::TEXTURE_MAX_LEVEL, levels.len() as gl::types::GLenum);
+            }
+            None => gl.generate_mipmap(),
+        }
+    } else {
+        gl.set_texture_parameter(gl::TEXTURE_MAX_LEVEL, 0);
+    }
+
+    gl.set_texture_parameter(
+        gl::TEXTURE_MIN_FILTER,
+        descriptor
+            .min_filter
+            .to_gl_min(descriptor.effective_mip_filter()),
+    );
+    gl.set_texture_parameter(gl::TEXTURE_MAG_FILTER, descriptor.mag_filter.to_gl_mag());
+    gl.set_texture_parameter(gl::TEXTURE_WRAP_S, descriptor.wrap_s.to_gl());
+    gl.set_texture_parameter(gl::TEXTURE_WRAP_T, descriptor.wrap_t.to_gl());
+    gl.set_texture_parameter_f(gl::TEXTURE_MAX_LOD, descriptor.max_lod);
+}
+
+impl TextureManager {
+    pub fn get_handle(&self, texture_name: &str) -> Option<Handle<Texture>> {
+        self.textures.get_handle(texture_name)
+    }
+
+    pub fn get_name(&self, handle: &Handle<Texture>) -> &str {
+        self.textures.get_name(handle).unwrap_or_default()
+    }
+
+    /// Same as `get_handle`, but falls back to the missing texture checkerboard
+    pub fn get_handle_or_missing(&self, texture_name: &str) -> Handle<Texture> {
+        self.textures
+            .get_handle(texture_name)
+            .filter(|handle| self.textures.is_loaded(handle))
+            .unwrap_or_else(|| self.missing_texture.clone())
+    }
+
+    pub fn get_texture(&self, handle: &Handle<Texture>) -> Result<&Texture, Error> {
+        self.textures
+            .get(handle)
+            .ok_or_else(|| Error::MissingAsset {
+                name: self
+                    .textures
+                    .get_name(handle)
+                    .unwrap_or_default()
+                    .to_owned(),
+            })
+    }
+
+    /// Same as `get_texture`, but falls back to the missing texture checkerboard
+    pub fn get_texture_or_missing(&self, handle: &Handle<Texture>) -> &Texture {
+        self.textures
+            .get(handle)
+            .or_else(|| self.textures.get(&self.missing_texture))
+            .expect("missing texture is persistent")
+    }
+
+    /// Persistent textures stay loaded without any material using them
+    pub fn set_persistent(&mut self, handle: &Handle<Texture>, persistent: bool) {
+        self.textures.set_persistent(handle, persistent);
+    }
+
+    /// Sampler with `settings`, created on first use and reused by every later call
+    pub fn get_sampler(&mut self, gl: &Gl, settings: SamplerSettings) -> &Sampler {
+        let index = match self
+            .samplers
+            .iter()
+            .position(|sampler| sampler.get_settings() == settings)
+        {
+            Some(index) => index,
+            None => {
+                self.samplers.push(Sampler::new(gl, settings));
+                self.samplers.len() - 1
+            }
+        };
+        &self.samplers[index]
+    }
+
+    /// Deletes every cached sampler, only once no material is drawn with them anymore
+    pub fn drop_samplers(&mut self, gl: &Gl) {
+        for sampler in self.samplers.drain(..) {
+            gl.drop_sampler(sampler.get_id());
+        }
+    }
+
+    /// Deletes every texture no material holds a handle to anymore
+    pub fn unload_unused(&mut self, gl: &Gl) {
+        for (name, texture) in self.textures.unload_unused() {
+            gl.drop_texture(texture.get_id());
+            self.sources.remove(&name);
+            println!("Texture \"{}\" - Unloaded", name);
+        }
+    }
+}
+
+/// Hot reloading
 impl TextureManager {
-    pub fn new(textures: std::collections::HashMap<&'static str, texture::Texture>) -> Self {
-        TextureManager { textures }
+    pub fn get_watched_files(&self) -> Vec<(Handle<Texture>, std::path::PathBuf)> {
+        self.textures
+            .handles()
+            .into_iter()
+            .filter_map(|(handle, name)| {
+                let path = self.sources.get(name)?;
+                Some((handle, self.loader.get_path(path)?))
+            })
+            .collect()
+    }
+
+    /// Decodes the image again and uploads it into the same gl texture,
+    /// so materials referencing it see the new pixels without being touched
+    pub fn reload_texture(&mut self, gl: &Gl, handle: &Handle<Texture>) -> Result<(), Error> {
+        let path = match self
+            .textures
+            .get_name(handle)
+            .and_then(|name| self.sources.get(name))
+        {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let (dimensions, img_data) = decode_rgba(self.loader.load_as_image(path)?);
+
+        match self.textures.get_mut(handle) {
+            Some(texture) => replace_texture_data(gl, texture, dimensions, &img_data),
+            None => Ok(()),
+        }
     }
+}
+
+/// Uploads new pixels into the gl texture of `texture`, keeping its id and descriptor
+fn replace_texture_data(
+    gl: &Gl,
+    texture: &mut Texture,
+    dimensions: cgmath::Vector2<u32>,
+    data: &[u8],
+) -> Result<(), Error> {
+    let descriptor = texture.get_descriptor();
+
+    gl.clear_errors();
+    gl.bind_texture(texture.get_id());
+    upload_texture(gl, dimensions, data, &descriptor);
+    gl.unbind_texture();
+
+    *texture = Texture::new(dimensions, texture.get_id(), descriptor);
 
-    pub fn get_texture(&self, texture_name: &'static str) -> &texture::Texture {
-        &self.textures.get(texture_name).unwrap()
+    match gl.get_error() {
+        Some(code) => Err(Error::Gl { code }),
+        None => Ok(()),
     }
 }
@@ -0,0 +1,117 @@
+use crate::vxl_gl::{gl, Gl};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MipFilter {
+    None,
+    Nearest,
+    Linear,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrap {
+    Repeat,
+    MirroredRepeat,
+    ClampToEdge,
+    ClampToBorder,
+}
+
+impl Filter {
+    pub fn to_gl_mag(self) -> gl::types::GLenum {
+        match self {
+            Filter::Nearest => gl::NEAREST,
+            Filter::Linear => gl::LINEAR,
+        }
+    }
+
+    pub fn to_gl_min(self, mip_filter: MipFilter) -> gl::types::GLenum {
+        match (self, mip_filter) {
+            (Filter::Nearest, MipFilter::None) => gl::NEAREST,
+            (Filter::Linear, MipFilter::None) => gl::LINEAR,
+            (Filter::Nearest, MipFilter::Nearest) => gl::NEAREST_MIPMAP_NEAREST,
+            (Filter::Linear, MipFilter::Nearest) => gl::LINEAR_MIPMAP_NEAREST,
+            (Filter::Nearest, MipFilter::Linear) => gl::NEAREST_MIPMAP_LINEAR,
+            (Filter::Linear, MipFilter::Linear) => gl::LINEAR_MIPMAP_LINEAR,
+        }
+    }
+}
+
+impl Wrap {
+    pub fn to_gl(self) -> gl::types::GLenum {
+        match self {
+            Wrap::Repeat => gl::REPEAT,
+            Wrap::MirroredRepeat => gl::MIRRORED_REPEAT,
+            Wrap::ClampToEdge => gl::CLAMP_TO_EDGE,
+            Wrap::ClampToBorder => gl::CLAMP_TO_BORDER,
+        }
+    }
+}
+
+/// How a texture is sampled when it is bound to a material slot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SamplerSettings {
+    pub min_filter: Filter,
+    pub mag_filter: Filter,
+    pub mip_filter: MipFilter,
+    pub wrap_s: Wrap,
+    pub wrap_t: Wrap,
+    /// Requested anisotropy level, clamped to what the driver supports. `1.0` disables it
+    pub anisotropy: f32,
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        SamplerSettings {
+            min_filter: Filter::Nearest,
+            mag_filter: Filter::Nearest,
+            mip_filter: MipFilter::Nearest,
+            wrap_s: Wrap::Repeat,
+            wrap_t: Wrap::Repeat,
+            anisotropy: 1.0,
+        }
+    }
+}
+
+/// GL sampler object, can be shared between any number of materials
+pub struct Sampler {
+    id: gl::types::GLuint,
+    settings: SamplerSettings,
+}
+
+impl Sampler {
+    pub fn new(gl: &Gl, settings: SamplerSettings) -> Sampler {
+        let id = gl.create_sampler();
+
+        gl.set_sampler_parameter(
+            id,
+            gl::TEXTURE_MIN_FILTER,
+            settings.min_filter.to_gl_min(settings.mip_filter),
+        );
+        gl.set_sampler_parameter(id, gl::TEXTURE_MAG_FILTER, settings.mag_filter.to_gl_mag());
+        gl.set_sampler_parameter(id, gl::TEXTURE_WRAP_S, settings.wrap_s.to_gl());
+        gl.set_sampler_parameter(id, gl::TEXTURE_WRAP_T, settings.wrap_t.to_gl());
+
+        if settings.anisotropy > 1.0 {
+            if let Some(max_anisotropy) = gl.get_max_anisotropy() {
+                gl.set_sampler_anisotropy(id, settings.anisotropy.min(max_anisotropy));
+            }
+        }
+
+        Sampler { id, settings }
+    }
+}
+
+impl Sampler {
+    pub fn get_id(&self) -> gl::types::GLuint {
+        self.id
+    }
+
+    pub fn get_settings(&self) -> SamplerSettings {
+        self.settings
+    }
+}
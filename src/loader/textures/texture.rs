@@ -1,13 +1,24 @@
 use crate::vxl_gl::gl;
 
+use super::descriptor::TextureDescriptor;
+
 pub struct Texture {
     dimensions: cgmath::Vector2<u32>,
     id: gl::types::GLuint,
+    descriptor: TextureDescriptor,
 }
 
 impl Texture {
-    pub fn new(dimensions: cgmath::Vector2<u32>, id: gl::types::GLuint) -> Self {
-        Texture { id, dimensions }
+    pub fn new(
+        dimensions: cgmath::Vector2<u32>,
+        id: gl::types::GLuint,
+        descriptor: TextureDescriptor,
+    ) -> Self {
+        Texture {
+            id,
+            dimensions,
+            descriptor,
+        }
     }
 
     pub fn get_dimensions(&self) -> cgmath::Vector2<u32> {
@@ -17,13 +28,8 @@ impl Texture {
     pub fn get_id(&self) -> gl::types::GLuint {
         self.id
     }
-}
 
-impl From<&Texture> for Texture {
-    fn from(value: &Texture) -> Self {
-        Texture {
-            id: value.get_id(),
-            dimensions: value.get_dimensions(),
-        }
+    pub fn get_descriptor(&self) -> TextureDescriptor {
+        self.descriptor
     }
 }
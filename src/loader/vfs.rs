@@ -0,0 +1,321 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+    sync::Mutex,
+};
+
+use super::{pak::PakReader, Error};
+
+/// Environment variable replacing the default asset directory
+pub const ASSET_ROOT_ENV: &str = "VXL_ASSET_ROOT";
+/// Environment variable with extra mount points, separated like `PATH`
+pub const MOUNTS_ENV: &str = "VXL_MOUNTS";
+pub const ASSET_ROOT_FLAG: &str = "--asset-root";
+pub const MOUNT_FLAG: &str = "--mount";
+
+/// Source of asset files inside the virtual file system
+pub trait Mount: Send + Sync {
+    /// Reads the file at the virtual `path`, `None` if the mount does not contain it
+    fn read(&self, path: &str) -> Option<io::Result<Vec<u8>>>;
+
+    fn contains(&self, path: &str) -> bool;
+
+    /// Real file backing the virtual `path`, used to watch it for changes
+    fn system_path(&self, _path: &str) -> Option<PathBuf> {
+        None
+    }
+
+    fn describe(&self) -> String;
+}
+
+/// Plain directory on disk. Paths leaving the directory, through `..` or by being
+/// absolute, are never found
+pub struct DirectoryMount {
+    root: PathBuf,
+}
+
+impl DirectoryMount {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        DirectoryMount { root: root.into() }
+    }
+}
+
+impl Mount for DirectoryMount {
+    fn read(&self, path: &str) -> Option<io::Result<Vec<u8>>> {
+        let path = self.system_path(path)?;
+        match fs::read(path) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            result => Some(result),
+        }
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        self.system_path(path).is_some()
+    }
+
+    fn system_path(&self, path: &str) -> Option<PathBuf> {
+        let inside = Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+        if !inside {
+            return None;
+        }
+        let path = self.root.join(path);
+        if path.is_file() {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    fn describe(&self) -> String {
+        format!("directory {}", self.root.display())
+    }
+}
+
+/// Zip archive, files are addressed by their path inside the archive
+pub struct ZipMount {
+    path: PathBuf,
+    archive: Mutex<zip::ZipArchive<fs::File>>,
+}
+
+impl ZipMount {
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self, Error> {
+        let path = path.into();
+        let file = fs::File::open(&path).map_err(|error| Error::Io {
+            path: path.clone(),
+            error,
+        })?;
+        let archive = zip::ZipArchive::new(file).map_err(|error| Error::Zip {
+            path: path.clone(),
+            error,
+        })?;
+
+        Ok(ZipMount {
+            path,
+            archive: Mutex::new(archive),
+        })
+    }
+}
+
+impl ZipMount {
+    fn lock(&self) -> std::sync::MutexGuard<'_, zip::ZipArchive<fs::File>> {
+        match self.archive.lock() {
+            Ok(archive) => archive,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl Mount for ZipMount {
+    fn read(&self, path: &str) -> Option<io::Result<Vec<u8>>> {
+        let mut archive = self.lock();
+        let mut entry = archive.by_name(path).ok()?;
+
+        let mut buffer = Vec::with_capacity(entry.size() as usize);
+        Some(entry.read_to_end(&mut buffer).map(|_| buffer))
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        self.lock().by_name(path).is_ok()
+    }
+
+    fn describe(&self) -> String {
+        format!("archive {}", self.path.display())
+    }
+}
+
+/// `.pak` archive written by `vxl-pack`
+pub struct PakMount {
+    path: PathBuf,
+    reader: PakReader<fs::File>,
+}
+
+impl PakMount {
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Self, Error> {
+        let path = path.into();
+        let reader = PakReader::open(&path).map_err(|error| Error::Io {
+            path: path.clone(),
+            error,
+        })?;
+
+        Ok(PakMount { path, reader })
+    }
+}
+
+impl Mount for PakMount {
+    fn read(&self, path: &str) -> Option<io::Result<Vec<u8>>> {
+        self.reader.read(path)
+    }
+
+    fn contains(&self, path: &str) -> bool {
+        self.reader.contains(path)
+    }
+
+    fn describe(&self) -> String {
+        format!("pak {}", self.path.display())
+    }
+}
+
+/// Layered set of mounts, files in later mounts override files in earlier ones
+#[derive(Default)]
+pub struct Vfs {
+    mounts: Vec<Box<dyn Mount>>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Vfs { mounts: vec![] }
+    }
+
+    /// Builds the file system from `VXL_ASSET_ROOT`/`VXL_MOUNTS` and the
+    /// `--asset-root <dir>`/`--mount <dir|archive>` command line flags, flags win over the env.
+    /// Without either, `default_root` or else `default_asset_root()` is mounted first
+    pub fn from_env_and_args<I>(args: I, default_root: Option<PathBuf>) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut root = std::env::var_os(ASSET_ROOT_ENV).map(PathBuf::from);
+        let mut mounts: Vec<PathBuf> = std::env::var_os(MOUNTS_ENV)
+            .map(|paths| std::env::split_paths(&paths).collect())
+            .unwrap_or_default();
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == ASSET_ROOT_FLAG {
+                root = Some(flag_value(ASSET_ROOT_FLAG, args.next())?);
+            } else if arg == MOUNT_FLAG {
+                mounts.push(flag_value(MOUNT_FLAG, args.next())?);
+            }
+        }
+
+        let mut vfs = Vfs::new();
+        vfs.mount_path(root.or(default_root).unwrap_or_else(default_asset_root))?;
+        for path in mounts {
+            vfs.mount_path(path)?;
+        }
+
+        Ok(vfs)
+    }
+
+    pub fn mount<M: Mount + 'static>(&mut self, mount: M) -> &mut Self {
+        self.mounts.push(Box::new(mount));
+        self
+    }
+
+    pub fn mount_dir<P: Into<PathBuf>>(&mut self, path: P) -> &mut Self {
+        self.mount(DirectoryMount::new(path))
+    }
+
+    /// Mounts a directory, a `.pak` archive or, for any other file, a zip archive
+    pub fn mount_path<P: Into<PathBuf>>(&mut self, path: P) -> Result<&mut Self, Error> {
+        let path = path.into();
+        if path.is_dir() {
+            Ok(self.mount_dir(path))
+        } else if path.extension().is_some_and(|extension| extension == "pak") {
+            Ok(self.mount(PakMount::open(path)?))
+        } else {
+            Ok(self.mount(ZipMount::open(path)?))
+        }
+    }
+
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, Error> {
+        for mount in self.mounts.iter().rev() {
+            if let Some(result) = mount.read(path) {
+                return result.map_err(|error| Error::Io {
+                    path: PathBuf::from(path),
+                    error,
+                });
+            }
+        }
+
+        Err(Error::Io {
+            path: PathBuf::from(path),
+            error: io::Error::new(io::ErrorKind::NotFound, "not found in any mount"),
+        })
+    }
+
+    /// Real file that `read` would return for `path`, if it comes from a directory
+    pub fn system_path(&self, path: &str) -> Option<PathBuf> {
+        for mount in self.mounts.iter().rev() {
+            if let Some(system_path) = mount.system_path(path) {
+                return Some(system_path);
+            }
+            if mount.contains(path) {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    pub fn describe(&self) -> Vec<String> {
+        self.mounts.iter().map(|mount| mount.describe()).collect()
+    }
+}
+
+fn flag_value(flag: &str, value: Option<String>) -> Result<PathBuf, Error> {
+    match value {
+        Some(value) if !value.starts_with("--") => Ok(PathBuf::from(value)),
+        _ => Err(Error::MissingArgument {
+            flag: flag.to_owned(),
+        }),
+    }
+}
+
+/// Archive looked up next to the executable when no asset root is configured
+pub const DEFAULT_PAK_NAME: &str = "assets.pak";
+
+/// `assets.pak` next to the executable, or a `res/` directory there if there is no archive
+pub fn default_asset_root() -> PathBuf {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+    let pak = exe_dir.join(DEFAULT_PAK_NAME);
+    if pak.is_file() {
+        pak
+    } else {
+        exe_dir.join("res/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn res() -> DirectoryMount {
+        DirectoryMount::new(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("res/"))
+    }
+
+    #[test]
+    fn directory_mount_stays_inside_its_root() {
+        let mount = res();
+        assert!(mount.contains("config/input.cfg"));
+        assert!(mount.contains("./config/input.cfg"));
+
+        assert!(!mount.contains("../Cargo.toml"));
+        assert!(!mount.contains("config/../../Cargo.toml"));
+        assert!(mount.read("../Cargo.toml").is_none());
+
+        let absolute = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+        assert!(!mount.contains(absolute.to_str().unwrap()));
+    }
+
+    #[test]
+    fn flags_without_a_value_are_errors() {
+        for args in &[
+            vec![ASSET_ROOT_FLAG],
+            vec![MOUNT_FLAG],
+            vec![ASSET_ROOT_FLAG, MOUNT_FLAG, "extra.pak"],
+        ] {
+            let args = args.iter().map(|arg| arg.to_string());
+            match Vfs::from_env_and_args(args, None) {
+                Err(Error::MissingArgument { .. }) => {}
+                Err(other) => panic!("expected a missing argument, got {}", other),
+                Ok(_) => panic!("expected a missing argument"),
+            }
+        }
+    }
+}
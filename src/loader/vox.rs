@@ -0,0 +1,931 @@
+//! MagicaVoxel `.vox` files. Reads the SIZE, XYZI and RGBA chunks and the
+//! nTRN/nGRP/nSHP scene graph, other chunks (materials, layers, cameras) are skipped.
+//! MagicaVoxel is z-up, `to_model` converts into the y-up space of the engine
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use super::{vfs::Vfs, Error, Loader};
+use crate::voxel::model::VoxelModel;
+
+const MAGIC: &[u8; 4] = b"VOX ";
+/// 150 files have no scene graph, 200 adds it
+const MAX_VERSION: u32 = 200;
+
+#[derive(Debug)]
+pub enum VoxError {
+    BadMagic,
+    UnsupportedVersion(u32),
+    Truncated {
+        chunk: String,
+    },
+    Malformed {
+        chunk: String,
+        message: String,
+    },
+    /// The merged model spans more voxels than can be allocated
+    TooLarge {
+        size: [u32; 3],
+    },
+    /// A scene graph node is its own ancestor
+    SceneCycle {
+        node: i32,
+    },
+    /// A transform or group node has more than one parent, only shapes can be shared
+    SharedNode {
+        node: i32,
+    },
+}
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoxError::BadMagic => write!(f, "not a MagicaVoxel file"),
+            VoxError::UnsupportedVersion(version) => {
+                write!(f, "unsupported vox version {}", version)
+            }
+            VoxError::Truncated { chunk } => write!(f, "chunk {} is truncated", chunk),
+            VoxError::Malformed { chunk, message } => write!(f, "chunk {}: {}", chunk, message),
+            VoxError::TooLarge { size } => write!(f, "model of size {:?} is too large", size),
+            VoxError::SceneCycle { node } => {
+                write!(f, "scene graph node {} is its own ancestor", node)
+            }
+            VoxError::SharedNode { node } => {
+                write!(f, "scene graph node {} has more than one parent", node)
+            }
+        }
+    }
+}
+
+/// One SIZE + XYZI pair. Voxels are `[x, y, z, color index]`, color index 0 is never used
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxModelData {
+    pub size: [u32; 3],
+    pub voxels: Vec<[u8; 4]>,
+}
+
+/// Signed permutation matrix, rows of `[i8; 3]`, as packed into the `_r` attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VoxRotation(pub [[i8; 3]; 3]);
+
+impl VoxRotation {
+    pub const IDENTITY: VoxRotation = VoxRotation([[1, 0, 0], [0, 1, 0], [0, 0, 1]]);
+
+    /// Bits 0-1 and 2-3 hold the column of the first and second row's non zero entry,
+    /// bits 4-6 flip the sign of the first, second and third row
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        let first = (byte & 0b11) as usize;
+        let second = ((byte >> 2) & 0b11) as usize;
+        if first > 2 || second > 2 || first == second {
+            return None;
+        }
+        let third = 3 - first - second;
+
+        let mut rows = [[0; 3]; 3];
+        for (row, (column, sign_bit)) in [(first, 4), (second, 5), (third, 6)].iter().enumerate() {
+            rows[row][*column] = if byte & (1 << sign_bit) == 0 { 1 } else { -1 };
+        }
+
+        Some(VoxRotation(rows))
+    }
+
+    pub fn apply(&self, v: [i32; 3]) -> [i32; 3] {
+        let mut result = [0; 3];
+        for (row, value) in self.0.iter().zip(result.iter_mut()) {
+            *value = (0..3).map(|column| row[column] as i32 * v[column]).sum();
+        }
+        result
+    }
+
+    /// Matrix product `self * other`, applying `other` first
+    pub fn multiply(&self, other: &VoxRotation) -> VoxRotation {
+        let mut rows = [[0; 3]; 3];
+        for (row, result_row) in rows.iter_mut().enumerate() {
+            for (column, value) in result_row.iter_mut().enumerate() {
+                *value = (0..3)
+                    .map(|k| self.0[row][k] * other.0[k][column])
+                    .sum::<i8>();
+            }
+        }
+        VoxRotation(rows)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VoxNode {
+    Transform {
+        child: i32,
+        layer: i32,
+        translation: [i32; 3],
+        rotation: VoxRotation,
+        hidden: bool,
+    },
+    Group {
+        children: Vec<i32>,
+    },
+    Shape {
+        models: Vec<i32>,
+    },
+}
+
+/// Model placed in the scene by the transforms above it, in MagicaVoxel space
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxInstance {
+    pub model: usize,
+    pub translation: [i32; 3],
+    pub rotation: VoxRotation,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxFile {
+    pub version: u32,
+    pub models: Vec<VoxModelData>,
+    /// Rgba of every color index, entry 0 is unused
+    pub palette: Vec<[u8; 4]>,
+    pub nodes: HashMap<i32, VoxNode>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    chunk: &'a str,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8], chunk: &'a str) -> Self {
+        Reader {
+            bytes,
+            position: 0,
+            chunk,
+        }
+    }
+
+    fn take(&mut self, count: usize) -> Result<&'a [u8], VoxError> {
+        let end = self
+            .position
+            .checked_add(count)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| VoxError::Truncated {
+                chunk: self.chunk.to_owned(),
+            })?;
+        let taken = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(taken)
+    }
+
+    fn read_i32(&mut self) -> Result<i32, VoxError> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, VoxError> {
+        Ok(self.read_i32()? as u32)
+    }
+
+    fn read_count(&mut self) -> Result<usize, VoxError> {
+        let count = self.read_i32()?;
+        if count < 0 {
+            return Err(self.malformed(format!("negative count {}", count)));
+        }
+        Ok(count as usize)
+    }
+
+    fn read_string(&mut self) -> Result<String, VoxError> {
+        let length = self.read_count()?;
+        Ok(String::from_utf8_lossy(self.take(length)?).into_owned())
+    }
+
+    fn read_dict(&mut self) -> Result<HashMap<String, String>, VoxError> {
+        let count = self.read_count()?;
+        let mut dict = HashMap::with_capacity(count.min(64));
+        for _ in 0..count {
+            let key = self.read_string()?;
+            let value = self.read_string()?;
+            dict.insert(key, value);
+        }
+        Ok(dict)
+    }
+
+    fn malformed(&self, message: String) -> VoxError {
+        VoxError::Malformed {
+            chunk: self.chunk.to_owned(),
+            message,
+        }
+    }
+}
+
+impl VoxFile {
+    pub fn parse(bytes: &[u8]) -> Result<VoxFile, VoxError> {
+        let mut reader = Reader::new(bytes, "header");
+        if reader.take(4).map_err(|_| VoxError::BadMagic)? != MAGIC {
+            return Err(VoxError::BadMagic);
+        }
+        let version = reader.read_u32()?;
+        if version > MAX_VERSION {
+            return Err(VoxError::UnsupportedVersion(version));
+        }
+
+        let mut file = VoxFile {
+            version,
+            models: vec![],
+            palette: default_palette(),
+            nodes: HashMap::new(),
+        };
+        let mut pending_size: Option<[u32; 3]> = None;
+
+        // MAIN only has children, every other chunk is read flat out of it
+        reader.chunk = "MAIN";
+        if reader.take(4)? != b"MAIN" {
+            return Err(reader.malformed("first chunk is not MAIN".to_owned()));
+        }
+        let _ = reader.read_count()?;
+        let _ = reader.read_count()?;
+
+        while reader.position < bytes.len() {
+            let id = reader.take(4)?;
+            let id = std::str::from_utf8(id).unwrap_or("????");
+            let content_size = reader.read_count()?;
+            let children_size = reader.read_count()?;
+            let content = reader.take(content_size)?;
+            reader.take(children_size)?;
+
+            let mut chunk = Reader::new(content, id);
+            match id {
+                "SIZE" => {
+                    pending_size = Some([chunk.read_u32()?, chunk.read_u32()?, chunk.read_u32()?]);
+                }
+                "XYZI" => {
+                    let size = pending_size
+                        .take()
+                        .ok_or_else(|| chunk.malformed("XYZI without SIZE".to_owned()))?;
+                    let count = chunk.read_count()?;
+                    let mut voxels = Vec::with_capacity(count.min(content.len() / 4));
+                    for _ in 0..count {
+                        let voxel = chunk.take(4)?;
+                        let voxel = [voxel[0], voxel[1], voxel[2], voxel[3]];
+                        if (0..3).any(|axis| voxel[axis] as u32 >= size[axis]) {
+                            return Err(chunk.malformed(format!(
+                                "voxel {:?} outside of size {:?}",
+                                voxel, size
+                            )));
+                        }
+                        voxels.push(voxel);
+                    }
+                    file.models.push(VoxModelData { size, voxels });
+                }
+                "RGBA" => {
+                    // Entry i of the chunk is color index i + 1
+                    let mut palette = vec![[0; 4]; 256];
+                    for color in palette.iter_mut().skip(1) {
+                        let rgba = chunk.take(4)?;
+                        *color = [rgba[0], rgba[1], rgba[2], rgba[3]];
+                    }
+                    file.palette = palette;
+                }
+                "nTRN" => {
+                    let id = chunk.read_i32()?;
+                    let attributes = chunk.read_dict()?;
+                    let child = chunk.read_i32()?;
+                    let _reserved = chunk.read_i32()?;
+                    let layer = chunk.read_i32()?;
+                    let frame_count = chunk.read_count()?;
+
+                    let mut translation = [0; 3];
+                    let mut rotation = VoxRotation::IDENTITY;
+                    // Animation frames are not supported, the first frame is the pose
+                    for frame in 0..frame_count {
+                        let frame_attributes = chunk.read_dict()?;
+                        if frame > 0 {
+                            continue;
+                        }
+                        if let Some(t) = frame_attributes.get("_t") {
+                            translation = parse_translation(t)
+                                .ok_or_else(|| chunk.malformed(format!("bad _t \"{}\"", t)))?;
+                        }
+                        if let Some(r) = frame_attributes.get("_r") {
+                            rotation = r
+                                .trim()
+                                .parse()
+                                .ok()
+                                .and_then(VoxRotation::from_byte)
+                                .ok_or_else(|| chunk.malformed(format!("bad _r \"{}\"", r)))?;
+                        }
+                    }
+
+                    file.nodes.insert(
+                        id,
+                        VoxNode::Transform {
+                            child,
+                            layer,
+                            translation,
+                            rotation,
+                            hidden: attributes.get("_hidden").map(String::as_str) == Some("1"),
+                        },
+                    );
+                }
+                "nGRP" => {
+                    let id = chunk.read_i32()?;
+                    let _attributes = chunk.read_dict()?;
+                    let count = chunk.read_count()?;
+                    let children = (0..count)
+                        .map(|_| chunk.read_i32())
+                        .collect::<Result<_, _>>()?;
+                    file.nodes.insert(id, VoxNode::Group { children });
+                }
+                "nSHP" => {
+                    let id = chunk.read_i32()?;
+                    let _attributes = chunk.read_dict()?;
+                    let count = chunk.read_count()?;
+                    let mut models = Vec::with_capacity(count.min(16));
+                    for _ in 0..count {
+                        models.push(chunk.read_i32()?);
+                        let _model_attributes = chunk.read_dict()?;
+                    }
+                    file.nodes.insert(id, VoxNode::Shape { models });
+                }
+                _ => (),
+            }
+        }
+
+        Ok(file)
+    }
+
+    /// Every visible model placement, walking the scene graph from its root node 0.
+    /// Files without a scene graph place each model at the origin
+    pub fn instances(&self) -> Result<Vec<VoxInstance>, VoxError> {
+        if self.nodes.is_empty() {
+            return Ok((0..self.models.len())
+                .map(|model| VoxInstance {
+                    model,
+                    translation: [0; 3],
+                    rotation: VoxRotation::IDENTITY,
+                })
+                .collect());
+        }
+
+        enum Step {
+            Enter(i32, [i32; 3], VoxRotation),
+            Leave(i32),
+        }
+
+        // Walked with an explicit stack so deep chains can not overflow the call stack.
+        // Transforms and groups are expanded at most once, which keeps broken files from
+        // looping forever or growing exponentially
+        let mut instances = vec![];
+        let mut path = HashSet::new();
+        let mut expanded = HashSet::new();
+        let mut stack = vec![Step::Enter(0, [0; 3], VoxRotation::IDENTITY)];
+        while let Some(step) = stack.pop() {
+            let (id, translation, rotation) = match step {
+                Step::Enter(id, translation, rotation) => (id, translation, rotation),
+                Step::Leave(id) => {
+                    path.remove(&id);
+                    continue;
+                }
+            };
+
+            let node = match self.nodes.get(&id) {
+                Some(node) => node,
+                None => continue,
+            };
+            match node {
+                VoxNode::Shape { models } => {
+                    for model in models {
+                        if *model >= 0 && (*model as usize) < self.models.len() {
+                            instances.push(VoxInstance {
+                                model: *model as usize,
+                                translation,
+                                rotation,
+                            });
+                        }
+                    }
+                    continue;
+                }
+                VoxNode::Transform { hidden: true, .. } => continue,
+                _ => (),
+            }
+
+            if path.contains(&id) {
+                return Err(VoxError::SceneCycle { node: id });
+            }
+            if !expanded.insert(id) {
+                return Err(VoxError::SharedNode { node: id });
+            }
+            path.insert(id);
+            stack.push(Step::Leave(id));
+
+            match node {
+                VoxNode::Transform {
+                    child,
+                    translation: local_translation,
+                    rotation: local_rotation,
+                    ..
+                } => {
+                    let offset = rotation.apply(*local_translation);
+                    let translation = [
+                        translation[0].saturating_add(offset[0]),
+                        translation[1].saturating_add(offset[1]),
+                        translation[2].saturating_add(offset[2]),
+                    ];
+                    stack.push(Step::Enter(
+                        *child,
+                        translation,
+                        rotation.multiply(local_rotation),
+                    ));
+                }
+                VoxNode::Group { children } => {
+                    for child in children.iter().rev() {
+                        stack.push(Step::Enter(*child, translation, rotation));
+                    }
+                }
+                VoxNode::Shape { .. } => (),
+            }
+        }
+
+        Ok(instances)
+    }
+
+    /// Merges every instance into one dense model. Instances are centered on their
+    /// translation like in MagicaVoxel, and z-up is turned into y-up
+    pub fn to_model(&self) -> Result<VoxelModel, VoxError> {
+        let mut placed: Vec<([i32; 3], u8)> = vec![];
+        for instance in self.instances()? {
+            let model = &self.models[instance.model];
+            let half = [
+                (model.size[0] / 2) as i32,
+                (model.size[1] / 2) as i32,
+                (model.size[2] / 2) as i32,
+            ];
+            for voxel in &model.voxels {
+                let local = [
+                    voxel[0] as i32 - half[0],
+                    voxel[1] as i32 - half[1],
+                    voxel[2] as i32 - half[2],
+                ];
+                let rotated = instance.rotation.apply(local);
+                let position = [
+                    rotated[0].saturating_add(instance.translation[0]),
+                    rotated[1].saturating_add(instance.translation[1]),
+                    rotated[2].saturating_add(instance.translation[2]),
+                ];
+                placed.push((position, voxel[3]));
+            }
+        }
+
+        let mut min = [i32::MAX; 3];
+        let mut max = [i32::MIN; 3];
+        for (position, _) in &placed {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+        }
+        // (x, y, z) z-up becomes (x, z, -y) y-up
+        let extent = |axis: usize| max[axis].abs_diff(min[axis]).saturating_add(1);
+        let size = match placed.is_empty() {
+            true => cgmath::vec3(0, 0, 0),
+            false => cgmath::vec3(extent(0), extent(2), extent(1)),
+        };
+        let mut model = VoxelModel::new(size, self.palette.clone())
+            .ok_or(VoxError::TooLarge { size: size.into() })?;
+        for (position, color) in placed {
+            model.set(
+                cgmath::vec3(
+                    position[0].abs_diff(min[0]),
+                    position[2].abs_diff(min[2]),
+                    max[1].abs_diff(position[1]),
+                ),
+                color,
+            );
+        }
+
+        Ok(model)
+    }
+}
+
+/// Reads `.vox` files from the `models/` directory
+pub struct VoxLoader {
+    loader: Loader,
+}
+
+impl VoxLoader {
+    pub fn new(vfs: &Arc<Vfs>) -> Self {
+        VoxLoader {
+            loader: Loader::new(vfs, "models/"),
+        }
+    }
+
+    pub fn import(&self, path: &str) -> Result<VoxFile, Error> {
+        let bytes = self.loader.load_as_bytes(path)?;
+
+        VoxFile::parse(&bytes).map_err(|error| Error::Vox {
+            path: PathBuf::from(path),
+            error,
+        })
+    }
+
+    /// Imports the file at `path` with all of its instances merged into one model
+    pub fn load_model(&self, path: &str) -> Result<VoxelModel, Error> {
+        self.import(path)?.to_model().map_err(|error| Error::Vox {
+            path: PathBuf::from(path),
+            error,
+        })
+    }
+}
+
+fn parse_translation(text: &str) -> Option<[i32; 3]> {
+    let mut parts = text.split_whitespace().map(|part| part.parse().ok());
+    let translation = [parts.next()??, parts.next()??, parts.next()??];
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(translation)
+}
+
+/// MagicaVoxel's built-in palette, used by files without an RGBA chunk: the 6x6x6 color
+/// cube without black, from white down, followed by ramps of red, green, blue and gray
+fn default_palette() -> Vec<[u8; 4]> {
+    const CUBE: [u8; 6] = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+    const RAMP: [u8; 10] = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+
+    let mut palette = vec![[0, 0, 0, 0]];
+    for &r in &CUBE {
+        for &g in &CUBE {
+            for &b in &CUBE {
+                if r != 0 || g != 0 || b != 0 {
+                    palette.push([r, g, b, 255]);
+                }
+            }
+        }
+    }
+    palette.extend(RAMP.iter().map(|&v| [v, 0, 0, 255]));
+    palette.extend(RAMP.iter().map(|&v| [0, v, 0, 255]));
+    palette.extend(RAMP.iter().map(|&v| [0, 0, v, 255]));
+    palette.extend(RAMP.iter().map(|&v| [v, v, v, 255]));
+    palette
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRATE: &[u8] = include_bytes!("../../res/models/props/crate.vox");
+    const LAMP: &[u8] = include_bytes!("../../res/models/props/lamp.vox");
+
+    fn chunk(id: &[u8; 4], content: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(&(content.len() as i32).to_le_bytes());
+        bytes.extend_from_slice(&0i32.to_le_bytes());
+        bytes.extend_from_slice(content);
+        bytes
+    }
+
+    fn vox_file(version: u32, chunks: &[Vec<u8>]) -> Vec<u8> {
+        let children = chunks.concat();
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(b"MAIN");
+        bytes.extend_from_slice(&0i32.to_le_bytes());
+        bytes.extend_from_slice(&(children.len() as i32).to_le_bytes());
+        bytes.extend(children);
+        bytes
+    }
+
+    fn size_chunk(size: [u32; 3]) -> Vec<u8> {
+        let content: Vec<u8> = size.iter().flat_map(|v| v.to_le_bytes().to_vec()).collect();
+        chunk(b"SIZE", &content)
+    }
+
+    fn xyzi_chunk(voxels: &[[u8; 4]]) -> Vec<u8> {
+        let mut content = (voxels.len() as i32).to_le_bytes().to_vec();
+        content.extend(voxels.concat());
+        chunk(b"XYZI", &content)
+    }
+
+    fn expect_malformed(bytes: &[u8], expected_chunk: &str) {
+        match VoxFile::parse(bytes) {
+            Err(VoxError::Malformed { chunk, .. }) => assert_eq!(chunk, expected_chunk),
+            other => panic!(
+                "expected a malformed {} chunk, got {:?}",
+                expected_chunk, other
+            ),
+        }
+    }
+
+    #[test]
+    fn crate_chunks_are_read() {
+        let file = VoxFile::parse(CRATE).unwrap();
+        assert_eq!(file.version, 150);
+        assert_eq!(file.models.len(), 1);
+        assert_eq!(file.models[0].size, [4, 4, 4]);
+        assert_eq!(file.models[0].voxels.len(), 56);
+        assert_eq!(file.models[0].voxels[0], [0, 0, 0, 2]);
+        assert!(file.nodes.is_empty());
+    }
+
+    #[test]
+    fn rgba_entries_start_at_color_index_one() {
+        let palette = VoxFile::parse(CRATE).unwrap().palette;
+        assert_eq!(palette.len(), 256);
+        assert_eq!(palette[0], [0, 0, 0, 0]);
+        assert_eq!(palette[1], [166, 118, 62, 255]);
+        assert_eq!(palette[2], [98, 64, 32, 255]);
+        assert_eq!(palette[4], [200, 200, 210, 255]);
+    }
+
+    #[test]
+    fn files_without_rgba_use_the_default_palette() {
+        let bytes = vox_file(150, &[size_chunk([1, 1, 1]), xyzi_chunk(&[[0, 0, 0, 1]])]);
+        let palette = VoxFile::parse(&bytes).unwrap().palette;
+        assert_eq!(palette.len(), 256);
+        assert_eq!(palette[0], [0, 0, 0, 0]);
+        assert_eq!(palette[1], [255, 255, 255, 255]);
+        assert_eq!(palette[6], [255, 255, 0, 255]);
+        assert_eq!(palette[215], [0, 0, 0x33, 255]);
+        assert_eq!(palette[216], [0xee, 0, 0, 255]);
+        assert_eq!(palette[226], [0, 0xee, 0, 255]);
+        assert_eq!(palette[236], [0, 0, 0xee, 255]);
+        assert_eq!(palette[255], [0x11, 0x11, 0x11, 255]);
+    }
+
+    #[test]
+    fn lamp_instances_follow_the_scene_graph() {
+        let file = VoxFile::parse(LAMP).unwrap();
+        assert_eq!(file.version, 200);
+        assert_eq!(file.models.len(), 2);
+        assert_eq!(file.models[0].size, [1, 1, 6]);
+        assert_eq!(file.models[1].size, [3, 1, 1]);
+
+        // The third transform is hidden
+        let instances = file.instances().unwrap();
+        assert_eq!(
+            instances,
+            vec![
+                VoxInstance {
+                    model: 0,
+                    translation: [0, 0, 3],
+                    rotation: VoxRotation::IDENTITY,
+                },
+                VoxInstance {
+                    model: 1,
+                    translation: [0, 0, 6],
+                    rotation: VoxRotation([[0, -1, 0], [1, 0, 0], [0, 0, 1]]),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn lamp_instances_merge_into_one_y_up_model() {
+        let model = VoxFile::parse(LAMP).unwrap().to_model().unwrap();
+        assert_eq!(model.get_size(), cgmath::vec3(1, 7, 3));
+        assert_eq!(model.get_voxel_count(), 9);
+        for y in 0..6 {
+            assert_eq!(model.get(cgmath::vec3(0, y, 1)), 4);
+        }
+        for z in 0..3 {
+            assert_eq!(model.get(cgmath::vec3(0, 6, z)), 3);
+        }
+    }
+
+    #[test]
+    fn models_without_scene_graph_sit_at_the_origin() {
+        let file = VoxFile::parse(CRATE).unwrap();
+        assert_eq!(
+            file.instances().unwrap(),
+            vec![VoxInstance {
+                model: 0,
+                translation: [0; 3],
+                rotation: VoxRotation::IDENTITY,
+            }]
+        );
+
+        let model = file.to_model().unwrap();
+        assert_eq!(model.get_size(), cgmath::vec3(4, 4, 4));
+        assert_eq!(model.get_voxel_count(), 56);
+        assert_eq!(model.get_palette()[1], [166, 118, 62, 255]);
+    }
+
+    #[test]
+    fn rotation_bytes_decode_into_signed_permutations() {
+        assert_eq!(
+            VoxRotation::from_byte(0b0000100),
+            Some(VoxRotation::IDENTITY)
+        );
+        assert_eq!(
+            VoxRotation::from_byte(0b1000100),
+            Some(VoxRotation([[1, 0, 0], [0, 1, 0], [0, 0, -1]]))
+        );
+        assert_eq!(
+            VoxRotation::from_byte(0b0010001),
+            Some(VoxRotation([[0, -1, 0], [1, 0, 0], [0, 0, 1]]))
+        );
+        // Both rows in the same column, or a column past the last one
+        assert_eq!(VoxRotation::from_byte(0b0000000), None);
+        assert_eq!(VoxRotation::from_byte(0b0000011), None);
+        assert_eq!(VoxRotation::from_byte(0b0001100), None);
+    }
+
+    #[test]
+    fn bad_headers_are_rejected() {
+        match VoxFile::parse(b"VOY \x96\0\0\0") {
+            Err(VoxError::BadMagic) => (),
+            other => panic!("expected BadMagic, got {:?}", other),
+        }
+        match VoxFile::parse(b"VO") {
+            Err(VoxError::BadMagic) => (),
+            other => panic!("expected BadMagic, got {:?}", other),
+        }
+        match VoxFile::parse(&vox_file(201, &[])) {
+            Err(VoxError::UnsupportedVersion(201)) => (),
+            other => panic!("expected UnsupportedVersion, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn truncated_chunks_are_rejected() {
+        let mut bytes = vox_file(150, &[size_chunk([2, 2, 2])]);
+        bytes.truncate(bytes.len() - 2);
+        match VoxFile::parse(&bytes) {
+            Err(VoxError::Truncated { chunk }) => assert_eq!(chunk, "MAIN"),
+            other => panic!("expected a truncated chunk, got {:?}", other),
+        }
+
+        // XYZI claiming more voxels than it holds
+        let mut content = 2i32.to_le_bytes().to_vec();
+        content.extend_from_slice(&[0, 0, 0, 1]);
+        let bytes = vox_file(150, &[size_chunk([1, 1, 1]), chunk(b"XYZI", &content)]);
+        match VoxFile::parse(&bytes) {
+            Err(VoxError::Truncated { chunk }) => assert_eq!(chunk, "XYZI"),
+            other => panic!("expected a truncated chunk, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn malformed_models_are_rejected() {
+        expect_malformed(&vox_file(150, &[xyzi_chunk(&[[0, 0, 0, 1]])]), "XYZI");
+        expect_malformed(
+            &vox_file(150, &[size_chunk([2, 2, 2]), xyzi_chunk(&[[0, 2, 0, 1]])]),
+            "XYZI",
+        );
+    }
+
+    #[test]
+    fn malformed_transforms_are_rejected() {
+        let transform = |key: &str, value: &str| {
+            // Node 0 without attributes, child 1, one frame with a single attribute
+            let mut content = vec![];
+            for value in &[0i32, 0, 1, -1, -1, 1, 1] {
+                content.extend_from_slice(&value.to_le_bytes());
+            }
+            for text in &[key, value] {
+                content.extend_from_slice(&(text.len() as i32).to_le_bytes());
+                content.extend_from_slice(text.as_bytes());
+            }
+            vox_file(200, &[chunk(b"nTRN", &content)])
+        };
+
+        assert!(VoxFile::parse(&transform("_t", "1 2 3")).is_ok());
+        expect_malformed(&transform("_t", "1 2"), "nTRN");
+        expect_malformed(&transform("_r", "3"), "nTRN");
+    }
+
+    #[test]
+    fn models_too_large_to_allocate_are_an_error() {
+        let transform = |child, translation| VoxNode::Transform {
+            child,
+            layer: 0,
+            translation,
+            rotation: VoxRotation::IDENTITY,
+            hidden: false,
+        };
+        let mut nodes = HashMap::new();
+        nodes.insert(0, transform(1, [0; 3]));
+        nodes.insert(
+            1,
+            VoxNode::Group {
+                children: vec![2, 4],
+            },
+        );
+        nodes.insert(2, transform(3, [-2_000_000_000, -2_000_000_000, 0]));
+        nodes.insert(3, VoxNode::Shape { models: vec![0] });
+        nodes.insert(4, transform(3, [2_000_000_000, 2_000_000_000, 0]));
+        let file = VoxFile {
+            version: 200,
+            models: vec![VoxModelData {
+                size: [1, 1, 1],
+                voxels: vec![[0, 0, 0, 1]],
+            }],
+            palette: default_palette(),
+            nodes,
+        };
+
+        match file.to_model() {
+            Err(VoxError::TooLarge { size }) => {
+                assert_eq!(size, [4_000_000_001, 1, 4_000_000_001])
+            }
+            other => panic!("expected TooLarge, got {:?}", other.map(|m| m.get_size())),
+        }
+    }
+
+    fn scene(nodes: Vec<(i32, VoxNode)>) -> VoxFile {
+        VoxFile {
+            version: 200,
+            models: vec![VoxModelData {
+                size: [1, 1, 1],
+                voxels: vec![[0, 0, 0, 1]],
+            }],
+            palette: default_palette(),
+            nodes: nodes.into_iter().collect(),
+        }
+    }
+
+    fn transform(child: i32, translation: [i32; 3]) -> VoxNode {
+        VoxNode::Transform {
+            child,
+            layer: 0,
+            translation,
+            rotation: VoxRotation::IDENTITY,
+            hidden: false,
+        }
+    }
+
+    #[test]
+    fn scene_cycles_are_an_error() {
+        let file = scene(vec![
+            (0, transform(1, [0; 3])),
+            (
+                1,
+                VoxNode::Group {
+                    children: vec![2, 0],
+                },
+            ),
+            (2, VoxNode::Shape { models: vec![0] }),
+        ]);
+
+        match file.instances() {
+            Err(VoxError::SceneCycle { node }) => assert_eq!(node, 0),
+            other => panic!("expected a cycle, got {:?}", other),
+        }
+        assert!(file.to_model().is_err());
+    }
+
+    #[test]
+    fn shared_shapes_are_placed_once_per_parent() {
+        let file = scene(vec![
+            (0, transform(1, [0; 3])),
+            (
+                1,
+                VoxNode::Group {
+                    children: vec![2, 3],
+                },
+            ),
+            (2, transform(4, [1, 0, 0])),
+            (3, transform(4, [5, 0, 0])),
+            (4, VoxNode::Shape { models: vec![0] }),
+        ]);
+
+        let translations: Vec<_> = file
+            .instances()
+            .unwrap()
+            .iter()
+            .map(|instance| instance.translation)
+            .collect();
+        assert_eq!(translations, vec![[1, 0, 0], [5, 0, 0]]);
+    }
+
+    #[test]
+    fn shared_groups_are_an_error() {
+        // Every level doubles the paths to the shape, 64 levels would never finish
+        let mut nodes = vec![(0, transform(1, [0; 3]))];
+        for level in 1..64 {
+            nodes.push((
+                level,
+                VoxNode::Group {
+                    children: vec![level + 1, level + 1],
+                },
+            ));
+        }
+        nodes.push((64, VoxNode::Shape { models: vec![0] }));
+
+        match scene(nodes).instances() {
+            Err(VoxError::SharedNode { node }) => assert_eq!(node, 63),
+            other => panic!("expected a shared node, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn deep_scene_graphs_do_not_overflow_the_stack() {
+        let depth = 100_000;
+        let mut nodes: Vec<_> = (0..depth)
+            .map(|id| (id, transform(id + 1, [0; 3])))
+            .collect();
+        nodes.push((depth, VoxNode::Shape { models: vec![0] }));
+
+        assert_eq!(scene(nodes).instances().unwrap().len(), 1);
+    }
+}
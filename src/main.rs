@@ -1,90 +1,71 @@
 extern crate cgmath;
-extern crate glutin;
-extern crate image;
-extern crate rand;
 extern crate specs;
+extern crate vxl_3;
 
-mod component;
-mod loader;
-mod render_functions;
-mod resource;
-mod system;
-mod utils;
-mod vxl_gl;
-
-use cgmath::vec3;
-use glutin::{
-    dpi::LogicalSize, dpi::Size, event::Event, event::WindowEvent, event_loop::ControlFlow,
-    event_loop::EventLoop, window::WindowBuilder, ContextBuilder,
-};
-use resource::{input::UserInput, DeltaTime, Task};
+use cgmath::{vec3, vec4};
 use specs::prelude::*;
-
-use component::{
-    camera::Camera, camera::MainCamera, material::Material, mesh::Mesh, player::Player,
-    transform::Transform,
-};
-use system::{
-    demo::DemoPlayerRotationSys,
-    tasks::{SetMainCameraSys, SetRenderTaskSys},
+use std::path::PathBuf;
+
+use vxl_3::{
+    component::{
+        camera::{Camera, Clear, MainCamera, Viewport},
+        character::{CharacterController, CharacterSettings},
+        material::Material,
+        mesh::{Mesh, MeshManager},
+        physics::Velocity,
+        player::Player,
+        transform::{Parent, Transform},
+    },
+    loader::{
+        shaders::ShaderManager,
+        textures::{
+            descriptor::TextureDescriptor,
+            sampler::{Filter, SamplerSettings},
+            TextureManager,
+        },
+    },
+    resource::time::Time,
+    system::character::CharacterControllerSys,
+    voxel::{model::VoxelModel, world::VoxelWorld},
+    vxl_gl::{gl, UniformValue},
+    App, SceneContext, WindowSettings,
 };
-use utils::key_codes;
-use vxl_gl::gl;
 
-const RFPS: f32 = 120.0;
-const WINDOW_HEIGHT: f32 = 720.0;
-const WINDOW_WIDTH: f32 = 1280.0;
+/// Block the demo floor is made of
+const STONE: u16 = 1;
 
-fn main() {
-    let event_loop = EventLoop::new();
-    let window_builder = WindowBuilder::new()
-        .with_title("VXL")
-        .with_resizable(false)
-        .with_inner_size(Size::new(LogicalSize::new(WINDOW_WIDTH, WINDOW_HEIGHT)));
-
-    let windowed_context = ContextBuilder::new()
-        .with_gl_profile(glutin::GlProfile::Core)
-        .with_multisampling(4)
-        .with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, (3, 3)))
-        .build_windowed(window_builder, &event_loop)
-        .unwrap();
-
-    let windowed_context = unsafe { windowed_context.make_current().unwrap() };
-
-    let mut gl = vxl_gl::load(&windowed_context.context());
-    gl.clear_color = vec3(0.1, 0.1, 0.1);
-
-    println!(
-        "Pixel format of context is {:?}",
-        windowed_context.get_pixel_format()
-    );
+/// Marks the entities `DemoSpinSys` turns
+#[derive(Default)]
+pub struct Spinning;
+impl Component for Spinning {
+    type Storage = NullStorage<Self>;
+}
 
-    let mut world = specs::World::new();
-    let shader_manager = loader::shaders::ShaderLoader::new(&gl)
-        .add_shader_program(
-            "default",
-            vec![
-                ("default/default.vert.glsl", gl::VERTEX_SHADER),
-                ("default/default.frag.glsl", gl::FRAGMENT_SHADER),
-            ],
-        )
-        .finish();
+pub struct DemoSpinSys;
+impl<'a> System<'a> for DemoSpinSys {
+    type SystemData = (
+        ReadStorage<'a, Spinning>,
+        WriteStorage<'a, Transform>,
+        ReadExpect<'a, Time>,
+    );
 
-    let texture_manager = loader::textures::TextureLoader::new(&gl)
-        .add_texture("test.png", "test")
-        .finish();
+    fn run(&mut self, (spinning, mut transfrom, time): Self::SystemData) {
+        // Degrees per second
+        const ROTATION_SPEED: f32 = 48.0;
 
-    world.register::<Mesh>();
-    world.register::<Material>();
-    world.register::<Transform>();
-    world.register::<Camera>();
-    world.register::<MainCamera>();
-    world.register::<Player>();
+        for (_, transform) in (&spinning, &mut transfrom).join() {
+            transform.rotate_y(ROTATION_SPEED * time.get_fixed_delta());
+        }
+    }
+}
 
-    let dispatcher_builder = specs::DispatcherBuilder::new();
+fn demo_scene(ctx: &mut SceneContext) {
+    let gl = ctx.gl;
+    let mut shader_manager = ctx.world.write_resource::<ShaderManager>();
+    let mut texture_manager = ctx.world.write_resource::<TextureManager>();
 
     let mut mesh = Mesh::from_data(
-        &gl,
+        gl,
         vec![
             cgmath::vec3(-0.5, 0.5, 0.0),
             cgmath::vec3(0.5, 0.5, 0.0),
@@ -95,7 +76,7 @@ fn main() {
     );
 
     mesh.add_uvs(
-        &gl,
+        gl,
         vec![
             cgmath::vec2(0.0, 0.0),
             cgmath::vec2(1.0, 0.0),
@@ -104,101 +85,161 @@ fn main() {
         ],
     );
 
-    let mut material = Material::default(&shader_manager);
-    material.add_texture(texture_manager.get_texture("test"));
+    let mut material = Material::default(&mut shader_manager);
+    let texture = texture_manager.get_handle_or_missing("test");
+    let pixel_sampler = texture_manager.get_sampler(gl, SamplerSettings::default());
+    material.set_texture("albedo", texture, Some(pixel_sampler));
+    material.set_parameter("tint", UniformValue::Color(vec4(1.0, 1.0, 1.0, 1.0)));
 
-    world
-        .create_entity()
-        .with(mesh)
+    let mut mesh_manager = ctx.world.write_resource::<MeshManager>();
+    let quad = mesh_manager.reserve("quad");
+    mesh_manager.set(&quad, mesh);
+
+    let quad = ctx
+        .world
+        .create_entity_unchecked()
+        .with(quad)
         .with(Transform::default())
         .with(material)
-        .with(Player)
+        .with(Spinning)
         .build();
 
-    world
+    // 16 x 16 floor with its top at y = -1, solid for the player and rendered with the
+    // crate palette
+    let mut floor = VoxelModel::new(vec3(16, 1, 16), vec![]).expect("floor fits in memory");
+    for x in 0..16 {
+        for z in 0..16 {
+            floor.set(vec3(x, 0, z), 2);
+        }
+    }
+    let floor_origin = vec3(-8, -2, -8);
+    ctx.world
+        .write_resource::<VoxelWorld>()
+        .stamp(&floor, floor_origin, |_| STONE);
+
+    // The crate is attached to the quad and turns with it
+    match ctx.assets.vox.load_model("props/crate.vox") {
+        Ok(model) => {
+            let (dimensions, palette) = model.palette_texture_data();
+            let palette_descriptor = TextureDescriptor::default()
+                .with_filter(Filter::Nearest, Filter::Nearest)
+                .with_generate_mips(false);
+            let palette = texture_manager.add_texture_from_data(
+                gl,
+                "props/crate.vox#palette",
+                dimensions,
+                &palette,
+                palette_descriptor,
+            );
+
+            let crate_mesh = mesh_manager.reserve("props/crate.vox");
+            mesh_manager.set(
+                &crate_mesh,
+                Mesh::from_mesh_data(gl, &model.build_mesh("crate")),
+            );
+
+            let mut crate_material = Material::default(&mut shader_manager);
+            let mut floor_material = Material::default(&mut shader_manager);
+            match palette {
+                Ok(palette) => {
+                    crate_material.set_texture("albedo", palette.clone(), None);
+                    floor_material.set_texture("albedo", palette, None);
+                }
+                Err(err) => println!("{}", err),
+            }
+
+            let floor_mesh = mesh_manager.reserve("floor");
+            mesh_manager.set(
+                &floor_mesh,
+                Mesh::from_mesh_data(gl, &floor.build_mesh("floor")),
+            );
+            ctx.world
+                .create_entity_unchecked()
+                .with(floor_mesh)
+                .with(Transform::from_position(floor_origin.cast().unwrap()))
+                .with(floor_material)
+                .build();
+
+            let mut crate_transform = Transform::from_position(cgmath::vec3(1.0, -0.5, -1.0));
+            crate_transform.set_scale_uniform(1.0 / 8.0);
+            ctx.world
+                .create_entity_unchecked()
+                .with(crate_mesh)
+                .with(crate_transform)
+                .with(Parent::new(quad))
+                .with(crate_material)
+                .build();
+        }
+        Err(err) => println!("{}", err),
+    }
+    drop(mesh_manager);
+    drop(shader_manager);
+    drop(texture_manager);
+
+    // The player stands on the floor, the controller keeps its camera at eye height
+    let settings = CharacterSettings::default();
+    let player = ctx
+        .world
+        .create_entity()
+        .with(Transform::from_position(cgmath::vec3(0.0, -1.0, 3.0)))
+        .with(Velocity::default())
+        .with(settings.get_collider())
+        .with(CharacterController::new(settings))
+        .with(Player)
+        .build();
+    ctx.world
         .create_entity()
-        .with(Transform::from_position(cgmath::vec3(0.0, 0.0, 2.0)))
-        .with(Camera::new(
-            45.0,
-            WINDOW_WIDTH / WINDOW_HEIGHT,
-            0.01,
-            1000.0,
-        ))
+        .with(Transform::default())
+        .with(Camera::new(45.0, ctx.window.get_aspect(), 0.01, 1000.0))
         .with(MainCamera)
+        .with(Parent::new(player))
         .build();
 
-    let mut dispatcher = dispatcher_builder
-        .with(SetMainCameraSys, "main_camera", &[])
-        .with(SetRenderTaskSys, "render_task", &[])
-        .with(DemoPlayerRotationSys, "demo_player_rotation", &[])
+    // Overhead view in the top right corner
+    let mut overhead = Transform::from_position(cgmath::vec3(0.0, 8.0, 0.0));
+    overhead.look_at(cgmath::vec3(0.0, 0.0, 0.0), -cgmath::Vector3::unit_z());
+    ctx.world
+        .create_entity()
+        .with(overhead)
+        .with(
+            Camera::orthographic(6.0, 1.0, 0.01, 100.0)
+                .with_viewport(Viewport::new(0.75, 0.75, 0.25, 0.25))
+                .with_clear(Clear::Color(cgmath::vec3(0.1, 0.1, 0.15)))
+                .with_order(1),
+        )
         .build();
-    dispatcher.setup(&mut world);
-
-    let rfps_barrier = 1000000.0 / RFPS;
-    let mut timer = std::time::Duration::new(0, 0);
-    let mut diff_timer = std::time::Duration::new(0, 0);
-    let mut second_timer = std::time::Duration::new(0, 0);
-
-    let mut rfps = 0;
-
-    world.insert(UserInput::default());
-    event_loop.run(move |event, _, control_flow| {
-        *control_flow = ControlFlow::Poll;
-        let timer_start = std::time::Instant::now();
-        world.insert(DeltaTime::new(diff_timer.as_micros() as u32));
-        world.insert(Task::default());
-
-        match event {
-            Event::LoopDestroyed => return,
-            Event::WindowEvent { event, .. } => match event {
-                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                WindowEvent::KeyboardInput { input, .. } => {
-                    let mut input_res = world.write_resource::<UserInput>();
-                    if input.state == glutin::event::ElementState::Pressed {
-                        input_res.add_key(input.scancode);
-                    } else if input.state == glutin::event::ElementState::Released {
-                        input_res.remove_key(input.scancode);
-                    }
-                }
-                _ => (),
-            },
-            _ => (),
-        };
-
-        println!(
-            "User input:\n W: {}",
-            world
-                .read_resource::<UserInput>()
-                .is_key_pressed(key_codes::KEY_W)
-        );
-
-        dispatcher.dispatch(&world);
-        world.maintain();
-
-        if (timer.as_micros() as f32) >= rfps_barrier {
-            gl.clear_screen();
-
-            let task_res = world.read_resource::<Task>();
-            render_functions::render_simple(&gl, &task_res);
-
-            windowed_context.swap_buffers().unwrap();
-
-            gl.print_error();
-            timer = std::time::Duration::new(0, 0);
-            rfps += 1;
-        }
-
-        let elapsed = timer_start.elapsed();
-        if (timer.as_micros() as f32) < rfps_barrier {
-            timer = timer.checked_add(elapsed).unwrap();
-        }
-        diff_timer = elapsed;
-        second_timer = second_timer.checked_add(elapsed).unwrap();
+}
 
-        if second_timer.as_secs() >= 1 {
-            println!("RFPS: {}", rfps);
-            rfps = 0;
-            second_timer = std::time::Duration::new(0, 0);
-        }
-    });
+fn main() {
+    let app = App::new()
+        .with_window(
+            WindowSettings::default()
+                .with_title("VXL")
+                .with_size(1280.0, 720.0)
+                .with_clear_color(vec3(0.1, 0.1, 0.1)),
+        )
+        .with_input_config("input.cfg")
+        .with_component::<Player>()
+        .with_component::<Spinning>()
+        .with_system(DemoSpinSys, "demo_spin", &[])
+        // The app runs the physics after every system added here
+        .with_system(CharacterControllerSys, "character_controller", &[])
+        .with_shader_program(
+            "default",
+            vec![
+                ("default/default.vert.glsl", gl::VERTEX_SHADER),
+                ("default/default.frag.glsl", gl::FRAGMENT_SHADER),
+            ],
+        )
+        .with_texture("test.png", "test", TextureDescriptor::default())
+        .with_scene("demo", demo_scene);
+
+    // Debug builds read assets straight from the source tree, so edits to `res/` are
+    // picked up by hot reloading without rebuilding
+    let source_res = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("res/");
+    if cfg!(debug_assertions) && source_res.is_dir() {
+        app.with_asset_root(source_res).run();
+    } else {
+        app.run();
+    }
 }
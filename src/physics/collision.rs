@@ -0,0 +1,382 @@
+//! Swept box against block collision. Motion is resolved one axis at a time, Y first,
+//! then X and Z, so the result only depends on the world and the inputs
+
+use cgmath::{prelude::*, Vector3};
+
+use super::Aabb;
+use crate::voxel::{world::VoxelWorld, BlockKind};
+
+/// Distance below which boxes count as touching, absorbs float errors of earlier moves
+const EPSILON: f32 = 1e-4;
+
+/// How far below a box is looked for ground
+const GROUND_PROBE: f32 = 0.01;
+
+const AXIS_Y: usize = 1;
+
+/// Outcome of `move_aabb`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MoveResult {
+    /// How far the box really moved
+    pub motion: Vector3<f32>,
+    /// Axes the motion was cut short on, X, Y and Z
+    pub blocked: [bool; 3],
+    /// Stands on a block after the move
+    pub grounded: bool,
+    /// Height climbed onto a ledge, 0 without a step up
+    pub step: f32,
+}
+
+/// Moves `aabb` by `motion`, stopping at solid blocks. A box that starts on the ground
+/// and runs into a ledge of at most `step_height` climbs it, if that gets it further
+pub fn move_aabb(
+    world: &VoxelWorld,
+    aabb: Aabb,
+    motion: Vector3<f32>,
+    step_height: f32,
+) -> MoveResult {
+    let (moved, blocked) = sweep(world, aabb, motion);
+    let mut result = MoveResult {
+        motion: moved,
+        blocked,
+        grounded: false,
+        step: 0.0,
+    };
+
+    let blocked_sideways = blocked[0] || blocked[2];
+    if step_height > 0.0 && blocked_sideways && motion.y <= 0.0 && is_grounded(world, &aabb) {
+        if let Some(stepped) = step_up(world, aabb, motion, step_height) {
+            if horizontal_distance2(stepped.motion) > horizontal_distance2(moved) + EPSILON {
+                result = stepped;
+            }
+        }
+    }
+
+    result.grounded = motion.y <= 0.0 && is_grounded(world, &aabb.translated(result.motion));
+    result
+}
+
+/// Whether a solid block is right below `aabb`
+pub fn is_grounded(world: &VoxelWorld, aabb: &Aabb) -> bool {
+    clip_axis(world, aabb, AXIS_Y, -GROUND_PROBE) > -GROUND_PROBE
+}
+
+/// Cuts the sideways part of `motion` short where it would carry a grounded box over
+/// an edge, so it never loses the ground below it. Goes in steps of `EDGE_STEP`
+pub fn clamp_to_edges(world: &VoxelWorld, aabb: &Aabb, motion: Vector3<f32>) -> Vector3<f32> {
+    const EDGE_STEP: f32 = 0.05;
+
+    let supported = |x: f32, z: f32| is_grounded(world, &aabb.translated(cgmath::vec3(x, 0.0, z)));
+    let shorten = |value: f32| {
+        if value.abs() <= EDGE_STEP {
+            0.0
+        } else {
+            value - EDGE_STEP * value.signum()
+        }
+    };
+
+    let (mut x, mut z) = (motion.x, motion.z);
+    while x != 0.0 && !supported(x, 0.0) {
+        x = shorten(x);
+    }
+    while z != 0.0 && !supported(0.0, z) {
+        z = shorten(z);
+    }
+    // Both axes may be fine alone and still leave the box past a corner
+    while x != 0.0 && z != 0.0 && !supported(x, z) {
+        x = shorten(x);
+        z = shorten(z);
+    }
+    cgmath::vec3(x, motion.y, z)
+}
+
+/// Whether `aabb` reaches into any block of `kind`
+pub fn intersects_kind(world: &VoxelWorld, aabb: &Aabb, kind: BlockKind) -> bool {
+    let (first, last) = aabb.get_block_range();
+    for x in first.x..=last.x {
+        for y in first.y..=last.y {
+            for z in first.z..=last.z {
+                let position = cgmath::vec3(x, y, z);
+                if world.get_kind_at(position) == kind
+                    && shrunk(aabb).intersects(&Aabb::from_block(position))
+                {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Raises the box, moves it sideways and sets it down again
+fn step_up(
+    world: &VoxelWorld,
+    aabb: Aabb,
+    motion: Vector3<f32>,
+    step_height: f32,
+) -> Option<MoveResult> {
+    let up = clip_axis(world, &aabb, AXIS_Y, step_height);
+    if up <= EPSILON {
+        return None;
+    }
+    let raised = aabb.translated(cgmath::vec3(0.0, up, 0.0));
+
+    let (sideways, blocked) = sweep(world, raised, cgmath::vec3(motion.x, 0.0, motion.z));
+    let down = clip_axis(world, &raised.translated(sideways), AXIS_Y, motion.y - up);
+    let climbed = up + down;
+
+    Some(MoveResult {
+        motion: cgmath::vec3(sideways.x, climbed, sideways.z),
+        blocked: [blocked[0], false, blocked[2]],
+        grounded: false,
+        step: climbed.max(0.0),
+    })
+}
+
+/// Moves along Y, X and Z in turn, each axis starting where the last one stopped
+fn sweep(world: &VoxelWorld, mut aabb: Aabb, motion: Vector3<f32>) -> (Vector3<f32>, [bool; 3]) {
+    let mut moved = Vector3::zero();
+    let mut blocked = [false; 3];
+    for &axis in &[AXIS_Y, 0, 2] {
+        let distance = clip_axis(world, &aabb, axis, motion[axis]);
+        blocked[axis] = distance != motion[axis];
+        moved[axis] = distance;
+
+        let mut offset = Vector3::zero();
+        offset[axis] = distance;
+        aabb = aabb.translated(offset);
+    }
+    (moved, blocked)
+}
+
+/// How far `aabb` gets along `axis` before it hits a solid block, at most `distance`.
+/// Blocks the box already reaches into are ignored, so it can move out of them
+fn clip_axis(world: &VoxelWorld, aabb: &Aabb, axis: usize, distance: f32) -> f32 {
+    if distance == 0.0 {
+        return 0.0;
+    }
+    let mut offset = Vector3::zero();
+    offset[axis] = distance;
+    let (first, last) = aabb.expanded(offset).get_block_range();
+    let inner = shrunk(aabb);
+
+    let mut clipped = distance;
+    for x in first.x..=last.x {
+        for y in first.y..=last.y {
+            for z in first.z..=last.z {
+                let position = cgmath::vec3(x, y, z);
+                if world.get_kind_at(position) != BlockKind::Solid {
+                    continue;
+                }
+                let block = Aabb::from_block(position);
+                let beside = (0..3)
+                    .filter(|other| *other != axis)
+                    .all(|other| inner.overlaps_on(&block, other));
+                if !beside {
+                    continue;
+                }
+
+                if distance > 0.0 && block.min[axis] >= aabb.max[axis] - EPSILON {
+                    clipped = clipped.min((block.min[axis] - aabb.max[axis]).max(0.0));
+                } else if distance < 0.0 && block.max[axis] <= aabb.min[axis] + EPSILON {
+                    clipped = clipped.max((block.max[axis] - aabb.min[axis]).min(0.0));
+                }
+            }
+        }
+    }
+    clipped
+}
+
+/// `aabb` without the touching margin, for overlap tests
+fn shrunk(aabb: &Aabb) -> Aabb {
+    let margin = cgmath::vec3(EPSILON, EPSILON, EPSILON);
+    Aabb {
+        min: aabb.min + margin,
+        max: aabb.max - margin,
+    }
+}
+
+fn horizontal_distance2(motion: Vector3<f32>) -> f32 {
+    motion.x * motion.x + motion.z * motion.z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: u16 = 1;
+    const WATER: u16 = 2;
+
+    /// Stone floor at y = -1 from -4 to 4 on X and Z
+    fn floor() -> VoxelWorld {
+        let mut world = VoxelWorld::new();
+        world.set_block_kind(WATER, BlockKind::Fluid);
+        for x in -4..=4 {
+            for z in -4..=4 {
+                world.set_block(cgmath::vec3(x, -1, z), STONE);
+            }
+        }
+        world
+    }
+
+    /// Player sized box standing at `position`
+    fn player(position: Vector3<f32>) -> Aabb {
+        Aabb::new(
+            position - cgmath::vec3(0.3, 0.0, 0.3),
+            position + cgmath::vec3(0.3, 1.8, 0.3),
+        )
+    }
+
+    fn assert_close(value: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (value - expected).magnitude() < 1e-4,
+            "expected {:?}, got {:?}",
+            expected,
+            value
+        );
+    }
+
+    #[test]
+    fn walls_clip_only_the_blocked_axis() {
+        let mut world = floor();
+        for y in 0..3 {
+            for z in -4..=4 {
+                world.set_block(cgmath::vec3(2, y, z), STONE);
+            }
+        }
+
+        let result = move_aabb(
+            &world,
+            player(cgmath::vec3(1.0, 0.0, 0.0)),
+            cgmath::vec3(2.0, 0.0, 0.5),
+            0.0,
+        );
+        assert_close(result.motion, cgmath::vec3(0.7, 0.0, 0.5));
+        assert_eq!(result.blocked, [true, false, false]);
+        assert!(result.grounded);
+        assert_eq!(result.step, 0.0);
+
+        // Touching the wall, only sliding along it is left
+        let touching = player(cgmath::vec3(1.7, 0.0, 0.0));
+        let result = move_aabb(&world, touching, cgmath::vec3(0.5, 0.0, -0.5), 0.0);
+        assert_close(result.motion, cgmath::vec3(0.0, 0.0, -0.5));
+        assert_eq!(result.blocked, [true, false, false]);
+    }
+
+    #[test]
+    fn falling_boxes_land_on_the_ground() {
+        let world = floor();
+        let falling = player(cgmath::vec3(0.0, 2.0, 0.0));
+
+        let result = move_aabb(&world, falling, cgmath::vec3(0.0, -1.0, 0.0), 0.0);
+        assert_close(result.motion, cgmath::vec3(0.0, -1.0, 0.0));
+        assert!(!result.grounded);
+        assert!(!is_grounded(&world, &falling));
+
+        let result = move_aabb(&world, falling, cgmath::vec3(0.5, -5.0, 0.0), 0.0);
+        assert_close(result.motion, cgmath::vec3(0.5, -2.0, 0.0));
+        assert_eq!(result.blocked, [false, true, false]);
+        assert!(result.grounded);
+        assert!(is_grounded(&world, &falling.translated(result.motion)));
+
+        // Jumping off the ground is not grounded
+        let standing = player(cgmath::vec3(0.0, 0.0, 0.0));
+        let result = move_aabb(&world, standing, cgmath::vec3(0.0, 0.2, 0.0), 0.0);
+        assert!(!result.grounded);
+    }
+
+    #[test]
+    fn fluids_do_not_stop_boxes() {
+        let mut world = floor();
+        world.set_block(cgmath::vec3(0, 0, 0), WATER);
+        let falling = player(cgmath::vec3(0.5, 2.0, 0.5));
+
+        let result = move_aabb(&world, falling, cgmath::vec3(0.0, -3.0, 0.0), 0.0);
+        assert_close(result.motion, cgmath::vec3(0.0, -2.0, 0.0));
+        assert!(intersects_kind(
+            &world,
+            &falling.translated(result.motion),
+            BlockKind::Fluid
+        ));
+    }
+
+    #[test]
+    fn ledges_up_to_the_step_height_are_climbed() {
+        let mut world = floor();
+        for z in -4..=4 {
+            world.set_block(cgmath::vec3(2, 0, z), STONE);
+        }
+        let standing = player(cgmath::vec3(1.0, 0.0, 0.0));
+
+        let result = move_aabb(&world, standing, cgmath::vec3(1.0, 0.0, 0.0), 1.0);
+        assert_close(result.motion, cgmath::vec3(1.0, 1.0, 0.0));
+        assert_eq!(result.blocked, [false, false, false]);
+        assert!((result.step - 1.0).abs() < 1e-4);
+        assert!(result.grounded);
+
+        // A lower step height stops at the ledge
+        let result = move_aabb(&world, standing, cgmath::vec3(1.0, 0.0, 0.0), 0.5);
+        assert_close(result.motion, cgmath::vec3(0.7, 0.0, 0.0));
+        assert_eq!(result.step, 0.0);
+
+        // So does a box in the air
+        let jumping = player(cgmath::vec3(1.0, 0.5, 0.0));
+        let result = move_aabb(&world, jumping, cgmath::vec3(1.0, 0.0, 0.0), 1.0);
+        assert_close(result.motion, cgmath::vec3(0.7, 0.0, 0.0));
+        assert_eq!(result.step, 0.0);
+    }
+
+    #[test]
+    fn ledges_above_the_step_height_block() {
+        let mut world = floor();
+        for y in 0..2 {
+            for z in -4..=4 {
+                world.set_block(cgmath::vec3(2, y, z), STONE);
+            }
+        }
+        let standing = player(cgmath::vec3(1.0, 0.0, 0.0));
+
+        let result = move_aabb(&world, standing, cgmath::vec3(1.0, 0.0, 0.0), 1.0);
+        assert_close(result.motion, cgmath::vec3(0.7, 0.0, 0.0));
+        assert_eq!(result.blocked, [true, false, false]);
+        assert_eq!(result.step, 0.0);
+        assert!(result.grounded);
+    }
+
+    #[test]
+    fn boxes_move_out_of_blocks_they_overlap() {
+        let mut world = floor();
+        world.set_block(cgmath::vec3(0, 0, 0), STONE);
+        world.set_block(cgmath::vec3(0, 0, 3), STONE);
+        // Reaches 0.4 into the block at the origin
+        let stuck = Aabb::new(cgmath::vec3(0.2, 0.0, 0.6), cgmath::vec3(0.8, 1.8, 1.2));
+        assert!(intersects_kind(&world, &stuck, BlockKind::Solid));
+
+        let result = move_aabb(&world, stuck, cgmath::vec3(0.0, 0.0, 0.5), 0.0);
+        assert_close(result.motion, cgmath::vec3(0.0, 0.0, 0.5));
+        assert_eq!(result.blocked, [false; 3]);
+        assert!(!intersects_kind(
+            &world,
+            &stuck.translated(result.motion),
+            BlockKind::Solid
+        ));
+
+        // The next block on the way still stops it
+        let result = move_aabb(&world, stuck, cgmath::vec3(0.0, 0.0, 5.0), 0.0);
+        assert_close(result.motion, cgmath::vec3(0.0, 0.0, 1.8));
+        assert_eq!(result.blocked, [false, false, true]);
+    }
+
+    #[test]
+    fn edges_are_not_walked_off() {
+        let world = floor();
+        // 0.1 from the edge of the floor at x = 5
+        let standing = player(cgmath::vec3(5.2, 0.0, 0.0));
+        assert!(is_grounded(&world, &standing));
+
+        let motion = clamp_to_edges(&world, &standing, cgmath::vec3(1.0, 0.0, 0.2));
+        assert!(motion.x < 0.1 + 1e-4, "walked off to {:?}", motion);
+        assert!(is_grounded(&world, &standing.translated(motion)));
+        assert_eq!(motion.z, 0.2);
+    }
+}
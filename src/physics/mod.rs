@@ -0,0 +1,95 @@
+//! Boxes moving through the voxel world. Everything here is plain functions of a
+//! `VoxelWorld`, the ECS side lives in `component::physics` and `system::physics`
+
+pub mod collision;
+
+use cgmath::Vector3;
+
+/// Axis aligned box, `min` has the smaller value on every axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+}
+
+impl Aabb {
+    /// The corners may be given in any order
+    pub fn new(a: Vector3<f32>, b: Vector3<f32>) -> Self {
+        Aabb {
+            min: cgmath::vec3(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: cgmath::vec3(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    pub fn from_center(center: Vector3<f32>, size: Vector3<f32>) -> Self {
+        Aabb::new(center - size / 2.0, center + size / 2.0)
+    }
+
+    /// Box of the block at `position`, blocks span one unit from their position up
+    pub fn from_block(position: Vector3<i32>) -> Self {
+        let min = position.cast().unwrap();
+        Aabb {
+            min,
+            max: min + cgmath::vec3(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn get_center(&self) -> Vector3<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn get_size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    pub fn translated(&self, offset: Vector3<f32>) -> Aabb {
+        Aabb {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+
+    /// Grows the box on the side `offset` points to, covering everything it passes
+    pub fn expanded(&self, offset: Vector3<f32>) -> Aabb {
+        self.union(&self.translated(offset))
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: cgmath::vec3(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: cgmath::vec3(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+        }
+    }
+
+    /// Boxes that only touch do not intersect
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.overlaps_on(other, axis))
+    }
+
+    pub fn overlaps_on(&self, other: &Aabb, axis: usize) -> bool {
+        self.min[axis] < other.max[axis] && self.max[axis] > other.min[axis]
+    }
+
+    /// First and last block the box reaches into on every axis, both inclusive
+    pub fn get_block_range(&self) -> (Vector3<i32>, Vector3<i32>) {
+        let first = cgmath::vec3(
+            self.min.x.floor() as i32,
+            self.min.y.floor() as i32,
+            self.min.z.floor() as i32,
+        );
+        let last = cgmath::vec3(
+            self.max.x.ceil() as i32 - 1,
+            self.max.y.ceil() as i32 - 1,
+            self.max.z.ceil() as i32 - 1,
+        );
+        (first, last)
+    }
+}
@@ -1,43 +1,73 @@
-use crate::{resource::Task, vxl_gl::Gl};
+use crate::{
+    component::camera::Clear,
+    resource::{tasks::CameraTask, tasks::RenderTask, Task},
+    vxl_gl::Gl,
+};
 
+/// Draws the tasks through every camera, into its framebuffer and viewport
 pub fn render_simple<'a>(gl: &'a Gl, task_res: &'a Task) {
-    let main_cam = task_res.get_main_camera_task();
+    for camera in task_res.get_camera_tasks() {
+        gl.bind_framebuffer(camera.get_fbo_id());
+        let (x, y, width, height) = camera.get_viewport();
+        gl.set_viewport(x, y, width, height);
+        // Clears only touch the viewport
+        gl.set_scissor(Some(camera.get_viewport()));
+        match camera.get_clear() {
+            Clear::WindowColor => gl.clear(Some(gl.clear_color)),
+            Clear::Color(color) => gl.clear(Some(color)),
+            Clear::Depth => gl.clear(None),
+            Clear::Nothing => (),
+        }
 
-    let projection_mat = main_cam.get_projection_mat();
-    let view_mat = main_cam.get_view_mat();
+        let render_tasks = task_res.get_render_tasks();
+        for render_task in render_tasks {
+            if render_task.get_layers() & camera.get_layer_mask() != 0 {
+                draw(gl, camera, render_task);
+            }
+        }
+    }
+    gl.set_scissor(None);
+    gl.unbind_framebuffer();
+}
 
-    let render_tasks = task_res.get_render_tasks();
-    for render_task in render_tasks {
-        let pid = render_task.get_pid();
-        let attrib_arrays = render_task.get_attri_arrays();
+fn draw(gl: &Gl, camera: &CameraTask, render_task: &RenderTask) {
+    let projection_mat = camera.get_projection_mat();
+    let view_mat = camera.get_view_mat();
 
-        gl.bind_program(pid);
+    let pid = render_task.get_pid();
+    let attrib_arrays = render_task.get_attri_arrays();
 
-        let ploc = gl.get_uniform_location(pid, "proj_mat");
-        gl.add_uniform_matrix4f(ploc, projection_mat);
-        let vloc = gl.get_uniform_location(pid, "view_mat");
-        gl.add_uniform_matrix4f(vloc, view_mat);
+    gl.bind_program(pid);
 
-        let texture_id = render_task.get_texture_id();
+    let (ploc, vloc) = render_task.get_camera_locations();
+    gl.add_uniform_matrix4f(ploc, projection_mat);
+    gl.add_uniform_matrix4f(vloc, view_mat);
 
-        if texture_id.is_some() {
-            gl.set_active_texture();
-            gl.bind_texture(texture_id.unwrap());
+    let textures = render_task.get_textures();
+    for binding in textures {
+        gl.set_active_texture(binding.get_unit());
+        gl.bind_texture(binding.get_texture_id());
+        if let Some(sampler_id) = binding.get_sampler_id() {
+            gl.bind_sampler(binding.get_unit(), sampler_id);
         }
+    }
 
-        for (name, value) in render_task.get_mat4f_unifroms() {
-            gl.add_uniform_matrix4f(gl.get_uniform_location(pid, *name), *value);
-        }
+    for (location, value) in render_task.get_uniforms() {
+        gl.add_uniform(*location, value);
+    }
 
-        gl.bind_vao(render_task.get_vao_id());
-        gl.enable_vertex_attrib_arrays(attrib_arrays);
-        gl.draw_elements(render_task.get_vertex_count());
-        gl.disable_vertex_attrib_arrays(attrib_arrays);
+    gl.bind_vao(render_task.get_vao_id());
+    gl.enable_vertex_attrib_arrays(attrib_arrays);
+    gl.draw_elements(render_task.get_vertex_count());
+    gl.disable_vertex_attrib_arrays(attrib_arrays);
 
-        if texture_id.is_some() {
-            gl.unbind_texture();
+    for binding in textures {
+        gl.set_active_texture(binding.get_unit());
+        gl.unbind_texture();
+        if binding.get_sampler_id().is_some() {
+            gl.unbind_sampler(binding.get_unit());
         }
-        gl.unbind_vao();
-        gl.unbind_program();
     }
+    gl.unbind_vao();
+    gl.unbind_program();
 }
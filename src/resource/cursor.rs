@@ -0,0 +1,16 @@
+/// Whether the cursor is locked to the window and hidden, for mouse look.
+/// The `App` applies changes to the window after every frame
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Cursor {
+    grabbed: bool,
+}
+
+impl Cursor {
+    pub fn set_grabbed(&mut self, grabbed: bool) {
+        self.grabbed = grabbed;
+    }
+
+    pub fn is_grabbed(&self) -> bool {
+        self.grabbed
+    }
+}
@@ -0,0 +1,234 @@
+//! Named actions ("jump", "move_forward") bound to keys and mouse buttons.
+//! Bindings are read from a config with one action per line:
+//!
+//! ```text
+//! # comment
+//! jump = Space
+//! attack = MouseLeft, F, PadRightTrigger
+//! move_forward = W, PadLeftStickY+
+//! ```
+//!
+//! Axis bindings name the axis and the half of it that drives the action
+
+use std::{collections::BTreeMap, fmt};
+
+use super::{
+    gamepad::{AxisDirection, GamepadAxis, GamepadButton},
+    keys::{Key, MouseButton},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Binding {
+    Key(Key),
+    Mouse(MouseButton),
+    Pad(GamepadButton),
+    PadAxis(GamepadAxis, AxisDirection),
+}
+
+impl Binding {
+    pub fn get_name(&self) -> String {
+        match self {
+            Binding::Key(key) => key.get_name().to_owned(),
+            Binding::Mouse(button) => button.get_name(),
+            Binding::Pad(button) => button.get_name().to_owned(),
+            Binding::PadAxis(axis, direction) => {
+                format!("{}{}", axis.get_name(), direction.get_suffix())
+            }
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Binding> {
+        let axis = |suffix: char, direction: AxisDirection| {
+            let axis = GamepadAxis::from_name(name.strip_suffix(suffix)?)?;
+            Some(Binding::PadAxis(axis, direction))
+        };
+
+        Key::from_name(name)
+            .map(Binding::Key)
+            .or_else(|| MouseButton::from_name(name).map(Binding::Mouse))
+            .or_else(|| GamepadButton::from_name(name).map(Binding::Pad))
+            .or_else(|| axis('+', AxisDirection::Positive))
+            .or_else(|| axis('-', AxisDirection::Negative))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActionMap {
+    bindings: BTreeMap<String, Vec<Binding>>,
+}
+
+impl ActionMap {
+    pub fn new() -> Self {
+        ActionMap::default()
+    }
+
+    pub fn with_binding(mut self, action: &str, binding: Binding) -> Self {
+        self.bind(action, binding);
+        self
+    }
+
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = self.bindings.entry(action.to_owned()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: &str, binding: Binding) {
+        if let Some(bindings) = self.bindings.get_mut(action) {
+            bindings.retain(|bound| *bound != binding);
+        }
+    }
+
+    /// Replaces every binding of `action`, for rebinding from a settings menu
+    pub fn set_bindings(&mut self, action: &str, bindings: Vec<Binding>) {
+        self.bindings.insert(action.to_owned(), bindings);
+    }
+
+    pub fn get_bindings(&self, action: &str) -> &[Binding] {
+        self.bindings.get(action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn get_actions(&self) -> impl Iterator<Item = &str> {
+        self.bindings.keys().map(String::as_str)
+    }
+
+    /// Actions of `other` replace the ones of the same name, like user bindings over defaults
+    pub fn merge(&mut self, other: ActionMap) {
+        self.bindings.extend(other.bindings);
+    }
+
+    pub fn parse(text: &str) -> Result<ActionMap, ParseError> {
+        let mut map = ActionMap::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let line = match line.find('#') {
+                Some(comment) => &line[..comment],
+                None => line,
+            }
+            .trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (action, bindings) = line.split_once('=').ok_or_else(|| ParseError {
+                line: line_number,
+                message: format!("expected \"action = binding\", found \"{}\"", line),
+            })?;
+            let action = action.trim();
+            if action.is_empty() || action.contains(char::is_whitespace) {
+                return Err(ParseError {
+                    line: line_number,
+                    message: format!("invalid action name \"{}\"", action),
+                });
+            }
+
+            let mut parsed = vec![];
+            for name in bindings.split(',').map(str::trim) {
+                if name.is_empty() {
+                    continue;
+                }
+                match Binding::from_name(name) {
+                    Some(binding) if !parsed.contains(&binding) => parsed.push(binding),
+                    Some(_) => (),
+                    None => {
+                        return Err(ParseError {
+                            line: line_number,
+                            message: format!("unknown key or button \"{}\"", name),
+                        })
+                    }
+                }
+            }
+            map.set_bindings(action, parsed);
+        }
+
+        Ok(map)
+    }
+
+    /// Config text that `parse` reads back into the same map
+    pub fn to_config_string(&self) -> String {
+        let mut text = String::new();
+        for (action, bindings) in &self.bindings {
+            let names: Vec<String> = bindings.iter().map(Binding::get_name).collect();
+            text.push_str(&format!("{} = {}\n", action, names.join(", ")));
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let map = ActionMap::parse(
+            "# movement\n\njump = Space # also on the pad\n   \nattack = MouseLeft, F\n",
+        )
+        .unwrap();
+
+        assert_eq!(map.get_bindings("jump"), &[Binding::Key(Key::Space)]);
+        assert_eq!(
+            map.get_bindings("attack"),
+            &[Binding::Mouse(MouseButton::Left), Binding::Key(Key::F)]
+        );
+        assert_eq!(
+            map.get_actions().collect::<Vec<_>>(),
+            vec!["attack", "jump"]
+        );
+    }
+
+    #[test]
+    fn duplicates_are_dropped_and_later_lines_win() {
+        let map =
+            ActionMap::parse("jump = Space, Space\nrun = LShift\nrun = RShift, , LShift").unwrap();
+
+        assert_eq!(map.get_bindings("jump"), &[Binding::Key(Key::Space)]);
+        assert_eq!(
+            map.get_bindings("run"),
+            &[Binding::Key(Key::RShift), Binding::Key(Key::LShift)]
+        );
+    }
+
+    #[test]
+    fn errors_carry_their_line_number() {
+        assert_eq!(
+            ActionMap::parse("jump = Space\n# fine\nattack = MouseLeft, Banana"),
+            Err(ParseError {
+                line: 3,
+                message: "unknown key or button \"Banana\"".to_owned(),
+            })
+        );
+        assert_eq!(ActionMap::parse("\njump Space").unwrap_err().line, 2);
+        assert_eq!(ActionMap::parse("move forward = W").unwrap_err().line, 1);
+        assert_eq!(ActionMap::parse(" = W").unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn config_strings_parse_back_into_the_same_map() {
+        let map = ActionMap::new()
+            .with_binding("jump", Binding::Key(Key::Space))
+            .with_binding("jump", Binding::Pad(GamepadButton::South))
+            .with_binding("attack", Binding::Mouse(MouseButton::Other(4)))
+            .with_binding(
+                "move_back",
+                Binding::PadAxis(GamepadAxis::LeftStickY, AxisDirection::Negative),
+            );
+
+        let text = map.to_config_string();
+        assert_eq!(ActionMap::parse(&text), Ok(map));
+    }
+}
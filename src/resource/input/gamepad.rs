@@ -0,0 +1,319 @@
+//! Gamepad buttons and axes as action sources. Events come from gilrs with the
+//! `gamepad` feature, the rest works on `InputEvent`s from any source
+
+/// Generates a gamepad element enum with its config name, `Pad` followed by the variant
+macro_rules! pad_elements {
+    ($(#[$meta:meta])* $name:ident { $($element:ident),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum $name {
+            $($element),*
+        }
+
+        impl $name {
+            pub const ALL: &'static [$name] = &[$($name::$element),*];
+
+            pub fn get_name(self) -> &'static str {
+                match self {
+                    $($name::$element => concat!("Pad", stringify!($element))),*
+                }
+            }
+
+            /// Case insensitive, as written in input configs
+            pub fn from_name(name: &str) -> Option<$name> {
+                $name::ALL
+                    .iter()
+                    .copied()
+                    .find(|element| element.get_name().eq_ignore_ascii_case(name))
+            }
+        }
+    };
+}
+
+pad_elements! {
+    /// Buttons by their position on a standard layout, `South` is A on Xbox pads
+    GamepadButton {
+        South, East, North, West,
+        LeftBumper, RightBumper, LeftTrigger, RightTrigger,
+        Select, Start, Mode, LeftStick, RightStick,
+        DPadUp, DPadDown, DPadLeft, DPadRight,
+    }
+}
+
+pad_elements! {
+    /// Sticks go from -1 to 1 with positive y up, triggers from 0 to 1
+    GamepadAxis {
+        LeftStickX, LeftStickY, RightStickX, RightStickY, LeftTrigger, RightTrigger,
+    }
+}
+
+impl GamepadAxis {
+    /// The other axis of the same stick, `None` for triggers
+    pub fn get_stick_pair(self) -> Option<GamepadAxis> {
+        match self {
+            GamepadAxis::LeftStickX => Some(GamepadAxis::LeftStickY),
+            GamepadAxis::LeftStickY => Some(GamepadAxis::LeftStickX),
+            GamepadAxis::RightStickX => Some(GamepadAxis::RightStickY),
+            GamepadAxis::RightStickY => Some(GamepadAxis::RightStickX),
+            GamepadAxis::LeftTrigger | GamepadAxis::RightTrigger => None,
+        }
+    }
+}
+
+/// Half of an axis an action is bound to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+impl AxisDirection {
+    pub fn get_sign(self) -> f32 {
+        match self {
+            AxisDirection::Positive => 1.0,
+            AxisDirection::Negative => -1.0,
+        }
+    }
+
+    pub fn get_suffix(self) -> char {
+        match self {
+            AxisDirection::Positive => '+',
+            AxisDirection::Negative => '-',
+        }
+    }
+}
+
+/// Values below `inner` read as 0, values above `outer` as 1, the range between is
+/// stretched so small deflections past the dead zone still register as small values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeadZone {
+    pub inner: f32,
+    pub outer: f32,
+}
+
+impl Default for DeadZone {
+    fn default() -> Self {
+        DeadZone {
+            inner: 0.15,
+            outer: 0.95,
+        }
+    }
+}
+
+impl DeadZone {
+    pub fn new(inner: f32, outer: f32) -> Self {
+        let inner = inner.clamp(0.0, 1.0);
+        DeadZone {
+            inner,
+            outer: outer.max(inner + f32::EPSILON).min(1.0),
+        }
+    }
+
+    /// Applied to one axis on its own, for triggers
+    pub fn apply(&self, value: f32) -> f32 {
+        let magnitude = value.abs();
+        if magnitude <= self.inner {
+            return 0.0;
+        }
+        value.signum() * ((magnitude - self.inner) / (self.outer - self.inner)).min(1.0)
+    }
+
+    /// Applied to the length of a stick, so diagonals are not cut off like with two
+    /// separate axes. Returns the scaled `(x, y)`
+    pub fn apply_radial(&self, x: f32, y: f32) -> (f32, f32) {
+        let length = (x * x + y * y).sqrt();
+        if length <= self.inner {
+            return (0.0, 0.0);
+        }
+        let scale = self.apply(length) / length;
+        (x * scale, y * scale)
+    }
+}
+
+#[cfg(feature = "gamepad")]
+pub use self::gilrs_source::{from_gilrs_event, Gamepads};
+
+#[cfg(feature = "gamepad")]
+mod gilrs_source {
+    use gilrs::{
+        ev::filter::{axis_dpad_to_button, FilterFn, Jitter},
+        Axis, Button, EventType, Gilrs, GilrsBuilder,
+    };
+
+    use super::{GamepadAxis, GamepadButton};
+    use crate::resource::input::InputEvent;
+
+    /// Connected controllers, polled once per frame for `InputEvent`s
+    pub struct Gamepads {
+        gilrs: Gilrs,
+        jitter: Jitter,
+    }
+
+    impl Gamepads {
+        /// Fails with the reason gilrs gave, for example an unsupported platform
+        pub fn new() -> Result<Gamepads, String> {
+            // Dead zones are applied by `UserInput` with its own settings
+            let gilrs = GilrsBuilder::new()
+                .with_default_filters(false)
+                .build()
+                .map_err(|err| err.to_string())?;
+
+            Ok(Gamepads {
+                gilrs,
+                jitter: Jitter::new(),
+            })
+        }
+
+        pub fn poll(&mut self) -> Vec<InputEvent> {
+            let mut events = vec![];
+            while let Some(event) = self.gilrs.next_event() {
+                let event = axis_dpad_to_button.filter(Some(event), &mut self.gilrs);
+                let event = self.jitter.filter(event, &mut self.gilrs);
+                if let Some(event) = event {
+                    events.extend(from_gilrs_event(event.id.into(), event.event));
+                }
+            }
+            events
+        }
+    }
+
+    /// `InputEvent` for a gilrs event of `gamepad`, `None` for what actions cannot bind
+    pub fn from_gilrs_event(gamepad: usize, event: EventType) -> Option<InputEvent> {
+        match event {
+            EventType::ButtonPressed(button, _) => convert_button(button)
+                .map(|button| InputEvent::GamepadButtonPressed { gamepad, button }),
+            EventType::ButtonReleased(button, _) => convert_button(button)
+                .map(|button| InputEvent::GamepadButtonReleased { gamepad, button }),
+            // Analog triggers report through their button
+            EventType::ButtonChanged(Button::LeftTrigger2, value, _) => {
+                Some(InputEvent::GamepadAxisMoved {
+                    gamepad,
+                    axis: GamepadAxis::LeftTrigger,
+                    value,
+                })
+            }
+            EventType::ButtonChanged(Button::RightTrigger2, value, _) => {
+                Some(InputEvent::GamepadAxisMoved {
+                    gamepad,
+                    axis: GamepadAxis::RightTrigger,
+                    value,
+                })
+            }
+            EventType::AxisChanged(axis, value, _) => {
+                convert_axis(axis).map(|axis| InputEvent::GamepadAxisMoved {
+                    gamepad,
+                    axis,
+                    value,
+                })
+            }
+            EventType::Disconnected => Some(InputEvent::GamepadDisconnected { gamepad }),
+            _ => None,
+        }
+    }
+
+    fn convert_button(button: Button) -> Option<GamepadButton> {
+        Some(match button {
+            Button::South => GamepadButton::South,
+            Button::East => GamepadButton::East,
+            Button::North => GamepadButton::North,
+            Button::West => GamepadButton::West,
+            Button::LeftTrigger => GamepadButton::LeftBumper,
+            Button::RightTrigger => GamepadButton::RightBumper,
+            Button::LeftTrigger2 => GamepadButton::LeftTrigger,
+            Button::RightTrigger2 => GamepadButton::RightTrigger,
+            Button::Select => GamepadButton::Select,
+            Button::Start => GamepadButton::Start,
+            Button::Mode => GamepadButton::Mode,
+            Button::LeftThumb => GamepadButton::LeftStick,
+            Button::RightThumb => GamepadButton::RightStick,
+            Button::DPadUp => GamepadButton::DPadUp,
+            Button::DPadDown => GamepadButton::DPadDown,
+            Button::DPadLeft => GamepadButton::DPadLeft,
+            Button::DPadRight => GamepadButton::DPadRight,
+            Button::C | Button::Z | Button::Unknown => return None,
+        })
+    }
+
+    fn convert_axis(axis: Axis) -> Option<GamepadAxis> {
+        Some(match axis {
+            Axis::LeftStickX => GamepadAxis::LeftStickX,
+            Axis::LeftStickY => GamepadAxis::LeftStickY,
+            Axis::RightStickX => GamepadAxis::RightStickX,
+            Axis::RightStickY => GamepadAxis::RightStickY,
+            // Triggers come through `ButtonChanged`, the d-pad as buttons
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: f32, expected: f32) {
+        assert!(
+            (value - expected).abs() < 1e-5,
+            "expected {}, got {}",
+            expected,
+            value
+        );
+    }
+
+    #[test]
+    fn dead_zone_scales_between_inner_and_outer() {
+        let dead_zone = DeadZone::new(0.15, 0.95);
+        assert_close(dead_zone.apply(0.0), 0.0);
+        assert_close(dead_zone.apply(0.1), 0.0);
+        assert_close(dead_zone.apply(0.15), 0.0);
+        assert_close(dead_zone.apply(0.55), 0.5);
+        assert_close(dead_zone.apply(-0.55), -0.5);
+        assert_close(dead_zone.apply(0.95), 1.0);
+        assert_close(dead_zone.apply(-1.0), -1.0);
+    }
+
+    #[test]
+    fn radial_dead_zone_works_on_the_stick_length() {
+        let dead_zone = DeadZone::new(0.15, 0.95);
+
+        // Inside the inner circle even though neither axis alone would be
+        assert_eq!(dead_zone.apply_radial(0.1, 0.1), (0.0, 0.0));
+
+        let (x, y) = dead_zone.apply_radial(0.0, 0.55);
+        assert_close(x, 0.0);
+        assert_close(y, 0.5);
+
+        // Diagonals keep their direction and reach the same length as straight pushes
+        let (x, y) = dead_zone.apply_radial(0.55 / 2f32.sqrt(), -0.55 / 2f32.sqrt());
+        assert_close(x, 0.5 / 2f32.sqrt());
+        assert_close(y, -0.5 / 2f32.sqrt());
+
+        // Past the outer circle the stick is at full length
+        let (x, y) = dead_zone.apply_radial(0.8, 0.8);
+        assert_close(x, 1.0 / 2f32.sqrt());
+        assert_close(y, 1.0 / 2f32.sqrt());
+    }
+
+    #[test]
+    fn dead_zone_bounds_are_kept_in_order() {
+        let dead_zone = DeadZone::new(0.5, 0.2);
+        assert!(dead_zone.outer > dead_zone.inner);
+        assert_close(dead_zone.apply(1.0), 1.0);
+
+        let dead_zone = DeadZone::new(-1.0, 2.0);
+        assert_eq!(dead_zone, DeadZone::new(0.0, 1.0));
+    }
+
+    #[test]
+    fn element_names_round_trip() {
+        for button in GamepadButton::ALL {
+            assert_eq!(GamepadButton::from_name(button.get_name()), Some(*button));
+        }
+        for axis in GamepadAxis::ALL {
+            assert_eq!(GamepadAxis::from_name(axis.get_name()), Some(*axis));
+        }
+        assert_eq!(
+            GamepadAxis::from_name("padleftsticky"),
+            Some(GamepadAxis::LeftStickY)
+        );
+    }
+}
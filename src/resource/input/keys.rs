@@ -0,0 +1,105 @@
+use glutin::event::VirtualKeyCode;
+
+/// Generates `Key` with its config name and the glutin key it comes from
+macro_rules! keys {
+    ($($key:ident => $glutin:ident),* $(,)?) => {
+        /// Keyboard key by its meaning in the current layout, not its physical position
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub enum Key {
+            $($key),*
+        }
+
+        impl Key {
+            pub const ALL: &'static [Key] = &[$(Key::$key),*];
+
+            pub fn get_name(self) -> &'static str {
+                match self {
+                    $(Key::$key => stringify!($key)),*
+                }
+            }
+
+            pub fn from_virtual_key(code: VirtualKeyCode) -> Option<Key> {
+                match code {
+                    $(VirtualKeyCode::$glutin => Some(Key::$key),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+keys! {
+    A => A, B => B, C => C, D => D, E => E, F => F, G => G, H => H, I => I, J => J,
+    K => K, L => L, M => M, N => N, O => O, P => P, Q => Q, R => R, S => S, T => T,
+    U => U, V => V, W => W, X => X, Y => Y, Z => Z,
+    Key0 => Key0, Key1 => Key1, Key2 => Key2, Key3 => Key3, Key4 => Key4,
+    Key5 => Key5, Key6 => Key6, Key7 => Key7, Key8 => Key8, Key9 => Key9,
+    F1 => F1, F2 => F2, F3 => F3, F4 => F4, F5 => F5, F6 => F6,
+    F7 => F7, F8 => F8, F9 => F9, F10 => F10, F11 => F11, F12 => F12,
+    Escape => Escape, Space => Space, Enter => Return, Tab => Tab, Backspace => Back,
+    LShift => LShift, RShift => RShift, LControl => LControl, RControl => RControl,
+    LAlt => LAlt, RAlt => RAlt, CapsLock => Capital,
+    Up => Up, Down => Down, Left => Left, Right => Right,
+    Insert => Insert, Delete => Delete, Home => Home, End => End,
+    PageUp => PageUp, PageDown => PageDown,
+    Minus => Minus, Equals => Equals, Comma => Comma, Period => Period, Slash => Slash,
+    Backslash => Backslash, Semicolon => Semicolon, Apostrophe => Apostrophe,
+    LBracket => LBracket, RBracket => RBracket, Grave => Grave,
+    Numpad0 => Numpad0, Numpad1 => Numpad1, Numpad2 => Numpad2, Numpad3 => Numpad3,
+    Numpad4 => Numpad4, Numpad5 => Numpad5, Numpad6 => Numpad6, Numpad7 => Numpad7,
+    Numpad8 => Numpad8, Numpad9 => Numpad9, NumpadEnter => NumpadEnter,
+}
+
+impl Key {
+    /// Case insensitive, as written in input configs
+    pub fn from_name(name: &str) -> Option<Key> {
+        Key::ALL
+            .iter()
+            .copied()
+            .find(|key| key.get_name().eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    /// Extra buttons, numbered by the platform
+    Other(u8),
+}
+
+impl MouseButton {
+    pub fn from_glutin(button: glutin::event::MouseButton) -> MouseButton {
+        match button {
+            glutin::event::MouseButton::Left => MouseButton::Left,
+            glutin::event::MouseButton::Right => MouseButton::Right,
+            glutin::event::MouseButton::Middle => MouseButton::Middle,
+            glutin::event::MouseButton::Other(index) => MouseButton::Other(index),
+        }
+    }
+
+    /// `MouseLeft`, `MouseRight`, `MouseMiddle` or `Mouse<number>`
+    pub fn get_name(self) -> String {
+        match self {
+            MouseButton::Left => "MouseLeft".to_owned(),
+            MouseButton::Right => "MouseRight".to_owned(),
+            MouseButton::Middle => "MouseMiddle".to_owned(),
+            MouseButton::Other(index) => format!("Mouse{}", index),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MouseButton> {
+        let rest = name
+            .get(..5)
+            .filter(|prefix| prefix.eq_ignore_ascii_case("mouse"))
+            .map(|_| &name[5..])?;
+
+        match rest.to_ascii_lowercase().as_str() {
+            "left" => Some(MouseButton::Left),
+            "right" => Some(MouseButton::Right),
+            "middle" => Some(MouseButton::Middle),
+            number => number.parse().ok().map(MouseButton::Other),
+        }
+    }
+}
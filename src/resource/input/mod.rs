@@ -0,0 +1,748 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use glutin::event::{DeviceEvent, ElementState, MouseScrollDelta, WindowEvent};
+
+use actions::{ActionMap, Binding};
+use gamepad::{DeadZone, GamepadAxis, GamepadButton};
+use keys::{Key, MouseButton};
+
+pub mod actions;
+pub mod gamepad;
+pub mod keys;
+pub mod recording;
+
+/// Pixels of a `PixelDelta` scroll that count as one line
+const PIXELS_PER_SCROLL_LINE: f32 = 20.0;
+
+/// Axis value past the dead zone at which an action bound to it counts as held
+const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+/// Input as the engine sees it, independent of the window system
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    KeyPressed(Key),
+    KeyReleased(Key),
+    MouseButtonPressed(MouseButton),
+    MouseButtonReleased(MouseButton),
+    /// Raw mouse movement, not limited by the window borders or cursor grabs
+    MouseMoved {
+        x: f32,
+        y: f32,
+    },
+    /// Cursor position in physical pixels from the top left of the window
+    CursorMoved {
+        x: f32,
+        y: f32,
+    },
+    /// In lines, positive `y` scrolls up
+    Scrolled {
+        x: f32,
+        y: f32,
+    },
+    /// The window lost focus, keys and mouse buttons held are released
+    FocusLost,
+    GamepadButtonPressed {
+        gamepad: usize,
+        button: GamepadButton,
+    },
+    GamepadButtonReleased {
+        gamepad: usize,
+        button: GamepadButton,
+    },
+    /// Raw value before the dead zone
+    GamepadAxisMoved {
+        gamepad: usize,
+        axis: GamepadAxis,
+        value: f32,
+    },
+    /// Everything held on the gamepad is released
+    GamepadDisconnected {
+        gamepad: usize,
+    },
+}
+
+impl InputEvent {
+    pub fn from_window_event(event: &WindowEvent) -> Option<InputEvent> {
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => {
+                let key = Key::from_virtual_key(input.virtual_keycode?)?;
+                Some(match input.state {
+                    ElementState::Pressed => InputEvent::KeyPressed(key),
+                    ElementState::Released => InputEvent::KeyReleased(key),
+                })
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = MouseButton::from_glutin(*button);
+                Some(match state {
+                    ElementState::Pressed => InputEvent::MouseButtonPressed(button),
+                    ElementState::Released => InputEvent::MouseButtonReleased(button),
+                })
+            }
+            WindowEvent::CursorMoved { position, .. } => Some(InputEvent::CursorMoved {
+                x: position.x as f32,
+                y: position.y as f32,
+            }),
+            WindowEvent::MouseWheel { delta, .. } => Some(match delta {
+                MouseScrollDelta::LineDelta(x, y) => InputEvent::Scrolled { x: *x, y: *y },
+                MouseScrollDelta::PixelDelta(position) => InputEvent::Scrolled {
+                    x: position.x as f32 / PIXELS_PER_SCROLL_LINE,
+                    y: position.y as f32 / PIXELS_PER_SCROLL_LINE,
+                },
+            }),
+            WindowEvent::Focused(false) => Some(InputEvent::FocusLost),
+            _ => None,
+        }
+    }
+
+    pub fn from_device_event(event: &DeviceEvent) -> Option<InputEvent> {
+        match event {
+            DeviceEvent::MouseMotion { delta } => Some(InputEvent::MouseMoved {
+                x: delta.0 as f32,
+                y: delta.1 as f32,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Held buttons plus the ones that changed since the last `end_step`
+#[derive(Debug, Clone)]
+struct ButtonState<T> {
+    down: BTreeSet<T>,
+    pressed: BTreeSet<T>,
+    released: BTreeSet<T>,
+}
+
+impl<T> Default for ButtonState<T> {
+    fn default() -> Self {
+        ButtonState {
+            down: BTreeSet::new(),
+            pressed: BTreeSet::new(),
+            released: BTreeSet::new(),
+        }
+    }
+}
+
+impl<T: Ord + Copy> ButtonState<T> {
+    fn press(&mut self, button: T) {
+        // Key repeat sends more presses while held, those are not new presses
+        if self.down.insert(button) {
+            self.pressed.insert(button);
+        }
+    }
+
+    fn release(&mut self, button: T) {
+        if self.down.remove(&button) {
+            self.released.insert(button);
+        }
+    }
+
+    fn release_all(&mut self) {
+        let down = std::mem::take(&mut self.down);
+        self.released.extend(down);
+    }
+
+    fn release_where<F: Fn(&T) -> bool>(&mut self, predicate: F) {
+        let released: Vec<T> = self.down.iter().copied().filter(predicate).collect();
+        for button in released {
+            self.release(button);
+        }
+    }
+
+    fn end_step(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+}
+
+/// Raw axis values by gamepad
+type AxisValues = BTreeMap<(usize, GamepadAxis), f32>;
+
+/// Keyboard, mouse and gamepad state, plus actions resolved through the `ActionMap`.
+///
+/// "Just pressed/released" and the mouse and scroll deltas cover everything since the
+/// previous fixed step, so a tap between two steps is seen by exactly one step.
+/// Gamepad queries look at every connected gamepad
+#[derive(Debug, Clone)]
+pub struct UserInput {
+    keys: ButtonState<Key>,
+    mouse_buttons: ButtonState<MouseButton>,
+    mouse_delta: cgmath::Vector2<f32>,
+    cursor_position: Option<cgmath::Vector2<f32>>,
+    scroll: cgmath::Vector2<f32>,
+    gamepad_buttons: ButtonState<(usize, GamepadButton)>,
+    gamepad_axes: AxisValues,
+    /// Axes at the end of the previous step, for the edges of axis bindings
+    previous_gamepad_axes: AxisValues,
+    stick_dead_zone: DeadZone,
+    trigger_dead_zone: DeadZone,
+    actions: ActionMap,
+}
+
+impl Default for UserInput {
+    fn default() -> Self {
+        UserInput::with_actions(ActionMap::new())
+    }
+}
+
+impl UserInput {
+    pub fn with_actions(actions: ActionMap) -> Self {
+        UserInput {
+            keys: ButtonState::default(),
+            mouse_buttons: ButtonState::default(),
+            mouse_delta: cgmath::vec2(0.0, 0.0),
+            cursor_position: None,
+            scroll: cgmath::vec2(0.0, 0.0),
+            gamepad_buttons: ButtonState::default(),
+            gamepad_axes: AxisValues::new(),
+            previous_gamepad_axes: AxisValues::new(),
+            stick_dead_zone: DeadZone::default(),
+            trigger_dead_zone: DeadZone::new(0.05, 0.95),
+            actions,
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::KeyPressed(key) => self.keys.press(key),
+            InputEvent::KeyReleased(key) => self.keys.release(key),
+            InputEvent::MouseButtonPressed(button) => self.mouse_buttons.press(button),
+            InputEvent::MouseButtonReleased(button) => self.mouse_buttons.release(button),
+            InputEvent::MouseMoved { x, y } => self.mouse_delta += cgmath::vec2(x, y),
+            InputEvent::CursorMoved { x, y } => self.cursor_position = Some(cgmath::vec2(x, y)),
+            InputEvent::Scrolled { x, y } => self.scroll += cgmath::vec2(x, y),
+            InputEvent::FocusLost => {
+                self.keys.release_all();
+                self.mouse_buttons.release_all();
+            }
+            InputEvent::GamepadButtonPressed { gamepad, button } => {
+                self.gamepad_buttons.press((gamepad, button))
+            }
+            InputEvent::GamepadButtonReleased { gamepad, button } => {
+                self.gamepad_buttons.release((gamepad, button))
+            }
+            InputEvent::GamepadAxisMoved {
+                gamepad,
+                axis,
+                value,
+            } => {
+                self.gamepad_axes.insert((gamepad, axis), value);
+            }
+            InputEvent::GamepadDisconnected { gamepad } => {
+                self.gamepad_buttons
+                    .release_where(|(pad, _)| *pad == gamepad);
+                self.gamepad_axes.retain(|(pad, _), _| *pad != gamepad);
+            }
+        }
+    }
+
+    /// Clears the per step state, called after every fixed step
+    pub fn end_step(&mut self) {
+        self.keys.end_step();
+        self.mouse_buttons.end_step();
+        self.mouse_delta = cgmath::vec2(0.0, 0.0);
+        self.scroll = cgmath::vec2(0.0, 0.0);
+        self.gamepad_buttons.end_step();
+        self.previous_gamepad_axes = self.gamepad_axes.clone();
+    }
+}
+
+/// Keyboard and mouse
+impl UserInput {
+    pub fn is_key_down(&self, key: Key) -> bool {
+        self.keys.down.contains(&key)
+    }
+
+    pub fn is_key_just_pressed(&self, key: Key) -> bool {
+        self.keys.pressed.contains(&key)
+    }
+
+    pub fn is_key_just_released(&self, key: Key) -> bool {
+        self.keys.released.contains(&key)
+    }
+
+    pub fn is_mouse_button_down(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.down.contains(&button)
+    }
+
+    pub fn is_mouse_button_just_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.pressed.contains(&button)
+    }
+
+    pub fn is_mouse_button_just_released(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.released.contains(&button)
+    }
+
+    /// Raw mouse movement since the previous step, in device units
+    pub fn get_mouse_delta(&self) -> cgmath::Vector2<f32> {
+        self.mouse_delta
+    }
+
+    /// `None` until the cursor entered the window
+    pub fn get_cursor_position(&self) -> Option<cgmath::Vector2<f32>> {
+        self.cursor_position
+    }
+
+    /// Lines scrolled since the previous step
+    pub fn get_scroll(&self) -> cgmath::Vector2<f32> {
+        self.scroll
+    }
+}
+
+/// Gamepads
+impl UserInput {
+    pub fn is_gamepad_button_down(&self, button: GamepadButton) -> bool {
+        self.gamepad_buttons
+            .down
+            .iter()
+            .any(|(_, down)| *down == button)
+    }
+
+    pub fn is_gamepad_button_just_pressed(&self, button: GamepadButton) -> bool {
+        let pressed = &self.gamepad_buttons.pressed;
+        pressed.iter().any(|(_, pressed)| *pressed == button)
+    }
+
+    /// True once the button is up on every gamepad
+    pub fn is_gamepad_button_just_released(&self, button: GamepadButton) -> bool {
+        let released = &self.gamepad_buttons.released;
+        !self.is_gamepad_button_down(button)
+            && released.iter().any(|(_, released)| *released == button)
+    }
+
+    /// Value after the dead zone, the one furthest from rest if several gamepads
+    /// move the axis
+    pub fn get_gamepad_axis(&self, axis: GamepadAxis) -> f32 {
+        self.get_axis_value(&self.gamepad_axes, axis)
+    }
+
+    pub fn get_stick_dead_zone(&self) -> DeadZone {
+        self.stick_dead_zone
+    }
+
+    pub fn set_stick_dead_zone(&mut self, dead_zone: DeadZone) {
+        self.stick_dead_zone = dead_zone;
+    }
+
+    pub fn get_trigger_dead_zone(&self) -> DeadZone {
+        self.trigger_dead_zone
+    }
+
+    pub fn set_trigger_dead_zone(&mut self, dead_zone: DeadZone) {
+        self.trigger_dead_zone = dead_zone;
+    }
+
+    fn get_axis_value(&self, axes: &AxisValues, axis: GamepadAxis) -> f32 {
+        let raw = |gamepad: usize, axis: GamepadAxis| {
+            axes.get(&(gamepad, axis)).copied().unwrap_or_default()
+        };
+
+        let mut result = 0.0f32;
+        for &(gamepad, moved) in axes.keys() {
+            if moved != axis {
+                continue;
+            }
+            let value = match axis.get_stick_pair() {
+                Some(pair) => {
+                    let (value, _) = self
+                        .stick_dead_zone
+                        .apply_radial(raw(gamepad, axis), raw(gamepad, pair));
+                    value
+                }
+                None => self.trigger_dead_zone.apply(raw(gamepad, axis)),
+            };
+            if value.abs() > result.abs() {
+                result = value;
+            }
+        }
+        result
+    }
+}
+
+/// Actions
+impl UserInput {
+    pub fn get_actions(&self) -> &ActionMap {
+        &self.actions
+    }
+
+    pub fn get_actions_mut(&mut self) -> &mut ActionMap {
+        &mut self.actions
+    }
+
+    pub fn set_actions(&mut self, actions: ActionMap) {
+        self.actions = actions;
+    }
+
+    /// 0 to 1, only the bound half of an axis counts
+    fn get_binding_value(&self, binding: &Binding, axes: &AxisValues) -> f32 {
+        match binding {
+            Binding::PadAxis(axis, direction) => {
+                (self.get_axis_value(axes, *axis) * direction.get_sign()).max(0.0)
+            }
+            _ if self.is_binding_down(binding) => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    fn is_binding_down(&self, binding: &Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.is_key_down(*key),
+            Binding::Mouse(button) => self.is_mouse_button_down(*button),
+            Binding::Pad(button) => self.is_gamepad_button_down(*button),
+            Binding::PadAxis(..) => {
+                self.get_binding_value(binding, &self.gamepad_axes) >= AXIS_PRESS_THRESHOLD
+            }
+        }
+    }
+
+    fn is_binding_just_pressed(&self, binding: &Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.is_key_just_pressed(*key),
+            Binding::Mouse(button) => self.is_mouse_button_just_pressed(*button),
+            Binding::Pad(button) => self.is_gamepad_button_just_pressed(*button),
+            Binding::PadAxis(..) => {
+                self.is_binding_down(binding)
+                    && self.get_binding_value(binding, &self.previous_gamepad_axes)
+                        < AXIS_PRESS_THRESHOLD
+            }
+        }
+    }
+
+    fn is_binding_just_released(&self, binding: &Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.is_key_just_released(*key),
+            Binding::Mouse(button) => self.is_mouse_button_just_released(*button),
+            Binding::Pad(button) => self.is_gamepad_button_just_released(*button),
+            Binding::PadAxis(..) => {
+                !self.is_binding_down(binding)
+                    && self.get_binding_value(binding, &self.previous_gamepad_axes)
+                        >= AXIS_PRESS_THRESHOLD
+            }
+        }
+    }
+
+    /// Whether any binding of `action` is held, axes count once they are pushed halfway.
+    /// False for unknown actions
+    pub fn is_action_down(&self, action: &str) -> bool {
+        let bindings = self.actions.get_bindings(action);
+        bindings.iter().any(|binding| self.is_binding_down(binding))
+    }
+
+    pub fn is_action_just_pressed(&self, action: &str) -> bool {
+        let bindings = self.actions.get_bindings(action);
+        bindings
+            .iter()
+            .any(|binding| self.is_binding_just_pressed(binding))
+    }
+
+    /// True once the last held binding of `action` was released
+    pub fn is_action_just_released(&self, action: &str) -> bool {
+        let bindings = self.actions.get_bindings(action);
+        !self.is_action_down(action)
+            && bindings
+                .iter()
+                .any(|binding| self.is_binding_just_released(binding))
+    }
+
+    /// 0 to 1, buttons give 1 while held and axes how far they are pushed.
+    /// The largest value of all bindings wins
+    pub fn get_action_value(&self, action: &str) -> f32 {
+        let bindings = self.actions.get_bindings(action);
+        bindings
+            .iter()
+            .map(|binding| self.get_binding_value(binding, &self.gamepad_axes))
+            .fold(0.0, f32::max)
+    }
+
+    /// `positive` minus `negative`, e.g. `get_axis("move_right", "move_left")`
+    pub fn get_axis(&self, positive: &str, negative: &str) -> f32 {
+        self.get_action_value(positive) - self.get_action_value(negative)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{actions::Binding, gamepad::AxisDirection, *};
+
+    fn pad_input() -> UserInput {
+        let actions = ActionMap::new()
+            .with_binding("jump", Binding::Pad(GamepadButton::South))
+            .with_binding(
+                "move_forward",
+                Binding::from_name("PadLeftStickY+").unwrap(),
+            )
+            .with_binding("move_back", Binding::from_name("PadLeftStickY-").unwrap());
+        UserInput::with_actions(actions)
+    }
+
+    fn axis_moved(gamepad: usize, axis: GamepadAxis, value: f32) -> InputEvent {
+        InputEvent::GamepadAxisMoved {
+            gamepad,
+            axis,
+            value,
+        }
+    }
+
+    fn assert_close(value: f32, expected: f32) {
+        assert!(
+            (value - expected).abs() < 1e-5,
+            "expected {}, got {}",
+            expected,
+            value
+        );
+    }
+
+    #[test]
+    fn axis_half_bindings_are_parsed() {
+        assert_eq!(
+            Binding::from_name("PadLeftStickY+"),
+            Some(Binding::PadAxis(
+                GamepadAxis::LeftStickY,
+                AxisDirection::Positive
+            ))
+        );
+        assert_eq!(
+            Binding::from_name("padrighttrigger-"),
+            Some(Binding::PadAxis(
+                GamepadAxis::RightTrigger,
+                AxisDirection::Negative
+            ))
+        );
+        // Without a sign the trigger is the button, not the axis
+        assert_eq!(
+            Binding::from_name("PadRightTrigger"),
+            Some(Binding::Pad(GamepadButton::RightTrigger))
+        );
+        assert_eq!(Binding::from_name("PadLeftStickY"), None);
+
+        let binding = Binding::PadAxis(GamepadAxis::RightStickX, AxisDirection::Negative);
+        assert_eq!(binding.get_name(), "PadRightStickX-");
+        assert_eq!(Binding::from_name(&binding.get_name()), Some(binding));
+    }
+
+    #[test]
+    fn gamepad_buttons_drive_actions() {
+        let mut input = pad_input();
+        let south = GamepadButton::South;
+
+        input.handle_event(&InputEvent::GamepadButtonPressed {
+            gamepad: 0,
+            button: south,
+        });
+        assert!(input.is_gamepad_button_down(south));
+        assert!(input.is_gamepad_button_just_pressed(south));
+        assert!(input.is_action_down("jump"));
+        assert!(input.is_action_just_pressed("jump"));
+        assert_close(input.get_action_value("jump"), 1.0);
+
+        input.end_step();
+        assert!(input.is_action_down("jump"));
+        assert!(!input.is_action_just_pressed("jump"));
+
+        input.handle_event(&InputEvent::GamepadButtonReleased {
+            gamepad: 0,
+            button: south,
+        });
+        assert!(!input.is_action_down("jump"));
+        assert!(input.is_action_just_released("jump"));
+    }
+
+    #[test]
+    fn buttons_held_on_another_gamepad_stay_down() {
+        let mut input = pad_input();
+        let south = GamepadButton::South;
+        for gamepad in 0..2 {
+            input.handle_event(&InputEvent::GamepadButtonPressed {
+                gamepad,
+                button: south,
+            });
+        }
+        input.end_step();
+
+        input.handle_event(&InputEvent::GamepadButtonReleased {
+            gamepad: 1,
+            button: south,
+        });
+        assert!(input.is_action_down("jump"));
+        assert!(!input.is_action_just_released("jump"));
+        assert!(!input.is_gamepad_button_just_released(south));
+    }
+
+    #[test]
+    fn stick_axes_go_through_the_radial_dead_zone() {
+        let mut input = pad_input();
+
+        input.handle_event(&axis_moved(0, GamepadAxis::LeftStickY, 0.1));
+        input.handle_event(&axis_moved(0, GamepadAxis::LeftStickX, 0.1));
+        assert_close(input.get_gamepad_axis(GamepadAxis::LeftStickY), 0.0);
+        assert_close(input.get_gamepad_axis(GamepadAxis::LeftStickX), 0.0);
+
+        input.handle_event(&axis_moved(0, GamepadAxis::LeftStickX, 0.0));
+        input.handle_event(&axis_moved(0, GamepadAxis::LeftStickY, 0.55));
+        assert_close(input.get_gamepad_axis(GamepadAxis::LeftStickY), 0.5);
+
+        // Triggers use their own, smaller dead zone
+        input.handle_event(&axis_moved(0, GamepadAxis::LeftTrigger, 0.04));
+        assert_close(input.get_gamepad_axis(GamepadAxis::LeftTrigger), 0.0);
+        input.handle_event(&axis_moved(0, GamepadAxis::LeftTrigger, 0.5));
+        assert_close(input.get_gamepad_axis(GamepadAxis::LeftTrigger), 0.5);
+    }
+
+    #[test]
+    fn axis_half_bindings_drive_actions() {
+        let mut input = pad_input();
+
+        // 0.75 after the default stick dead zone of 0.15 to 0.95
+        input.handle_event(&axis_moved(0, GamepadAxis::LeftStickY, 0.75));
+        assert_close(input.get_action_value("move_forward"), 0.75);
+        assert_close(input.get_action_value("move_back"), 0.0);
+        assert_close(input.get_axis("move_forward", "move_back"), 0.75);
+        assert!(input.is_action_down("move_forward"));
+        assert!(input.is_action_just_pressed("move_forward"));
+        assert!(!input.is_action_down("move_back"));
+
+        input.end_step();
+        assert!(!input.is_action_just_pressed("move_forward"));
+
+        input.handle_event(&axis_moved(0, GamepadAxis::LeftStickY, -0.75));
+        assert_close(input.get_axis("move_forward", "move_back"), -0.75);
+        assert!(input.is_action_just_released("move_forward"));
+        assert!(input.is_action_just_pressed("move_back"));
+
+        // Below the press threshold the value still counts, the action is not held
+        input.end_step();
+        input.handle_event(&axis_moved(0, GamepadAxis::LeftStickY, -0.35));
+        assert_close(input.get_action_value("move_back"), 0.25);
+        assert!(!input.is_action_down("move_back"));
+        assert!(input.is_action_just_released("move_back"));
+    }
+
+    #[test]
+    fn the_furthest_pushed_gamepad_wins() {
+        let mut input = pad_input();
+        input.handle_event(&axis_moved(0, GamepadAxis::LeftStickY, 0.35));
+        input.handle_event(&axis_moved(1, GamepadAxis::LeftStickY, -0.75));
+        assert_close(input.get_gamepad_axis(GamepadAxis::LeftStickY), -0.75);
+        // Both halves read the same winning value, the smaller push is ignored
+        assert_close(input.get_action_value("move_forward"), 0.0);
+        assert_close(input.get_axis("move_forward", "move_back"), -0.75);
+    }
+
+    #[test]
+    fn disconnecting_resets_that_gamepad() {
+        let mut input = pad_input();
+        let south = GamepadButton::South;
+        for gamepad in 0..2 {
+            input.handle_event(&InputEvent::GamepadButtonPressed {
+                gamepad,
+                button: GamepadButton::North,
+            });
+        }
+        input.handle_event(&InputEvent::GamepadButtonPressed {
+            gamepad: 1,
+            button: south,
+        });
+        input.handle_event(&axis_moved(1, GamepadAxis::LeftStickY, 0.75));
+        input.end_step();
+
+        input.handle_event(&InputEvent::GamepadDisconnected { gamepad: 1 });
+        assert!(!input.is_gamepad_button_down(south));
+        assert!(input.is_action_just_released("jump"));
+        assert_close(input.get_gamepad_axis(GamepadAxis::LeftStickY), 0.0);
+        assert!(input.is_action_just_released("move_forward"));
+
+        // The other gamepad is untouched
+        assert!(input.is_gamepad_button_down(GamepadButton::North));
+    }
+
+    #[test]
+    fn key_edges_last_one_step() {
+        let mut input = UserInput::default();
+        input.handle_event(&InputEvent::KeyPressed(Key::W));
+        assert!(input.is_key_down(Key::W));
+        assert!(input.is_key_just_pressed(Key::W));
+
+        // Key repeat while held is not a new press
+        input.end_step();
+        input.handle_event(&InputEvent::KeyPressed(Key::W));
+        assert!(input.is_key_down(Key::W));
+        assert!(!input.is_key_just_pressed(Key::W));
+
+        input.handle_event(&InputEvent::KeyReleased(Key::W));
+        assert!(!input.is_key_down(Key::W));
+        assert!(input.is_key_just_released(Key::W));
+
+        input.end_step();
+        assert!(!input.is_key_just_released(Key::W));
+    }
+
+    #[test]
+    fn taps_between_steps_are_seen_once() {
+        let mut input = UserInput::default();
+        input.handle_event(&InputEvent::MouseButtonPressed(MouseButton::Left));
+        input.handle_event(&InputEvent::MouseButtonReleased(MouseButton::Left));
+
+        assert!(!input.is_mouse_button_down(MouseButton::Left));
+        assert!(input.is_mouse_button_just_pressed(MouseButton::Left));
+        assert!(input.is_mouse_button_just_released(MouseButton::Left));
+
+        input.end_step();
+        assert!(!input.is_mouse_button_just_pressed(MouseButton::Left));
+        assert!(!input.is_mouse_button_just_released(MouseButton::Left));
+    }
+
+    #[test]
+    fn losing_focus_releases_keys_and_mouse_buttons() {
+        let mut input = UserInput::default();
+        input.handle_event(&InputEvent::KeyPressed(Key::Space));
+        input.handle_event(&InputEvent::MouseButtonPressed(MouseButton::Right));
+        input.end_step();
+
+        input.handle_event(&InputEvent::FocusLost);
+        assert!(!input.is_key_down(Key::Space));
+        assert!(input.is_key_just_released(Key::Space));
+        assert!(!input.is_mouse_button_down(MouseButton::Right));
+        assert!(input.is_mouse_button_just_released(MouseButton::Right));
+    }
+
+    #[test]
+    fn mouse_and_scroll_deltas_add_up_until_the_step_ends() {
+        let mut input = UserInput::default();
+        input.handle_event(&InputEvent::MouseMoved { x: 3.0, y: -1.0 });
+        input.handle_event(&InputEvent::MouseMoved { x: 2.0, y: 4.0 });
+        input.handle_event(&InputEvent::Scrolled { x: 0.0, y: 1.0 });
+        input.handle_event(&InputEvent::Scrolled { x: 0.5, y: 2.0 });
+        input.handle_event(&InputEvent::CursorMoved { x: 10.0, y: 20.0 });
+
+        assert_eq!(input.get_mouse_delta(), cgmath::vec2(5.0, 3.0));
+        assert_eq!(input.get_scroll(), cgmath::vec2(0.5, 3.0));
+
+        input.end_step();
+        assert_eq!(input.get_mouse_delta(), cgmath::vec2(0.0, 0.0));
+        assert_eq!(input.get_scroll(), cgmath::vec2(0.0, 0.0));
+        // The cursor stays where it is
+        assert_eq!(input.get_cursor_position(), Some(cgmath::vec2(10.0, 20.0)));
+    }
+
+    #[test]
+    fn key_and_mouse_bindings_drive_actions() {
+        let actions = ActionMap::parse("attack = MouseLeft, F").unwrap();
+        let mut input = UserInput::with_actions(actions);
+        input.handle_event(&InputEvent::KeyPressed(Key::F));
+        input.handle_event(&InputEvent::MouseButtonPressed(MouseButton::Left));
+        assert!(input.is_action_just_pressed("attack"));
+        assert_close(input.get_action_value("attack"), 1.0);
+        input.end_step();
+
+        // Still held through the mouse button
+        input.handle_event(&InputEvent::KeyReleased(Key::F));
+        assert!(input.is_action_down("attack"));
+        assert!(!input.is_action_just_released("attack"));
+        input.end_step();
+
+        input.handle_event(&InputEvent::MouseButtonReleased(MouseButton::Left));
+        assert!(!input.is_action_down("attack"));
+        assert!(input.is_action_just_released("attack"));
+        assert_close(input.get_action_value("attack"), 0.0);
+    }
+}
@@ -0,0 +1,456 @@
+//! Input events with the fixed step that first saw them, saved as text so a run can
+//! be replayed step for step:
+//!
+//! ```text
+//! vxl-input 1
+//! ticks 240
+//! bind jump = Space, PadSouth
+//! 12 KeyPressed Space
+//! 12 MouseMoved 1.5 -2
+//! 30 GamepadAxisMoved 0 PadLeftStickY 0.55
+//! ```
+//!
+//! The bindings are part of the recording, a replay does not depend on the config
+//! of the machine it runs on
+
+use std::{fmt, fs, io, path::Path, str::FromStr};
+
+use super::{
+    actions::{ActionMap, ParseError},
+    gamepad::{GamepadAxis, GamepadButton},
+    keys::{Key, MouseButton},
+    InputEvent, UserInput,
+};
+
+const HEADER: &str = "vxl-input 1";
+
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(io::Error),
+    Parse(ParseError),
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordingError::Io(error) => write!(f, "{}", error),
+            RecordingError::Parse(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RecordedEvent {
+    /// Fixed step the event is handled before
+    pub tick: u64,
+    pub event: InputEvent,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InputRecording {
+    actions: ActionMap,
+    events: Vec<RecordedEvent>,
+    /// Fixed steps the recording covers, events may end long before
+    tick_count: u64,
+}
+
+impl InputRecording {
+    pub fn new(actions: ActionMap) -> Self {
+        InputRecording {
+            actions,
+            events: vec![],
+            tick_count: 0,
+        }
+    }
+
+    /// Events have to be recorded in order, one with a `tick` before the last recorded
+    /// event is refused. Returns whether `event` was recorded
+    pub fn record(&mut self, tick: u64, event: InputEvent) -> bool {
+        if tick < self.get_last_tick() {
+            return false;
+        }
+        self.events.push(RecordedEvent { tick, event });
+        self.tick_count = self.tick_count.max(tick);
+        true
+    }
+
+    /// Never less than the tick of the last event
+    pub fn set_tick_count(&mut self, tick_count: u64) {
+        self.tick_count = tick_count.max(self.get_last_tick());
+    }
+
+    pub fn get_tick_count(&self) -> u64 {
+        self.tick_count
+    }
+
+    pub fn get_actions(&self) -> &ActionMap {
+        &self.actions
+    }
+
+    fn get_last_tick(&self) -> u64 {
+        self.events.last().map_or(0, |recorded| recorded.tick)
+    }
+
+    pub fn get_events(&self) -> &[RecordedEvent] {
+        &self.events
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<InputRecording, RecordingError> {
+        let text = fs::read_to_string(path).map_err(RecordingError::Io)?;
+        InputRecording::parse(&text).map_err(RecordingError::Parse)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    pub fn parse(text: &str) -> Result<InputRecording, ParseError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        match lines.next() {
+            Some((_, HEADER)) => (),
+            _ => return Err(error(1, format!("expected \"{}\"", HEADER))),
+        }
+
+        let mut recording = InputRecording::default();
+        let mut tick_count = 0;
+        for (line_number, line) in lines {
+            let mut words = line.split_whitespace();
+            let first = words.next().unwrap_or_default();
+
+            if first == "ticks" {
+                tick_count = parse_word(&mut words, line_number, "tick count")?;
+            } else if first == "bind" {
+                let binding = line["bind".len()..].trim();
+                let actions =
+                    ActionMap::parse(binding).map_err(|err| error(line_number, err.message))?;
+                recording.actions.merge(actions);
+            } else {
+                let tick = first
+                    .parse()
+                    .map_err(|_| error(line_number, format!("invalid tick \"{}\"", first)))?;
+                let event = parse_event(&mut words, line_number)?;
+                if words.next().is_some() {
+                    return Err(error(line_number, "too many values".to_owned()));
+                }
+                if !recording.record(tick, event) {
+                    return Err(error(
+                        line_number,
+                        format!(
+                            "tick {} is before the previous event at {}",
+                            tick,
+                            recording.get_last_tick()
+                        ),
+                    ));
+                }
+            }
+        }
+        recording.set_tick_count(tick_count);
+
+        Ok(recording)
+    }
+
+    /// Text that `parse` reads back into the same recording
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\nticks {}\n", HEADER, self.tick_count);
+        for line in self.actions.to_config_string().lines() {
+            text.push_str(&format!("bind {}\n", line));
+        }
+        for recorded in &self.events {
+            text.push_str(&format!(
+                "{} {}\n",
+                recorded.tick,
+                event_to_text(&recorded.event)
+            ));
+        }
+        text
+    }
+}
+
+/// Feeds a recording back into `UserInput` one fixed step at a time
+#[derive(Debug, Clone)]
+pub struct InputReplay {
+    recording: InputRecording,
+    next_event: usize,
+}
+
+impl InputReplay {
+    pub fn new(recording: InputRecording) -> Self {
+        InputReplay {
+            recording,
+            next_event: 0,
+        }
+    }
+
+    pub fn get_recording(&self) -> &InputRecording {
+        &self.recording
+    }
+
+    /// Handles every event up to `tick`, call it right before that step runs
+    pub fn apply(&mut self, tick: u64, input: &mut UserInput) {
+        let events = &self.recording.events[self.next_event..];
+        for recorded in events.iter().take_while(|recorded| recorded.tick <= tick) {
+            input.handle_event(&recorded.event);
+            self.next_event += 1;
+        }
+    }
+
+    /// Whether the step `tick` is past the end of the recording
+    pub fn is_finished(&self, tick: u64) -> bool {
+        tick > self.recording.tick_count
+    }
+}
+
+fn error(line: usize, message: String) -> ParseError {
+    ParseError { line, message }
+}
+
+fn parse_word<'a, T: FromStr, I: Iterator<Item = &'a str>>(
+    words: &mut I,
+    line: usize,
+    what: &str,
+) -> Result<T, ParseError> {
+    let word = words
+        .next()
+        .ok_or_else(|| error(line, format!("missing {}", what)))?;
+    word.parse()
+        .map_err(|_| error(line, format!("invalid {} \"{}\"", what, word)))
+}
+
+fn parse_named<'a, T, I, F>(
+    words: &mut I,
+    line: usize,
+    what: &str,
+    from_name: F,
+) -> Result<T, ParseError>
+where
+    I: Iterator<Item = &'a str>,
+    F: Fn(&str) -> Option<T>,
+{
+    let word = words
+        .next()
+        .ok_or_else(|| error(line, format!("missing {}", what)))?;
+    from_name(word).ok_or_else(|| error(line, format!("unknown {} \"{}\"", what, word)))
+}
+
+fn parse_event<'a, I: Iterator<Item = &'a str>>(
+    words: &mut I,
+    line: usize,
+) -> Result<InputEvent, ParseError> {
+    let name = words
+        .next()
+        .ok_or_else(|| error(line, "missing event".to_owned()))?;
+
+    let event = match name {
+        "KeyPressed" => InputEvent::KeyPressed(parse_named(words, line, "key", Key::from_name)?),
+        "KeyReleased" => InputEvent::KeyReleased(parse_named(words, line, "key", Key::from_name)?),
+        "MouseButtonPressed" => InputEvent::MouseButtonPressed(parse_named(
+            words,
+            line,
+            "mouse button",
+            MouseButton::from_name,
+        )?),
+        "MouseButtonReleased" => InputEvent::MouseButtonReleased(parse_named(
+            words,
+            line,
+            "mouse button",
+            MouseButton::from_name,
+        )?),
+        "MouseMoved" => InputEvent::MouseMoved {
+            x: parse_word(words, line, "x")?,
+            y: parse_word(words, line, "y")?,
+        },
+        "CursorMoved" => InputEvent::CursorMoved {
+            x: parse_word(words, line, "x")?,
+            y: parse_word(words, line, "y")?,
+        },
+        "Scrolled" => InputEvent::Scrolled {
+            x: parse_word(words, line, "x")?,
+            y: parse_word(words, line, "y")?,
+        },
+        "FocusLost" => InputEvent::FocusLost,
+        "GamepadButtonPressed" => InputEvent::GamepadButtonPressed {
+            gamepad: parse_word(words, line, "gamepad")?,
+            button: parse_named(words, line, "gamepad button", GamepadButton::from_name)?,
+        },
+        "GamepadButtonReleased" => InputEvent::GamepadButtonReleased {
+            gamepad: parse_word(words, line, "gamepad")?,
+            button: parse_named(words, line, "gamepad button", GamepadButton::from_name)?,
+        },
+        "GamepadAxisMoved" => InputEvent::GamepadAxisMoved {
+            gamepad: parse_word(words, line, "gamepad")?,
+            axis: parse_named(words, line, "gamepad axis", GamepadAxis::from_name)?,
+            value: parse_word(words, line, "axis value")?,
+        },
+        "GamepadDisconnected" => InputEvent::GamepadDisconnected {
+            gamepad: parse_word(words, line, "gamepad")?,
+        },
+        _ => return Err(error(line, format!("unknown event \"{}\"", name))),
+    };
+
+    Ok(event)
+}
+
+/// Floats are written with the shortest text that parses back to the same value
+fn event_to_text(event: &InputEvent) -> String {
+    match *event {
+        InputEvent::KeyPressed(key) => format!("KeyPressed {}", key.get_name()),
+        InputEvent::KeyReleased(key) => format!("KeyReleased {}", key.get_name()),
+        InputEvent::MouseButtonPressed(button) => {
+            format!("MouseButtonPressed {}", button.get_name())
+        }
+        InputEvent::MouseButtonReleased(button) => {
+            format!("MouseButtonReleased {}", button.get_name())
+        }
+        InputEvent::MouseMoved { x, y } => format!("MouseMoved {} {}", x, y),
+        InputEvent::CursorMoved { x, y } => format!("CursorMoved {} {}", x, y),
+        InputEvent::Scrolled { x, y } => format!("Scrolled {} {}", x, y),
+        InputEvent::FocusLost => "FocusLost".to_owned(),
+        InputEvent::GamepadButtonPressed { gamepad, button } => {
+            format!("GamepadButtonPressed {} {}", gamepad, button.get_name())
+        }
+        InputEvent::GamepadButtonReleased { gamepad, button } => {
+            format!("GamepadButtonReleased {} {}", gamepad, button.get_name())
+        }
+        InputEvent::GamepadAxisMoved {
+            gamepad,
+            axis,
+            value,
+        } => format!("GamepadAxisMoved {} {} {}", gamepad, axis.get_name(), value),
+        InputEvent::GamepadDisconnected { gamepad } => format!("GamepadDisconnected {}", gamepad),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::actions::Binding, *};
+
+    fn sample() -> InputRecording {
+        let actions = ActionMap::new()
+            .with_binding("jump", Binding::Key(Key::Space))
+            .with_binding("jump", Binding::Pad(GamepadButton::South))
+            .with_binding(
+                "move_forward",
+                Binding::from_name("PadLeftStickY+").unwrap(),
+            );
+        let mut recording = InputRecording::new(actions);
+        let events = [
+            (1, InputEvent::KeyPressed(Key::Space)),
+            (1, InputEvent::MouseMoved { x: 1.5, y: -2.0 }),
+            (3, InputEvent::CursorMoved { x: 0.1, y: 1e-7 }),
+            (3, InputEvent::KeyReleased(Key::Space)),
+            (5, InputEvent::MouseButtonPressed(MouseButton::Left)),
+            (6, InputEvent::MouseButtonReleased(MouseButton::Other(9))),
+            (7, InputEvent::Scrolled { x: 0.0, y: -1.0 }),
+            (7, InputEvent::FocusLost),
+            (
+                8,
+                InputEvent::GamepadButtonPressed {
+                    gamepad: 1,
+                    button: GamepadButton::South,
+                },
+            ),
+            (
+                9,
+                InputEvent::GamepadAxisMoved {
+                    gamepad: 1,
+                    axis: GamepadAxis::LeftStickY,
+                    value: 0.55,
+                },
+            ),
+            (
+                9,
+                InputEvent::GamepadButtonReleased {
+                    gamepad: 1,
+                    button: GamepadButton::South,
+                },
+            ),
+            (12, InputEvent::GamepadDisconnected { gamepad: 1 }),
+        ];
+        for (tick, event) in events.iter() {
+            assert!(recording.record(*tick, *event));
+        }
+        recording.set_tick_count(20);
+        recording
+    }
+
+    #[test]
+    fn text_round_trips() {
+        let recording = sample();
+        let parsed = InputRecording::parse(&recording.to_text()).unwrap();
+        assert_eq!(parsed, recording);
+        assert_eq!(parsed.get_tick_count(), 20);
+        assert_eq!(parsed.get_events().len(), 12);
+    }
+
+    #[test]
+    fn saved_recordings_load_back() {
+        let path = std::env::temp_dir().join(format!(
+            "vxl-recording-round-trip-{}.txt",
+            std::process::id()
+        ));
+        let recording = sample();
+        recording.save(&path).unwrap();
+        let loaded = InputRecording::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap(), recording);
+    }
+
+    #[test]
+    fn out_of_order_ticks_are_refused() {
+        let mut recording = InputRecording::default();
+        assert!(recording.record(4, InputEvent::FocusLost));
+        assert!(recording.record(4, InputEvent::FocusLost));
+        assert!(!recording.record(3, InputEvent::FocusLost));
+        assert_eq!(recording.get_events().len(), 2);
+
+        let text = "vxl-input 1\nticks 10\n4 KeyPressed Space\n2 KeyReleased Space\n";
+        match InputRecording::parse(text) {
+            Err(ParseError { line, .. }) => assert_eq!(line, 4),
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn tick_count_covers_every_event() {
+        let recording = InputRecording::parse("vxl-input 1\nticks 2\n5 FocusLost\n").unwrap();
+        assert_eq!(recording.get_tick_count(), 5);
+    }
+
+    #[test]
+    fn malformed_lines_are_rejected() {
+        let line_of = |text: &str| match InputRecording::parse(text) {
+            Err(error) => error.line,
+            Ok(recording) => panic!("expected a parse error, got {:?}", recording),
+        };
+        assert_eq!(line_of("ticks 4\n"), 1);
+        assert_eq!(line_of("vxl-input 1\nx KeyPressed Space\n"), 2);
+        assert_eq!(line_of("vxl-input 1\n1 KeyPressed Nope\n"), 2);
+        assert_eq!(line_of("vxl-input 1\n\n1 MouseMoved 1\n"), 3);
+        assert_eq!(line_of("vxl-input 1\n1 FocusLost now\n"), 2);
+        assert_eq!(line_of("vxl-input 1\n1 Teleported\n"), 2);
+    }
+
+    #[test]
+    fn replay_hands_out_events_up_to_each_step() {
+        let mut replay = InputReplay::new(sample());
+        let mut input = UserInput::with_actions(replay.get_recording().get_actions().clone());
+
+        replay.apply(1, &mut input);
+        assert!(input.is_action_just_pressed("jump"));
+        assert_eq!(input.get_mouse_delta(), cgmath::vec2(1.5, -2.0));
+        input.end_step();
+
+        replay.apply(2, &mut input);
+        assert!(input.is_action_down("jump"));
+        replay.apply(3, &mut input);
+        assert!(!input.is_action_down("jump"));
+
+        assert!(!replay.is_finished(20));
+        assert!(replay.is_finished(21));
+    }
+}
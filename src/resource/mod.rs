@@ -1,34 +1,42 @@
-use cgmath::prelude::*;
-use tasks::{MainCameraTask, RenderTask};
+use tasks::{CameraTask, RenderTask};
 
+pub mod cursor;
 pub mod input;
+pub mod scene;
+pub mod screen;
 pub mod tasks;
+pub mod time;
 
-pub struct DeltaTime {
-    raw_time: u32,
-    delta: f32,
+/// How far rendering is between the previous and the current fixed step, 0 to 1.
+/// Transforms are interpolated with it, see `PreviousTransform`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interpolation {
+    alpha: f32,
 }
 
-impl DeltaTime {
-    pub fn new(raw_time: u32) -> Self {
-        DeltaTime {
-            raw_time,
-            delta: raw_time as f32 / 16700.0,
+impl Interpolation {
+    pub fn new(alpha: f32) -> Self {
+        Interpolation {
+            alpha: alpha.clamp(0.0, 1.0),
         }
     }
 
-    pub fn get_raw(&self) -> u32 {
-        self.raw_time
+    pub fn get_alpha(&self) -> f32 {
+        self.alpha
     }
+}
 
-    pub fn get_delta(&self) -> f32 {
-        self.delta
+impl Default for Interpolation {
+    fn default() -> Self {
+        Interpolation { alpha: 1.0 }
     }
 }
 
+/// Everything to draw this frame, filled by the frame systems
+#[derive(Default)]
 pub struct Task {
     render: Vec<RenderTask>,
-    main_camera: MainCameraTask,
+    cameras: Vec<CameraTask>,
 }
 impl Task {
     pub fn push_render_task(&mut self, task: RenderTask) {
@@ -39,23 +47,12 @@ impl Task {
         &self.render
     }
 
-    pub fn set_main_camera_task(&mut self, task: MainCameraTask) {
-        self.main_camera = task
+    /// Cameras are drawn in the order they are pushed
+    pub fn push_camera_task(&mut self, task: CameraTask) {
+        self.cameras.push(task);
     }
 
-    pub fn get_main_camera_task(&self) -> &MainCameraTask {
-        &self.main_camera
-    }
-}
-
-impl Default for Task {
-    fn default() -> Self {
-        Task {
-            render: vec![],
-            main_camera: MainCameraTask::new(
-                cgmath::Matrix4::identity(),
-                cgmath::Matrix4::identity(),
-            ),
-        }
+    pub fn get_camera_tasks(&self) -> &Vec<CameraTask> {
+        &self.cameras
     }
 }
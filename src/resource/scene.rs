@@ -0,0 +1,25 @@
+/// Name of the running scene, and the scene to switch to at the start of the next frame.
+/// Switching deletes every entity and unloads the assets nothing references anymore
+#[derive(Debug, Clone, Default)]
+pub struct Scenes {
+    current: Option<String>,
+    requested: Option<String>,
+}
+
+impl Scenes {
+    pub fn request(&mut self, name: &str) {
+        self.requested = Some(name.to_owned());
+    }
+
+    pub fn take_request(&mut self) -> Option<String> {
+        self.requested.take()
+    }
+
+    pub fn set_current(&mut self, name: &str) {
+        self.current = Some(name.to_owned());
+    }
+
+    pub fn get_current(&self) -> Option<&str> {
+        self.current.as_deref()
+    }
+}
@@ -0,0 +1,28 @@
+/// Size of the window's framebuffer in pixels, updated by the `App` when it is resized
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Screen {
+    width: u32,
+    height: u32,
+}
+
+impl Screen {
+    pub fn new(width: u32, height: u32) -> Self {
+        Screen { width, height }
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    /// Width divided by height, `None` while the window is minimized
+    pub fn get_aspect(&self) -> Option<f32> {
+        if self.width == 0 || self.height == 0 {
+            return None;
+        }
+        Some(self.width as f32 / self.height as f32)
+    }
+}
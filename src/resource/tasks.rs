@@ -1,29 +1,75 @@
-use crate::vxl_gl::gl;
+use crate::{
+    component::camera::Clear,
+    vxl_gl::{gl, UniformValue},
+};
+
+/// Texture and optional sampler object bound to a texture unit for a draw call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureBinding {
+    unit: u32,
+    texture_id: gl::types::GLuint,
+    sampler_id: Option<gl::types::GLuint>,
+}
+
+impl TextureBinding {
+    pub fn new(
+        unit: u32,
+        texture_id: gl::types::GLuint,
+        sampler_id: Option<gl::types::GLuint>,
+    ) -> Self {
+        TextureBinding {
+            unit,
+            texture_id,
+            sampler_id,
+        }
+    }
+
+    pub fn get_unit(&self) -> u32 {
+        self.unit
+    }
+
+    pub fn get_texture_id(&self) -> gl::types::GLuint {
+        self.texture_id
+    }
+
+    pub fn get_sampler_id(&self) -> Option<gl::types::GLuint> {
+        self.sampler_id
+    }
+}
 
 pub struct RenderTask {
     program_id: gl::types::GLuint,
     vao_id: gl::types::GLuint,
     vertex_count: i32,
     attrib_arrays: Vec<gl::types::GLuint>,
-    mat4f_uniforms: Vec<(&'static str, cgmath::Matrix4<f32>)>,
-    texture_id: Option<gl::types::GLuint>,
+    /// Locations of `proj_mat` and `view_mat`, set per camera
+    camera_locations: (i32, i32),
+    /// Values by their reflected uniform location
+    uniforms: Vec<(i32, UniformValue)>,
+    textures: Vec<TextureBinding>,
+    layers: u32,
 }
 impl RenderTask {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         program_id: gl::types::GLuint,
         vao_id: gl::types::GLuint,
         vertex_count: i32,
         attrib_arrays: Vec<gl::types::GLuint>,
-        mat4f_uniforms: Vec<(&'static str, cgmath::Matrix4<f32>)>,
-        texture_id: Option<gl::types::GLuint>,
+        camera_locations: (i32, i32),
+        uniforms: Vec<(i32, UniformValue)>,
+        textures: Vec<TextureBinding>,
+        layers: u32,
     ) -> Self {
         RenderTask {
             program_id,
             vao_id,
             vertex_count,
             attrib_arrays,
-            mat4f_uniforms,
-            texture_id,
+            camera_locations,
+            uniforms,
+            textures,
+            layers,
         }
     }
     pub fn get_pid(&self) -> gl::types::GLuint {
@@ -42,25 +88,51 @@ impl RenderTask {
         &self.attrib_arrays
     }
 
-    pub fn get_mat4f_unifroms(&self) -> &Vec<(&str, cgmath::Matrix4<f32>)> {
-        &self.mat4f_uniforms
+    pub fn get_camera_locations(&self) -> (i32, i32) {
+        self.camera_locations
     }
 
-    pub fn get_texture_id(&self) -> Option<gl::types::GLuint> {
-        self.texture_id
+    pub fn get_uniforms(&self) -> &Vec<(i32, UniformValue)> {
+        &self.uniforms
+    }
+
+    pub fn get_textures(&self) -> &Vec<TextureBinding> {
+        &self.textures
+    }
+
+    pub fn get_layers(&self) -> u32 {
+        self.layers
     }
 }
 
-pub struct MainCameraTask {
+/// One camera's pass: where it draws and what it sees
+pub struct CameraTask {
     projection_mat: cgmath::Matrix4<f32>,
     view_mat: cgmath::Matrix4<f32>,
+    /// 0 for the screen
+    fbo_id: gl::types::GLuint,
+    /// `x`, `y`, `width` and `height` in pixels
+    viewport: (i32, i32, i32, i32),
+    layer_mask: u32,
+    clear: Clear,
 }
 
-impl MainCameraTask {
-    pub fn new(projection_mat: cgmath::Matrix4<f32>, view_mat: cgmath::Matrix4<f32>) -> Self {
-        MainCameraTask {
+impl CameraTask {
+    pub fn new(
+        projection_mat: cgmath::Matrix4<f32>,
+        view_mat: cgmath::Matrix4<f32>,
+        fbo_id: gl::types::GLuint,
+        viewport: (i32, i32, i32, i32),
+        layer_mask: u32,
+        clear: Clear,
+    ) -> Self {
+        CameraTask {
             projection_mat,
             view_mat,
+            fbo_id,
+            viewport,
+            layer_mask,
+            clear,
         }
     }
 
@@ -71,4 +143,20 @@ impl MainCameraTask {
     pub fn get_view_mat(&self) -> cgmath::Matrix4<f32> {
         self.view_mat
     }
+
+    pub fn get_fbo_id(&self) -> gl::types::GLuint {
+        self.fbo_id
+    }
+
+    pub fn get_viewport(&self) -> (i32, i32, i32, i32) {
+        self.viewport
+    }
+
+    pub fn get_layer_mask(&self) -> u32 {
+        self.layer_mask
+    }
+
+    pub fn get_clear(&self) -> Clear {
+        self.clear
+    }
 }
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+/// Timing of the fixed simulation steps and the rendered frames, kept up to date by
+/// the `GameLoop`. Systems of the fixed step should move things by `get_fixed_delta`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Time {
+    tick_count: u64,
+    fixed_delta: Duration,
+    frame_delta: Duration,
+    elapsed: Duration,
+}
+
+impl Time {
+    pub fn new(fixed_delta: Duration) -> Self {
+        Time {
+            tick_count: 0,
+            fixed_delta,
+            frame_delta: Duration::new(0, 0),
+            elapsed: Duration::new(0, 0),
+        }
+    }
+
+    pub(crate) fn advance_tick(&mut self) {
+        self.tick_count += 1;
+    }
+
+    pub(crate) fn set_frame(&mut self, frame_delta: Duration, elapsed: Duration) {
+        self.frame_delta = frame_delta;
+        self.elapsed = elapsed;
+    }
+
+    /// Fixed steps run so far, including the one currently running
+    pub fn get_tick_count(&self) -> u64 {
+        self.tick_count
+    }
+
+    /// Length of a fixed step in seconds
+    pub fn get_fixed_delta(&self) -> f32 {
+        self.fixed_delta.as_secs_f32()
+    }
+
+    pub fn get_fixed_duration(&self) -> Duration {
+        self.fixed_delta
+    }
+
+    /// Seconds between the last two rendered frames
+    pub fn get_frame_delta(&self) -> f32 {
+        self.frame_delta.as_secs_f32()
+    }
+
+    /// Wall clock seconds since the loop started, as of the last frame
+    pub fn get_elapsed(&self) -> f32 {
+        self.elapsed.as_secs_f32()
+    }
+
+    /// Seconds of simulated time, `tick count * fixed delta`
+    pub fn get_simulation_time(&self) -> f32 {
+        self.tick_count as f32 * self.get_fixed_delta()
+    }
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Time::new(Duration::from_secs(1) / 60)
+    }
+}
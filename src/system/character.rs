@@ -0,0 +1,532 @@
+use cgmath::{prelude::*, Deg, Quaternion, Vector3};
+use specs::prelude::*;
+
+use crate::{
+    component::{
+        camera::MainCamera,
+        character::{CharacterController, MovementMode},
+        physics::{Collider, Gravity, Velocity},
+        transform::{Parent, Transform},
+    },
+    physics::collision::{clamp_to_edges, intersects_kind},
+    resource::{
+        cursor::Cursor,
+        input::{keys::MouseButton, UserInput},
+        time::Time,
+    },
+    voxel::{world::VoxelWorld, BlockKind},
+};
+
+/// Turns input into the `Velocity` of entities with a `CharacterController` and points
+/// their `MainCamera` child. Runs in the fixed step before the `PhysicsSys`, which
+/// does the moving, and keeps the `Collider` in shape for the current pose.
+/// Walking characters fall by `CharacterSettings::gravity`, unless the entity has a
+/// `Gravity`, which the `PhysicsSys` applies instead so it does not fall twice
+pub struct CharacterControllerSys;
+impl<'a> System<'a> for CharacterControllerSys {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Parent>,
+        ReadStorage<'a, MainCamera>,
+        ReadStorage<'a, Gravity>,
+        WriteStorage<'a, CharacterController>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Collider>,
+        WriteStorage<'a, Transform>,
+        Read<'a, UserInput>,
+        Write<'a, Cursor>,
+        Read<'a, VoxelWorld>,
+        ReadExpect<'a, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            parent,
+            main_camera,
+            gravity,
+            mut controller,
+            mut velocity,
+            mut collider,
+            mut transform,
+            input,
+            mut cursor,
+            world,
+            time,
+        ): Self::SystemData,
+    ) {
+        let delta = time.get_fixed_delta();
+        let mut eyes = vec![];
+
+        for (entity, controller, velocity, collider, transform, gravity) in (
+            &entities,
+            &mut controller,
+            &mut velocity,
+            &mut collider,
+            &mut transform,
+            gravity.maybe(),
+        )
+            .join()
+        {
+            let settings = controller.get_settings().clone();
+
+            if settings.grab_cursor {
+                if input.is_action_just_pressed("pause") {
+                    cursor.set_grabbed(false);
+                } else if input.is_mouse_button_just_pressed(MouseButton::Left) {
+                    cursor.set_grabbed(true);
+                }
+            }
+
+            let mut yaw = controller.get_yaw();
+            let mut pitch = controller.get_pitch();
+            if cursor.is_grabbed() || !settings.grab_cursor {
+                let mouse = input.get_mouse_delta();
+                yaw -= mouse.x * settings.mouse_sensitivity;
+                pitch -= mouse.y * settings.mouse_sensitivity;
+            }
+            yaw += input.get_axis("look_left", "look_right") * settings.look_speed * delta;
+            pitch += input.get_axis("look_up", "look_down") * settings.look_speed * delta;
+            controller.set_look(yaw, pitch);
+            // The body only turns around Y, the camera child does the pitch
+            transform.set_rotation(Quaternion::from_angle_y(Deg(controller.get_yaw())));
+
+            let mut mode = controller.get_mode();
+            if input.is_action_just_pressed("toggle_fly") {
+                mode = match mode {
+                    MovementMode::Flying => MovementMode::Walking,
+                    _ => MovementMode::Flying,
+                };
+            }
+            if input.is_action_just_pressed("toggle_noclip") {
+                mode = match mode {
+                    MovementMode::Noclip => MovementMode::Walking,
+                    _ => MovementMode::Noclip,
+                };
+            }
+            controller.set_mode(mode);
+            collider.set_enabled(mode != MovementMode::Noclip);
+
+            // Standing up needs room for the full height
+            let position = transform.get_position();
+            let standing = settings.get_collider().get_aabb(position);
+            let crouching = mode == MovementMode::Walking
+                && (input.is_action_down("crouch")
+                    || (controller.is_crouching()
+                        && intersects_kind(&world, &standing, BlockKind::Solid)));
+            let height = if crouching {
+                settings.crouch_height
+            } else {
+                settings.height
+            };
+            collider.set_size(cgmath::vec3(settings.width, height, settings.width));
+            collider.set_offset(cgmath::vec3(0.0, height / 2.0, 0.0));
+            collider.set_step_height(settings.step_height);
+
+            let aabb = collider.get_aabb(position);
+            let swimming =
+                mode == MovementMode::Walking && intersects_kind(&world, &aabb, BlockKind::Fluid);
+            controller.set_state(crouching, swimming);
+
+            let mut direction = transform.forward() * input.get_axis("move_forward", "move_back")
+                + transform.right() * input.get_axis("move_right", "move_left");
+            if direction.magnitude2() > 1.0 {
+                direction = direction.normalize();
+            }
+            let sprinting = input.is_action_down("sprint");
+            let linear = &mut velocity.linear;
+
+            if mode != MovementMode::Walking {
+                let speed = if sprinting {
+                    settings.fly_sprint_speed
+                } else {
+                    settings.fly_speed
+                };
+                let vertical = Vector3::unit_y() * input.get_axis("jump", "crouch");
+                let target = (direction + vertical) * speed;
+                *linear = approach(*linear, target, settings.ground_acceleration * delta);
+            } else if swimming {
+                let vertical = if input.is_action_down("jump") {
+                    settings.swim_up_speed
+                } else if input.is_action_down("crouch") {
+                    -settings.swim_up_speed
+                } else {
+                    -settings.swim_sink_speed
+                };
+                let target = direction * settings.swim_speed + Vector3::unit_y() * vertical;
+                *linear = approach(*linear, target, settings.air_acceleration * delta);
+            } else {
+                let speed = if crouching {
+                    settings.crouch_speed
+                } else if sprinting {
+                    settings.sprint_speed
+                } else {
+                    settings.walk_speed
+                };
+                let grounded = collider.is_grounded();
+                let acceleration = if grounded {
+                    settings.ground_acceleration
+                } else {
+                    settings.air_acceleration
+                };
+                let horizontal = approach(
+                    cgmath::vec3(linear.x, 0.0, linear.z),
+                    direction * speed,
+                    acceleration * delta,
+                );
+
+                let fall = if gravity.is_some() {
+                    0.0
+                } else {
+                    settings.gravity * delta
+                };
+                let mut vertical = (linear.y - fall).max(-settings.terminal_speed);
+                if grounded && !crouching && input.is_action_down("jump") {
+                    vertical = settings.get_jump_speed();
+                }
+                *linear = cgmath::vec3(horizontal.x, vertical, horizontal.z);
+
+                // Crouching never walks off an edge
+                if crouching && grounded && delta > 0.0 {
+                    let motion = clamp_to_edges(&world, &aabb, *linear * delta);
+                    linear.x = motion.x / delta;
+                    linear.z = motion.z / delta;
+                }
+            }
+
+            eyes.push((entity, controller.get_eye_height(), controller.get_pitch()));
+        }
+
+        for (parent, _, transform) in (&parent, &main_camera, &mut transform).join() {
+            let eye = eyes
+                .iter()
+                .find(|(entity, _, _)| *entity == parent.get_entity());
+            if let Some((_, eye_height, pitch)) = eye {
+                transform.set_position(cgmath::vec3(0.0, *eye_height, 0.0));
+                transform.set_rotation(Quaternion::from_angle_x(Deg(*pitch)));
+            }
+        }
+    }
+}
+
+/// Moves `current` towards `target` by at most `max_change`
+fn approach(current: Vector3<f32>, target: Vector3<f32>, max_change: f32) -> Vector3<f32> {
+    let difference = target - current;
+    let distance = difference.magnitude();
+    if distance <= max_change {
+        target
+    } else {
+        current + difference * (max_change / distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        component::character::CharacterSettings,
+        resource::input::{actions::ActionMap, keys::Key, InputEvent},
+        voxel::AIR,
+        App, HeadlessApp,
+    };
+
+    const STONE: u16 = 1;
+    const WATER: u16 = 2;
+
+    /// Stone floor at y = -1 from -8 to 8 on X and Z, with a pool of water three blocks
+    /// deep above it from 4 to 6 on X and -1 to 1 on Z
+    fn world() -> VoxelWorld {
+        let mut world = VoxelWorld::new();
+        world.set_block_kind(WATER, BlockKind::Fluid);
+        for x in -8..=8 {
+            for z in -8..=8 {
+                world.set_block(cgmath::vec3(x, -1, z), STONE);
+            }
+        }
+        for x in 4..=6 {
+            for y in 0..=2 {
+                for z in -1..=1 {
+                    world.set_block(cgmath::vec3(x, y, z), WATER);
+                }
+            }
+        }
+        world
+    }
+
+    /// Character facing -Z at `position`, with a `MainCamera` child
+    fn spawn(position: Vector3<f32>, settings: CharacterSettings) -> (HeadlessApp, Entity, Entity) {
+        let actions = ActionMap::parse(
+            "move_forward = W
+            jump = Space
+            crouch = C
+            sprint = LShift
+            toggle_fly = F
+            toggle_noclip = N",
+        )
+        .unwrap();
+        let mut headless = App::new()
+            .with_vfs_args(vec![])
+            .with_actions(actions)
+            .with_system(CharacterControllerSys, "character_controller", &[])
+            .into_headless();
+        let ecs = headless.get_world_mut();
+        ecs.insert(world());
+        let character = ecs
+            .create_entity()
+            .with(Transform::from_position(position))
+            .with(Velocity::default())
+            .with(settings.get_collider())
+            .with(CharacterController::new(settings))
+            .build();
+        let camera = ecs
+            .create_entity()
+            .with(Transform::default())
+            .with(Parent::new(character))
+            .with(MainCamera)
+            .build();
+
+        (headless, character, camera)
+    }
+
+    fn press(headless: &mut HeadlessApp, key: Key) {
+        let mut input = headless.get_world_mut().write_resource::<UserInput>();
+        input.handle_event(&InputEvent::KeyPressed(key));
+    }
+
+    fn release(headless: &mut HeadlessApp, key: Key) {
+        let mut input = headless.get_world_mut().write_resource::<UserInput>();
+        input.handle_event(&InputEvent::KeyReleased(key));
+    }
+
+    /// Taps `key` for a single step
+    fn tap(headless: &mut HeadlessApp, key: Key) {
+        press(headless, key);
+        headless.step();
+        release(headless, key);
+    }
+
+    fn position(headless: &HeadlessApp, entity: Entity) -> Vector3<f32> {
+        let transforms = headless.get_world().read_storage::<Transform>();
+        transforms.get(entity).unwrap().get_position()
+    }
+
+    fn velocity(headless: &HeadlessApp, entity: Entity) -> Vector3<f32> {
+        let velocities = headless.get_world().read_storage::<Velocity>();
+        velocities.get(entity).unwrap().linear
+    }
+
+    fn controller(headless: &HeadlessApp, entity: Entity) -> CharacterController {
+        let controllers = headless.get_world().read_storage::<CharacterController>();
+        controllers.get(entity).unwrap().clone()
+    }
+
+    fn collider(headless: &HeadlessApp, entity: Entity) -> Collider {
+        let colliders = headless.get_world().read_storage::<Collider>();
+        *colliders.get(entity).unwrap()
+    }
+
+    fn assert_close(value: f32, expected: f32, tolerance: f32) {
+        assert!(
+            (value - expected).abs() < tolerance,
+            "expected {}, got {}",
+            expected,
+            value
+        );
+    }
+
+    #[test]
+    fn walks_forward_on_the_ground() {
+        let settings = CharacterSettings::default();
+        let (mut headless, character, _) = spawn(cgmath::vec3(0.5, 0.0, 0.5), settings.clone());
+
+        press(&mut headless, Key::W);
+        headless.run_steps(60);
+
+        let linear = velocity(&headless, character);
+        assert_close(linear.z, -settings.walk_speed, 1e-4);
+        assert_close(linear.x, 0.0, 1e-4);
+        let position = position(&headless, character);
+        assert_close(position.y, 0.0, 1e-3);
+        assert!(position.z < -3.5, "only walked to {:?}", position);
+        assert!(collider(&headless, character).is_grounded());
+    }
+
+    #[test]
+    fn sprinting_is_faster() {
+        let settings = CharacterSettings::default();
+        let (mut headless, character, _) = spawn(cgmath::vec3(0.5, 0.0, 0.5), settings.clone());
+
+        press(&mut headless, Key::W);
+        press(&mut headless, Key::LShift);
+        headless.run_steps(60);
+
+        assert_close(
+            velocity(&headless, character).z,
+            -settings.sprint_speed,
+            1e-4,
+        );
+    }
+
+    #[test]
+    fn jumps_to_the_jump_height_and_lands() {
+        let settings = CharacterSettings::default();
+        let (mut headless, character, _) = spawn(cgmath::vec3(0.5, 0.0, 0.5), settings.clone());
+        headless.run_steps(5);
+
+        tap(&mut headless, Key::Space);
+        assert_close(
+            velocity(&headless, character).y,
+            settings.get_jump_speed(),
+            1e-4,
+        );
+
+        let mut highest: f32 = 0.0;
+        for _ in 0..60 {
+            headless.step();
+            highest = highest.max(position(&headless, character).y);
+        }
+        assert_close(highest, settings.jump_height, 0.15);
+        assert_close(position(&headless, character).y, 0.0, 1e-3);
+        assert!(collider(&headless, character).is_grounded());
+    }
+
+    #[test]
+    fn stays_crouched_under_a_low_ceiling() {
+        // Too tall to stand under a block two above the floor
+        let settings = CharacterSettings {
+            height: 2.4,
+            eye_height: 2.2,
+            ..CharacterSettings::default()
+        };
+        let (mut headless, character, camera) =
+            spawn(cgmath::vec3(0.5, 0.0, 0.5), settings.clone());
+        let ceiling = cgmath::vec3(0, 2, 0);
+        headless
+            .get_world_mut()
+            .write_resource::<VoxelWorld>()
+            .set_block(ceiling, STONE);
+
+        tap(&mut headless, Key::C);
+        headless.step();
+        assert!(controller(&headless, character).is_crouching());
+        assert_close(
+            collider(&headless, character).get_size().y,
+            settings.crouch_height,
+            1e-6,
+        );
+        assert_close(
+            position(&headless, camera).y,
+            settings.crouch_eye_height,
+            1e-6,
+        );
+
+        headless
+            .get_world_mut()
+            .write_resource::<VoxelWorld>()
+            .set_block(ceiling, AIR);
+        headless.step();
+        assert!(!controller(&headless, character).is_crouching());
+        assert_close(
+            collider(&headless, character).get_size().y,
+            settings.height,
+            1e-6,
+        );
+        assert_close(position(&headless, camera).y, settings.eye_height, 1e-6);
+    }
+
+    #[test]
+    fn crouching_does_not_walk_off_edges() {
+        let (mut headless, character, _) =
+            spawn(cgmath::vec3(0.5, 0.0, -7.0), CharacterSettings::default());
+
+        press(&mut headless, Key::C);
+        press(&mut headless, Key::W);
+        headless.run_steps(120);
+
+        let position = position(&headless, character);
+        assert_close(position.y, 0.0, 1e-3);
+        assert!(position.z < -7.5, "only walked to {:?}", position);
+    }
+
+    #[test]
+    fn swims_in_fluids() {
+        let settings = CharacterSettings::default();
+        let (mut headless, character, _) = spawn(cgmath::vec3(5.5, 1.0, 0.5), settings.clone());
+
+        headless.run_steps(10);
+        assert!(controller(&headless, character).is_swimming());
+        assert_close(
+            velocity(&headless, character).y,
+            -settings.swim_sink_speed,
+            1e-4,
+        );
+
+        press(&mut headless, Key::Space);
+        headless.run_steps(30);
+        assert!(controller(&headless, character).is_swimming());
+        assert_close(
+            velocity(&headless, character).y,
+            settings.swim_up_speed,
+            1e-4,
+        );
+    }
+
+    #[test]
+    fn toggles_flying() {
+        let settings = CharacterSettings::default();
+        let (mut headless, character, _) = spawn(cgmath::vec3(0.5, 0.0, 0.5), settings.clone());
+
+        tap(&mut headless, Key::F);
+        assert_eq!(
+            controller(&headless, character).get_mode(),
+            MovementMode::Flying
+        );
+
+        press(&mut headless, Key::Space);
+        headless.run_steps(30);
+        assert_close(velocity(&headless, character).y, settings.fly_speed, 1e-4);
+        release(&mut headless, Key::Space);
+
+        // Hovers without gravity
+        headless.run_steps(30);
+        let hover = position(&headless, character).y;
+        assert!(hover > 2.0, "only rose to {}", hover);
+        headless.run_steps(30);
+        assert_close(position(&headless, character).y, hover, 1e-4);
+
+        tap(&mut headless, Key::F);
+        assert_eq!(
+            controller(&headless, character).get_mode(),
+            MovementMode::Walking
+        );
+        headless.run_steps(5);
+        assert!(position(&headless, character).y < hover);
+    }
+
+    #[test]
+    fn toggles_noclip() {
+        let (mut headless, character, _) =
+            spawn(cgmath::vec3(0.5, 0.0, 0.5), CharacterSettings::default());
+
+        tap(&mut headless, Key::N);
+        assert_eq!(
+            controller(&headless, character).get_mode(),
+            MovementMode::Noclip
+        );
+        assert!(!collider(&headless, character).is_enabled());
+
+        // Sinks through the floor
+        press(&mut headless, Key::C);
+        headless.run_steps(30);
+        assert!(position(&headless, character).y < -1.5);
+        release(&mut headless, Key::C);
+
+        tap(&mut headless, Key::N);
+        assert_eq!(
+            controller(&headless, character).get_mode(),
+            MovementMode::Walking
+        );
+        assert!(collider(&headless, character).is_enabled());
+    }
+}
@@ -0,0 +1,70 @@
+use cgmath::{prelude::*, Deg, Quaternion, Rad};
+use specs::prelude::*;
+
+use crate::{
+    component::{camera::MainCamera, fly_camera::FlyCameraController, transform::Transform},
+    resource::{
+        cursor::Cursor,
+        input::{keys::MouseButton, UserInput},
+        time::Time,
+    },
+};
+
+/// Moves and turns the `MainCamera` by its `FlyCameraController`. Runs in the fixed
+/// step, so movement scales with the fixed delta and the interpolation smooths it out
+/// between steps
+pub struct FlyCameraSys;
+impl<'a> System<'a> for FlyCameraSys {
+    type SystemData = (
+        ReadStorage<'a, MainCamera>,
+        ReadStorage<'a, FlyCameraController>,
+        WriteStorage<'a, Transform>,
+        Read<'a, UserInput>,
+        Write<'a, Cursor>,
+        ReadExpect<'a, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (main_camera, controller, mut transform, input, mut cursor, time): Self::SystemData,
+    ) {
+        let delta = time.get_fixed_delta();
+
+        for (_, controller, transform) in (&main_camera, &controller, &mut transform).join() {
+            if controller.grab_cursor {
+                if input.is_action_just_pressed("pause") {
+                    cursor.set_grabbed(false);
+                } else if input.is_mouse_button_just_pressed(MouseButton::Left) {
+                    cursor.set_grabbed(true);
+                }
+            }
+
+            // Angles are read back from the rotation, so other systems may turn the camera too
+            let forward = transform.forward();
+            let mut yaw = Deg::from(Rad((-forward.x).atan2(-forward.z))).0;
+            let mut pitch = Deg::from(Rad(forward.y.clamp(-1.0, 1.0).asin())).0;
+
+            if cursor.is_grabbed() || !controller.grab_cursor {
+                let mouse = input.get_mouse_delta();
+                yaw -= mouse.x * controller.mouse_sensitivity;
+                pitch -= mouse.y * controller.mouse_sensitivity;
+            }
+            yaw += input.get_axis("look_left", "look_right") * controller.look_speed * delta;
+            pitch += input.get_axis("look_up", "look_down") * controller.look_speed * delta;
+            pitch = pitch.clamp(-controller.max_pitch, controller.max_pitch);
+
+            transform.set_rotation(
+                Quaternion::from_angle_y(Deg(yaw)) * Quaternion::from_angle_x(Deg(pitch)),
+            );
+
+            let mut direction = transform.forward() * input.get_axis("move_forward", "move_back")
+                + transform.right() * input.get_axis("move_right", "move_left")
+                + cgmath::Vector3::unit_y() * input.get_axis("move_up", "move_down");
+            // Diagonals are not faster, slight stick tilts stay slow
+            if direction.magnitude2() > 1.0 {
+                direction = direction.normalize();
+            }
+            transform.translate_vector(direction * controller.move_speed * delta);
+        }
+    }
+}
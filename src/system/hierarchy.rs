@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use specs::prelude::*;
+
+use crate::{
+    component::transform::{Parent, PreviousTransform, Transform, WorldTransform},
+    resource::Interpolation,
+};
+
+/// Computes the `WorldTransform` of every entity with a `Transform` by applying the
+/// transforms of its parents. Entities with a `PreviousTransform` are interpolated
+/// by the `Interpolation` alpha first. Parents that are dead or have no `Transform` count as
+/// the world origin, entities in a parent cycle are placed relative to the world
+pub struct TransformHierarchySys;
+impl<'a> System<'a> for TransformHierarchySys {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Transform>,
+        ReadStorage<'a, PreviousTransform>,
+        ReadStorage<'a, Parent>,
+        Read<'a, Interpolation>,
+        WriteStorage<'a, WorldTransform>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, transforms, previous, parents, interpolation, mut world_transforms): Self::SystemData,
+    ) {
+        let alpha = interpolation.get_alpha();
+        let local_matrix = |entity: Entity| {
+            let transform = transforms.get(entity).unwrap();
+            match previous.get(entity) {
+                Some(previous) => transform
+                    .interpolate(previous.get_transform(), alpha)
+                    .get_transform_matrix(),
+                None => transform.get_transform_matrix(),
+            }
+        };
+
+        let mut matrices: HashMap<Entity, cgmath::Matrix4<f32>> = HashMap::new();
+        let mut chain = vec![];
+
+        for (entity, _) in (&entities, &transforms).join() {
+            // Walk up until an entity with a known matrix or the root, then resolve downwards
+            let mut current = entity;
+            let mut base = cgmath::Matrix4::from_scale(1.0);
+            loop {
+                if let Some(matrix) = matrices.get(&current) {
+                    base = *matrix;
+                    break;
+                }
+                // Every entity of a cycle is placed relative to the world, entities leading
+                // into it are placed on top of the entity they reach it at
+                if let Some(start) = chain.iter().position(|looped| *looped == current) {
+                    for looped in chain.drain(start..) {
+                        matrices.insert(looped, local_matrix(looped));
+                    }
+                    base = matrices[&current];
+                    break;
+                }
+                chain.push(current);
+
+                match parents.get(current) {
+                    Some(parent)
+                        if entities.is_alive(parent.get_entity())
+                            && transforms.contains(parent.get_entity()) =>
+                    {
+                        current = parent.get_entity();
+                    }
+                    _ => break,
+                }
+            }
+
+            for entity in chain.drain(..).rev() {
+                base = base * local_matrix(entity);
+                matrices.insert(entity, base);
+            }
+        }
+
+        for (entity, matrix) in matrices {
+            match world_transforms.get_mut(entity) {
+                Some(world_transform) => *world_transform = WorldTransform::new(matrix),
+                None => {
+                    let _ = world_transforms.insert(entity, WorldTransform::new(matrix));
+                }
+            }
+        }
+
+        let stale: Vec<Entity> = (&entities, !&transforms, &world_transforms)
+            .join()
+            .map(|(entity, _, _)| entity)
+            .collect();
+        for entity in stale {
+            world_transforms.remove(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{vec3, InnerSpace, Vector3};
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Transform>();
+        world.register::<PreviousTransform>();
+        world.register::<Parent>();
+        world.register::<WorldTransform>();
+        world.insert(Interpolation::new(1.0));
+        world
+    }
+
+    fn world_position(world: &World, entity: Entity) -> Vector3<f32> {
+        world
+            .read_storage::<WorldTransform>()
+            .get(entity)
+            .expect("entity has a world transform")
+            .get_position()
+    }
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        assert!(
+            (a - b).magnitude() < 1e-5,
+            "expected {:?} to equal {:?}",
+            a,
+            b
+        );
+    }
+
+    #[test]
+    fn children_are_placed_relative_to_their_parents() {
+        let mut world = world();
+        let mut turned = Transform::from_position(vec3(10.0, 0.0, 0.0));
+        turned.rotate_y(90.0);
+        turned.set_scale_uniform(2.0);
+        let root = world.create_entity().with(turned).build();
+        let child = world
+            .create_entity()
+            .with(Transform::from_position(vec3(0.0, 0.0, -1.0)))
+            .with(Parent::new(root))
+            .build();
+        let grandchild = world
+            .create_entity()
+            .with(Transform::from_position(vec3(1.0, 0.0, 0.0)))
+            .with(Parent::new(child))
+            .build();
+
+        TransformHierarchySys.run_now(&world);
+
+        assert_close(world_position(&world, root), vec3(10.0, 0.0, 0.0));
+        // Turned left and scaled by the root, -Z points to -X
+        assert_close(world_position(&world, child), vec3(8.0, 0.0, 0.0));
+        assert_close(world_position(&world, grandchild), vec3(8.0, 0.0, -2.0));
+    }
+
+    #[test]
+    fn dead_parents_count_as_the_origin() {
+        let mut world = world();
+        let parent = world
+            .create_entity()
+            .with(Transform::from_position(vec3(5.0, 0.0, 0.0)))
+            .build();
+        let child = world
+            .create_entity()
+            .with(Transform::from_position(vec3(0.0, 1.0, 0.0)))
+            .with(Parent::new(parent))
+            .build();
+
+        TransformHierarchySys.run_now(&world);
+        assert_close(world_position(&world, child), vec3(5.0, 1.0, 0.0));
+
+        world.delete_entity(parent).unwrap();
+        world.maintain();
+        TransformHierarchySys.run_now(&world);
+        assert_close(world_position(&world, child), vec3(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn entities_without_transform_lose_their_world_transform() {
+        let mut world = world();
+        let entity = world.create_entity().with(Transform::default()).build();
+
+        TransformHierarchySys.run_now(&world);
+        world.write_storage::<Transform>().remove(entity);
+        TransformHierarchySys.run_now(&world);
+
+        assert!(world.read_storage::<WorldTransform>().get(entity).is_none());
+    }
+
+    #[test]
+    fn parent_cycles_are_placed_relative_to_the_world() {
+        let mut world = world();
+        let a = world
+            .create_entity()
+            .with(Transform::from_position(vec3(1.0, 0.0, 0.0)))
+            .build();
+        let b = world
+            .create_entity()
+            .with(Transform::from_position(vec3(0.0, 2.0, 0.0)))
+            .with(Parent::new(a))
+            .build();
+        world
+            .write_storage::<Parent>()
+            .insert(a, Parent::new(b))
+            .unwrap();
+        let tail = world
+            .create_entity()
+            .with(Transform::from_position(vec3(0.0, 0.0, 3.0)))
+            .with(Parent::new(b))
+            .build();
+
+        TransformHierarchySys.run_now(&world);
+
+        assert_close(world_position(&world, a), vec3(1.0, 0.0, 0.0));
+        assert_close(world_position(&world, b), vec3(0.0, 2.0, 0.0));
+        assert_close(world_position(&world, tail), vec3(0.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn previous_transforms_are_interpolated_before_parenting() {
+        let mut world = world();
+        world.insert(Interpolation::new(0.5));
+        let parent = world
+            .create_entity()
+            .with(Transform::from_position(vec3(4.0, 0.0, 0.0)))
+            .with(PreviousTransform::new(Transform::default()))
+            .build();
+        let child = world
+            .create_entity()
+            .with(Transform::from_position(vec3(0.0, 1.0, 0.0)))
+            .with(Parent::new(parent))
+            .build();
+
+        TransformHierarchySys.run_now(&world);
+
+        assert_close(world_position(&world, parent), vec3(2.0, 0.0, 0.0));
+        assert_close(world_position(&world, child), vec3(2.0, 1.0, 0.0));
+    }
+}
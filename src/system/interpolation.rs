@@ -0,0 +1,25 @@
+use specs::prelude::*;
+
+use crate::component::transform::{PreviousTransform, Transform};
+
+/// Remembers every `Transform` before the fixed step changes it,
+/// has to run before any system of the step that moves entities
+pub struct StorePreviousTransformSys;
+impl<'a> System<'a> for StorePreviousTransformSys {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Transform>,
+        WriteStorage<'a, PreviousTransform>,
+    );
+
+    fn run(&mut self, (entities, transforms, mut previous): Self::SystemData) {
+        for (entity, transform) in (&entities, &transforms).join() {
+            match previous.get_mut(entity) {
+                Some(previous) => *previous = PreviousTransform::new(*transform),
+                None => {
+                    let _ = previous.insert(entity, PreviousTransform::new(*transform));
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use specs::prelude::*;
-
-pub mod demo;
+pub mod character;
+pub mod fly_camera;
+pub mod hierarchy;
+pub mod interpolation;
+pub mod physics;
 pub mod tasks;
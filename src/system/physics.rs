@@ -0,0 +1,78 @@
+use specs::prelude::*;
+
+use crate::{
+    component::{
+        physics::{Collider, Gravity, Velocity},
+        transform::{Parent, Transform},
+    },
+    physics::collision::{move_aabb, MoveResult},
+    resource::time::Time,
+    voxel::world::VoxelWorld,
+};
+
+/// Moves entities with a `Velocity` through the `VoxelWorld` resource. The `App` runs it
+/// at the end of every fixed step, after everything that changes velocities, so it
+/// must not be added with `with_system` again. Entities with a `Collider` stop at
+/// solid blocks, the velocity along a blocked axis drops to 0. Entities with a `Parent`
+/// are left alone, their position is not in world space
+pub struct PhysicsSys;
+impl<'a> System<'a> for PhysicsSys {
+    type SystemData = (
+        ReadStorage<'a, Parent>,
+        ReadStorage<'a, Gravity>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Collider>,
+        WriteStorage<'a, Transform>,
+        Read<'a, VoxelWorld>,
+        ReadExpect<'a, Time>,
+    );
+
+    fn run(
+        &mut self,
+        (parent, gravity, mut velocity, mut collider, mut transform, world, time): Self::SystemData,
+    ) {
+        let delta = time.get_fixed_delta();
+
+        for (velocity, collider, gravity, transform, ()) in (
+            &mut velocity,
+            (&mut collider).maybe(),
+            gravity.maybe(),
+            &mut transform,
+            !&parent,
+        )
+            .join()
+        {
+            if let Some(gravity) = gravity {
+                gravity.apply(&mut velocity.linear, delta);
+            }
+            let motion = velocity.linear * delta;
+
+            let collider = match collider {
+                Some(collider) => collider,
+                None => {
+                    transform.translate_vector(motion);
+                    continue;
+                }
+            };
+
+            let result = if collider.is_enabled() {
+                let aabb = collider.get_aabb(transform.get_position());
+                move_aabb(&world, aabb, motion, collider.get_step_height())
+            } else {
+                MoveResult {
+                    motion,
+                    blocked: [false; 3],
+                    grounded: false,
+                    step: 0.0,
+                }
+            };
+            transform.translate_vector(result.motion);
+            for axis in 0..3 {
+                if result.blocked[axis] {
+                    velocity.linear[axis] = 0.0;
+                }
+            }
+            collider.set_contacts(&result);
+        }
+    }
+}
@@ -1,52 +1,152 @@
 use crate::{
-    component::camera::Camera, component::camera::MainCamera, component::material::Material,
-    component::mesh::Mesh, component::transform::Transform, resource::tasks::MainCameraTask,
-    resource::tasks::RenderTask, resource::Task,
+    component::camera::{Camera, RenderLayers, DEFAULT_LAYER},
+    component::material::{Material, DEFAULT_TINT},
+    component::mesh::Mesh,
+    component::mesh::MeshManager,
+    component::render_target::RenderTargetManager,
+    component::transform::WorldTransform,
+    loader::assets::Handle,
+    loader::shaders::{shader_program, ShaderManager},
+    loader::textures::TextureManager,
+    resource::screen::Screen,
+    resource::tasks::CameraTask,
+    resource::tasks::{RenderTask, TextureBinding},
+    resource::Task,
+    vxl_gl::UniformValue,
 };
 use specs::prelude::*;
-pub struct SetMainCameraSys;
-impl<'a> System<'a> for SetMainCameraSys {
+
+/// Turns every `Camera` into a `CameraTask`. Cameras without a visible viewport
+/// or with a target that is not loaded are skipped
+pub struct SetCameraTasksSys;
+impl<'a> System<'a> for SetCameraTasksSys {
     type SystemData = (
-        ReadStorage<'a, MainCamera>,
-        ReadStorage<'a, Camera>,
-        ReadStorage<'a, Transform>,
+        WriteStorage<'a, Camera>,
+        ReadStorage<'a, WorldTransform>,
+        ReadExpect<'a, RenderTargetManager>,
+        Read<'a, Screen>,
         WriteExpect<'a, Task>,
     );
 
-    fn run(&mut self, (main_camera, camera, transform, mut task): Self::SystemData) {
-        let (_, camera, transform) = (&main_camera, &camera, &transform).join().next().unwrap();
+    fn run(&mut self, (mut camera, transform, targets, screen, mut task): Self::SystemData) {
+        let mut camera_tasks = vec![];
+        for (camera, transform) in (&mut camera, &transform).join() {
+            let (fbo_id, dimensions) = match camera.get_target() {
+                Some(target) => match targets.get(target) {
+                    Some(target) => (target.get_fbo_id(), target.get_dimensions()),
+                    None => continue,
+                },
+                None => (0, cgmath::vec2(screen.get_width(), screen.get_height())),
+            };
+            let viewport = camera.get_viewport().to_pixels(dimensions);
+            let (_, _, width, height) = viewport;
+            if width == 0 || height == 0 {
+                continue;
+            }
+            camera.set_aspect(width as f32 / height as f32);
+
+            let camera_task = CameraTask::new(
+                camera.get_projection_matrix(),
+                transform.get_view_matrix(),
+                fbo_id,
+                viewport,
+                camera.get_layer_mask(),
+                camera.get_clear(),
+            );
+            // Offscreen targets first, the screen may show their textures
+            camera_tasks.push(((fbo_id == 0, camera.get_order()), camera_task));
+        }
 
-        let projection_mat = camera.get_projection_matrix();
-        let view_mat = transform.get_view_matrix();
-        task.set_main_camera_task(MainCameraTask::new(projection_mat, view_mat));
+        camera_tasks.sort_by_key(|(key, _)| *key);
+        for (_, camera_task) in camera_tasks {
+            task.push_camera_task(camera_task);
+        }
     }
 }
 
+/// Turns every drawable entity into a `RenderTask`. The program and textures of its
+/// `Material` are resolved here, falling back to the error program and the missing
+/// texture while they are not loaded. Parameters the loaded program has no matching
+/// uniform for are printed once per program version and left out
 pub struct SetRenderTaskSys;
 impl<'a> System<'a> for SetRenderTaskSys {
     type SystemData = (
-        ReadStorage<'a, Material>,
-        ReadStorage<'a, Mesh>,
-        ReadStorage<'a, Transform>,
+        WriteStorage<'a, Material>,
+        ReadStorage<'a, Handle<Mesh>>,
+        ReadStorage<'a, WorldTransform>,
+        ReadStorage<'a, RenderLayers>,
+        ReadExpect<'a, MeshManager>,
+        ReadExpect<'a, ShaderManager>,
+        ReadExpect<'a, TextureManager>,
         WriteExpect<'a, Task>,
     );
 
-    fn run(&mut self, (material, mesh, transform, mut task): Self::SystemData) {
-        for (material, mesh, transform) in (&material, &mesh, &transform).join() {
-            let program_id = material.get_program_id();
-            let texture_id = material.get_texture_id();
+    fn run(
+        &mut self,
+        (mut material, mesh, transform, layers, meshes, shaders, textures, mut task): Self::SystemData,
+    ) {
+        for (material, mesh, transform, layers) in
+            (&mut material, &mesh, &transform, layers.maybe()).join()
+        {
+            let mesh = match meshes.get(mesh) {
+                Some(mesh) => mesh,
+                None => continue,
+            };
+            // The error program has none of the material's uniforms, it is not checked
+            if let Ok(program) = shaders.get_shader_program(material.get_program()) {
+                for err in material.check_program(program) {
+                    let name = shaders.get_name(material.get_program());
+                    println!("Material of program \"{}\": {}", name, err);
+                }
+            }
+            let program = shaders.get_shader_program_or_error(material.get_program());
+            let program_uniforms = program.get_uniforms();
+            let location = |name: &str| {
+                program_uniforms
+                    .get(name)
+                    .map_or(-1, |uniform| uniform.location)
+            };
+
+            let texture_bindings = material
+                .get_textures()
+                .iter()
+                .map(|slot| {
+                    let texture_id = textures.get_texture_or_missing(slot.get_texture()).get_id();
+                    TextureBinding::new(slot.get_unit(), texture_id, slot.get_sampler())
+                })
+                .collect();
             let vao_id = mesh.get_vao_id();
             let vertex_count = mesh.get_vertex_count();
             let attrib_arrays = mesh.get_attrib_arrays().to_owned();
-            let mat4f_uniforms = vec![("trans_mat", transform.get_transform_matrix())];
+            let mut uniforms: Vec<(i32, UniformValue)> = material
+                .get_parameters()
+                .iter()
+                .filter(|(name, value)| {
+                    shader_program::check_uniform(&program_uniforms, name, value).is_ok()
+                })
+                .map(|(name, value)| (location(name), *value))
+                .collect();
+            let tint = UniformValue::Color(DEFAULT_TINT);
+            if !material.get_parameters().contains_key("tint")
+                && shader_program::check_uniform(&program_uniforms, "tint", &tint).is_ok()
+            {
+                uniforms.push((location("tint"), tint));
+            }
+            uniforms.push((
+                location("trans_mat"),
+                UniformValue::Mat4(transform.get_matrix()),
+            ));
+            let camera_locations = (location("proj_mat"), location("view_mat"));
 
             task.push_render_task(RenderTask::new(
-                program_id,
+                program.get_id(),
                 vao_id,
                 vertex_count,
                 attrib_arrays,
-                mat4f_uniforms,
-                texture_id,
+                camera_locations,
+                uniforms,
+                texture_bindings,
+                layers.map_or(DEFAULT_LAYER, RenderLayers::get_mask),
             ));
         }
     }
@@ -1 +0,0 @@
-pub const KEY_W: u32 = 13;
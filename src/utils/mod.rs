@@ -1,9 +0,0 @@
-use std::ffi::CString;
-
-pub mod key_codes;
-
-pub fn create_whitespace_csting_with_len(len: usize) -> CString {
-    let mut buffer: Vec<u8> = Vec::with_capacity(len as usize + 1);
-    buffer.extend([b' '].iter().cycle().take(len as usize));
-    unsafe { CString::from_vec_unchecked(buffer) }
-}
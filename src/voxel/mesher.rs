@@ -0,0 +1,113 @@
+use cgmath::{Vector2, Vector3};
+
+use crate::loader::meshes::MeshData;
+
+/// Outward normal and corners of each cube face, counter clockwise seen from outside
+const FACES: [([i32; 3], [[f32; 3]; 4]); 6] = [
+    (
+        [1, 0, 0],
+        [
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [1.0, 1.0, 1.0],
+            [1.0, 0.0, 1.0],
+        ],
+    ),
+    (
+        [-1, 0, 0],
+        [
+            [0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0],
+            [0.0, 1.0, 1.0],
+            [0.0, 1.0, 0.0],
+        ],
+    ),
+    (
+        [0, 1, 0],
+        [
+            [0.0, 1.0, 0.0],
+            [0.0, 1.0, 1.0],
+            [1.0, 1.0, 1.0],
+            [1.0, 1.0, 0.0],
+        ],
+    ),
+    (
+        [0, -1, 0],
+        [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 0.0, 1.0],
+            [0.0, 0.0, 1.0],
+        ],
+    ),
+    (
+        [0, 0, 1],
+        [
+            [0.0, 0.0, 1.0],
+            [1.0, 0.0, 1.0],
+            [1.0, 1.0, 1.0],
+            [0.0, 1.0, 1.0],
+        ],
+    ),
+    (
+        [0, 0, -1],
+        [
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+        ],
+    ),
+];
+
+/// Builds a mesh of every voxel face that is not hidden by a neighbour.
+/// `voxel_at` returns the voxel at a position, 0 for empty, and is also asked about
+/// positions just outside of `size`. `uv_for` picks the uv of a voxel's faces
+pub fn build_mesh<F, U>(name: &str, size: Vector3<i32>, voxel_at: F, uv_for: U) -> MeshData
+where
+    F: Fn(Vector3<i32>) -> u16,
+    U: Fn(u16) -> Vector2<f32>,
+{
+    let mut data = MeshData::new(name);
+
+    for z in 0..size.z {
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let position = cgmath::vec3(x, y, z);
+                let voxel = voxel_at(position);
+                if voxel == 0 {
+                    continue;
+                }
+
+                let uv = uv_for(voxel);
+                for (normal, corners) in FACES.iter() {
+                    let normal = cgmath::vec3(normal[0], normal[1], normal[2]);
+                    if voxel_at(position + normal) != 0 {
+                        continue;
+                    }
+
+                    let first = data.positions.len() as u32;
+                    for corner in corners {
+                        data.positions.push(cgmath::vec3(
+                            x as f32 + corner[0],
+                            y as f32 + corner[1],
+                            z as f32 + corner[2],
+                        ));
+                        data.uvs.push(uv);
+                        data.normals.push(normal.cast().unwrap());
+                    }
+                    data.indices.extend_from_slice(&[
+                        first,
+                        first + 1,
+                        first + 2,
+                        first,
+                        first + 2,
+                        first + 3,
+                    ]);
+                }
+            }
+        }
+    }
+
+    data
+}
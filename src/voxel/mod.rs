@@ -0,0 +1,20 @@
+pub mod mesher;
+pub mod model;
+pub mod world;
+
+/// Block type stored in the voxel world, see `AIR`
+pub type BlockId = u16;
+
+/// Empty block
+pub const AIR: BlockId = 0;
+
+/// How a block type behaves for physics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockKind {
+    /// Nothing, like `AIR`
+    Empty,
+    /// Stops colliders
+    Solid,
+    /// Colliders pass through and swim in it
+    Fluid,
+}
@@ -0,0 +1,122 @@
+use cgmath::Vector3;
+
+use super::mesher;
+use crate::loader::meshes::MeshData;
+
+/// Width of the palette texture, one texel per color index
+pub const PALETTE_SIZE: u32 = 256;
+
+/// Dense grid of palette color indices, 0 is empty. Used for voxel props that are
+/// rendered as standalone meshes or stamped into the world
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxelModel {
+    size: Vector3<u32>,
+    voxels: Vec<u8>,
+    palette: Vec<[u8; 4]>,
+}
+
+impl VoxelModel {
+    /// Empty model, `None` if `size` holds more voxels than can be allocated
+    pub fn new(size: Vector3<u32>, mut palette: Vec<[u8; 4]>) -> Option<Self> {
+        let count = (size.x as usize)
+            .checked_mul(size.y as usize)?
+            .checked_mul(size.z as usize)?;
+        let mut voxels = vec![];
+        voxels.try_reserve_exact(count).ok()?;
+        voxels.resize(count, 0);
+
+        palette.resize(PALETTE_SIZE as usize, [0, 0, 0, 0]);
+        Some(VoxelModel {
+            size,
+            voxels,
+            palette,
+        })
+    }
+
+    fn index(&self, position: Vector3<u32>) -> Option<usize> {
+        if position.x < self.size.x && position.y < self.size.y && position.z < self.size.z {
+            let (width, height) = (self.size.x as usize, self.size.y as usize);
+            Some(
+                position.x as usize
+                    + position.y as usize * width
+                    + position.z as usize * width * height,
+            )
+        } else {
+            None
+        }
+    }
+
+    /// Color index at `position`, 0 for empty voxels and positions outside of the model
+    pub fn get(&self, position: Vector3<u32>) -> u8 {
+        self.index(position).map_or(0, |index| self.voxels[index])
+    }
+
+    pub fn set(&mut self, position: Vector3<u32>, color: u8) {
+        if let Some(index) = self.index(position) {
+            self.voxels[index] = color;
+        }
+    }
+
+    pub fn get_size(&self) -> Vector3<u32> {
+        self.size
+    }
+
+    pub fn get_palette(&self) -> &[[u8; 4]] {
+        &self.palette
+    }
+
+    pub fn get_voxel_count(&self) -> usize {
+        self.voxels.iter().filter(|color| **color != 0).count()
+    }
+
+    /// Solid voxels with their position and color index
+    pub fn voxels(&self) -> impl Iterator<Item = (Vector3<u32>, u8)> + '_ {
+        let (width, height) = (self.size.x as usize, self.size.y as usize);
+        self.voxels
+            .iter()
+            .enumerate()
+            .filter(|(_, color)| **color != 0)
+            .map(move |(index, color)| {
+                let position = cgmath::vec3(
+                    index % width,
+                    index / width % height,
+                    index / (width * height),
+                );
+                (position.cast().unwrap(), *color)
+            })
+    }
+
+    /// Meshes the model with one unit per voxel. Every face samples its color
+    /// from the palette texture, see `palette_texture_data`
+    pub fn build_mesh(&self, name: &str) -> MeshData {
+        let size = self
+            .size
+            .cast::<i32>()
+            .unwrap_or_else(|| cgmath::vec3(0, 0, 0));
+        mesher::build_mesh(
+            name,
+            size,
+            |position| {
+                position
+                    .cast::<u32>()
+                    .map_or(0, |position| self.get(position) as u16)
+            },
+            |color| palette_uv(color as u8),
+        )
+    }
+
+    /// Rgba8 pixels of a `PALETTE_SIZE` x 1 texture holding the palette
+    pub fn palette_texture_data(&self) -> (cgmath::Vector2<u32>, Vec<u8>) {
+        let data = self
+            .palette
+            .iter()
+            .flat_map(|color| color.iter().copied())
+            .collect();
+        (cgmath::vec2(PALETTE_SIZE, 1), data)
+    }
+}
+
+/// Center of the palette texel of `color`
+pub fn palette_uv(color: u8) -> cgmath::Vector2<f32> {
+    cgmath::vec2((color as f32 + 0.5) / PALETTE_SIZE as f32, 0.5)
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use cgmath::Vector3;
+
+use super::{model::VoxelModel, BlockId, BlockKind, AIR};
+
+/// Edge length of a chunk in blocks
+pub const CHUNK_SIZE: i32 = 16;
+
+/// Cube of `CHUNK_SIZE`³ blocks
+pub struct Chunk {
+    blocks: Vec<BlockId>,
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Chunk {
+            blocks: vec![AIR; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize],
+        }
+    }
+}
+
+impl Chunk {
+    fn index(local: Vector3<i32>) -> usize {
+        (local.x + local.y * CHUNK_SIZE + local.z * CHUNK_SIZE * CHUNK_SIZE) as usize
+    }
+
+    pub fn get_block(&self, local: Vector3<i32>) -> BlockId {
+        self.blocks[Chunk::index(local)]
+    }
+
+    pub fn set_block(&mut self, local: Vector3<i32>, block: BlockId) {
+        self.blocks[Chunk::index(local)] = block;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.iter().all(|block| *block == AIR)
+    }
+}
+
+/// Infinite block grid made of sparsely allocated chunks. Every block type is solid
+/// unless it is given another `BlockKind`, `AIR` is always empty
+#[derive(Default)]
+pub struct VoxelWorld {
+    chunks: HashMap<Vector3<i32>, Chunk>,
+    /// Kinds are plain values keyed by the id stored in every block, not named assets, so
+    /// they stay out of `AssetManager`
+    kinds: HashMap<BlockId, BlockKind>,
+}
+
+/// Splits a block position into its chunk position and the position inside the chunk
+pub fn split_position(position: Vector3<i32>) -> (Vector3<i32>, Vector3<i32>) {
+    let chunk = cgmath::vec3(
+        position.x.div_euclid(CHUNK_SIZE),
+        position.y.div_euclid(CHUNK_SIZE),
+        position.z.div_euclid(CHUNK_SIZE),
+    );
+    let local = cgmath::vec3(
+        position.x.rem_euclid(CHUNK_SIZE),
+        position.y.rem_euclid(CHUNK_SIZE),
+        position.z.rem_euclid(CHUNK_SIZE),
+    );
+    (chunk, local)
+}
+
+impl VoxelWorld {
+    pub fn new() -> Self {
+        VoxelWorld::default()
+    }
+
+    pub fn get_block(&self, position: Vector3<i32>) -> BlockId {
+        let (chunk, local) = split_position(position);
+        self.chunks
+            .get(&chunk)
+            .map_or(AIR, |chunk| chunk.get_block(local))
+    }
+
+    /// Sets a block, allocating its chunk if needed
+    pub fn set_block(&mut self, position: Vector3<i32>, block: BlockId) {
+        let (chunk, local) = split_position(position);
+        if block == AIR && !self.chunks.contains_key(&chunk) {
+            return;
+        }
+        self.chunks
+            .entry(chunk)
+            .or_default()
+            .set_block(local, block);
+    }
+
+    pub fn set_block_kind(&mut self, block: BlockId, kind: BlockKind) {
+        if block != AIR {
+            self.kinds.insert(block, kind);
+        }
+    }
+
+    pub fn get_block_kind(&self, block: BlockId) -> BlockKind {
+        if block == AIR {
+            return BlockKind::Empty;
+        }
+        self.kinds.get(&block).copied().unwrap_or(BlockKind::Solid)
+    }
+
+    /// Kind of the block at `position`
+    pub fn get_kind_at(&self, position: Vector3<i32>) -> BlockKind {
+        self.get_block_kind(self.get_block(position))
+    }
+
+    pub fn get_chunk(&self, chunk: Vector3<i32>) -> Option<&Chunk> {
+        self.chunks.get(&chunk)
+    }
+
+    pub fn get_chunk_positions(&self) -> Vec<Vector3<i32>> {
+        self.chunks.keys().copied().collect()
+    }
+
+    /// Copies every voxel of `model` into the world with its minimum corner at `origin`.
+    /// `block_for_color` maps palette colors to blocks, returning `AIR` skips the voxel
+    pub fn stamp<F>(&mut self, model: &VoxelModel, origin: Vector3<i32>, block_for_color: F)
+    where
+        F: Fn(u8) -> BlockId,
+    {
+        for (position, color) in model.voxels() {
+            let block = block_for_color(color);
+            if block != AIR {
+                self.set_block(origin + position.cast().unwrap(), block);
+            }
+        }
+    }
+}
@@ -1,15 +1,86 @@
 use std::ffi::{CStr, CString};
 
-use crate::utils::create_whitespace_csting_with_len;
 use cgmath::prelude::*;
-use cgmath::{vec3, Vector3};
+use cgmath::{vec3, Vector2, Vector3};
 use glutin::{self, PossiblyCurrent};
 
+#[allow(clippy::all)]
 pub mod gl {
     pub use self::Gles2 as Gl;
     include!(concat!(env!("OUT_DIR"), "/gl_bindings.rs"));
 }
 
+/// Value that can be uploaded into a shader uniform
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UniformValue {
+    Float(f32),
+    Vec2(cgmath::Vector2<f32>),
+    Vec3(cgmath::Vector3<f32>),
+    Vec4(cgmath::Vector4<f32>),
+    Int(i32),
+    Color(cgmath::Vector4<f32>),
+    Mat4(cgmath::Matrix4<f32>),
+    Sampler(i32),
+}
+
+impl UniformValue {
+    /// Checks whether the value can be uploaded into a uniform of the reflected `gl_type`
+    pub fn matches_gl_type(&self, gl_type: gl::types::GLenum) -> bool {
+        match self {
+            UniformValue::Float(_) => gl_type == gl::FLOAT,
+            UniformValue::Vec2(_) => gl_type == gl::FLOAT_VEC2,
+            UniformValue::Vec3(_) => gl_type == gl::FLOAT_VEC3,
+            UniformValue::Vec4(_) | UniformValue::Color(_) => gl_type == gl::FLOAT_VEC4,
+            UniformValue::Int(_) => gl_type == gl::INT || is_sampler_type(gl_type),
+            UniformValue::Mat4(_) => gl_type == gl::FLOAT_MAT4,
+            UniformValue::Sampler(_) => is_sampler_type(gl_type),
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            UniformValue::Float(_) => "float",
+            UniformValue::Vec2(_) => "vec2",
+            UniformValue::Vec3(_) => "vec3",
+            UniformValue::Vec4(_) => "vec4",
+            UniformValue::Int(_) => "int",
+            UniformValue::Color(_) => "color",
+            UniformValue::Mat4(_) => "mat4",
+            UniformValue::Sampler(_) => "sampler",
+        }
+    }
+}
+
+pub fn is_sampler_type(gl_type: gl::types::GLenum) -> bool {
+    matches!(
+        gl_type,
+        gl::SAMPLER_2D
+            | gl::SAMPLER_3D
+            | gl::SAMPLER_CUBE
+            | gl::SAMPLER_2D_SHADOW
+            | gl::SAMPLER_2D_ARRAY
+            | gl::SAMPLER_2D_ARRAY_SHADOW
+            | gl::SAMPLER_CUBE_SHADOW
+            | gl::INT_SAMPLER_2D
+            | gl::INT_SAMPLER_3D
+            | gl::INT_SAMPLER_CUBE
+            | gl::INT_SAMPLER_2D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D
+            | gl::UNSIGNED_INT_SAMPLER_3D
+            | gl::UNSIGNED_INT_SAMPLER_CUBE
+            | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY
+    )
+}
+
+/// Uniform as reported by the driver after the program was linked
+#[derive(Debug, Clone)]
+pub struct ActiveUniform {
+    pub name: String,
+    pub gl_type: gl::types::GLenum,
+    pub size: i32,
+    pub location: i32,
+}
+
 pub struct Gl {
     pub gl: gl::Gl,
     pub clear_color: Vector3<f32>,
@@ -43,9 +114,122 @@ impl Gl {
                 self.clear_color.z,
                 1.0,
             );
-            self.gl.Clear(gl::COLOR_BUFFER_BIT);
+            self.gl.Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+        }
+    }
+
+    pub fn enable_depth_test(&self) {
+        unsafe {
+            self.gl.Enable(gl::DEPTH_TEST);
+            self.gl.DepthFunc(gl::LESS);
+        }
+    }
+
+    /// Area of the framebuffer drawn to, in pixels from the bottom left
+    pub fn set_viewport(&self, x: i32, y: i32, width: i32, height: i32) {
+        unsafe {
+            self.gl.Viewport(x, y, width, height);
+        }
+    }
+
+    /// Limits clears and draws to the area, `None` turns the limit off
+    pub fn set_scissor(&self, area: Option<(i32, i32, i32, i32)>) {
+        unsafe {
+            match area {
+                Some((x, y, width, height)) => {
+                    self.gl.Enable(gl::SCISSOR_TEST);
+                    self.gl.Scissor(x, y, width, height);
+                }
+                None => self.gl.Disable(gl::SCISSOR_TEST),
+            }
+        }
+    }
+
+    /// Clears the depth and, if given, the color of the bound framebuffer
+    pub fn clear(&self, color: Option<Vector3<f32>>) {
+        unsafe {
+            match color {
+                Some(color) => {
+                    self.gl.ClearColor(color.x, color.y, color.z, 1.0);
+                    self.gl.Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
+                }
+                None => self.gl.Clear(gl::DEPTH_BUFFER_BIT),
+            }
+        }
+    }
+}
+
+/// Framebuffers
+impl Gl {
+    pub fn create_framebuffer(&self) -> gl::types::GLuint {
+        let mut fbo: gl::types::GLuint = 0;
+        unsafe { self.gl.GenFramebuffers(1, &mut fbo) };
+        fbo
+    }
+
+    pub fn drop_framebuffer(&self, fbo_id: gl::types::GLuint) {
+        unsafe {
+            self.gl.DeleteFramebuffers(1, &fbo_id);
+        }
+    }
+
+    /// 0 is the window's framebuffer
+    pub fn bind_framebuffer(&self, fbo_id: gl::types::GLuint) {
+        unsafe {
+            self.gl.BindFramebuffer(gl::FRAMEBUFFER, fbo_id);
+        }
+    }
+
+    pub fn unbind_framebuffer(&self) {
+        self.bind_framebuffer(0);
+    }
+
+    /// Attaches level 0 of a 2d texture as the color buffer of the bound framebuffer
+    pub fn attach_color_texture(&self, texture_id: gl::types::GLuint) {
+        unsafe {
+            self.gl.FramebufferTexture2D(
+                gl::FRAMEBUFFER,
+                gl::COLOR_ATTACHMENT0,
+                gl::TEXTURE_2D,
+                texture_id,
+                0,
+            );
+        }
+    }
+
+    /// Creates a 24 bit depth renderbuffer and attaches it to the bound framebuffer
+    pub fn attach_depth_renderbuffer(&self, dimensions: Vector2<u32>) -> gl::types::GLuint {
+        let mut rbo: gl::types::GLuint = 0;
+        unsafe {
+            self.gl.GenRenderbuffers(1, &mut rbo);
+            self.gl.BindRenderbuffer(gl::RENDERBUFFER, rbo);
+            self.gl.RenderbufferStorage(
+                gl::RENDERBUFFER,
+                gl::DEPTH_COMPONENT24,
+                dimensions.x as i32,
+                dimensions.y as i32,
+            );
+            self.gl.BindRenderbuffer(gl::RENDERBUFFER, 0);
+            self.gl.FramebufferRenderbuffer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                gl::RENDERBUFFER,
+                rbo,
+            );
+        }
+        rbo
+    }
+
+    pub fn drop_renderbuffer(&self, rbo_id: gl::types::GLuint) {
+        unsafe {
+            self.gl.DeleteRenderbuffers(1, &rbo_id);
         }
     }
+
+    /// `FRAMEBUFFER_COMPLETE` when the bound framebuffer can be drawn to
+    pub fn get_framebuffer_status(&self) -> gl::types::GLenum {
+        unsafe { self.gl.CheckFramebufferStatus(gl::FRAMEBUFFER) }
+    }
 }
 
 /// VAOs
@@ -56,6 +240,12 @@ impl Gl {
         vao
     }
 
+    pub fn drop_vao(&self, vao_id: gl::types::GLuint) {
+        unsafe {
+            self.gl.DeleteVertexArrays(1, &vao_id);
+        }
+    }
+
     pub fn unbind_vao(&self) {
         unsafe {
             self.gl.BindVertexArray(0);
@@ -68,7 +258,7 @@ impl Gl {
         }
     }
 
-    pub fn enable_vertex_attrib_arrays(&self, attribs: &Vec<gl::types::GLuint>) {
+    pub fn enable_vertex_attrib_arrays(&self, attribs: &[gl::types::GLuint]) {
         unsafe {
             attribs.iter().for_each(|attrib| {
                 let index = *attrib;
@@ -77,7 +267,7 @@ impl Gl {
         }
     }
 
-    pub fn disable_vertex_attrib_arrays(&self, attribs: &Vec<gl::types::GLuint>) {
+    pub fn disable_vertex_attrib_arrays(&self, attribs: &[gl::types::GLuint]) {
         unsafe {
             attribs
                 .iter()
@@ -88,6 +278,12 @@ impl Gl {
 
 /// VBOs
 impl Gl {
+    pub fn drop_vbo(&self, vbo_id: gl::types::GLuint) {
+        unsafe {
+            self.gl.DeleteBuffers(1, &vbo_id);
+        }
+    }
+
     pub fn create_vertex_vbo(&self, vertices: Vec<cgmath::Vector3<f32>>) -> gl::types::GLuint {
         let mut vbo: gl::types::GLuint = 0;
         unsafe { self.gl.GenBuffers(1, &mut vbo) };
@@ -170,6 +366,41 @@ impl Gl {
 
         vbo
     }
+
+    pub fn create_normals_vbo(&self, normals: Vec<cgmath::Vector3<f32>>) -> gl::types::GLuint {
+        let mut vbo: gl::types::GLuint = 0;
+
+        let mut nverts: Vec<f32> = Vec::with_capacity(normals.len() * 3);
+        normals.iter().for_each(|normal| {
+            nverts.push(normal.x);
+            nverts.push(normal.y);
+            nverts.push(normal.z);
+        });
+
+        unsafe { self.gl.GenBuffers(1, &mut vbo) };
+        unsafe {
+            self.gl.BindBuffer(gl::ARRAY_BUFFER, vbo);
+            self.gl.BufferData(
+                gl::ARRAY_BUFFER,
+                (nverts.len() * std::mem::size_of::<f32>()) as gl::types::GLsizeiptr,
+                nverts.as_ptr() as *const gl::types::GLvoid,
+                gl::STATIC_DRAW,
+            );
+            self.gl.EnableVertexAttribArray(2);
+            self.gl.VertexAttribPointer(
+                2,
+                3,
+                gl::FLOAT,
+                gl::FALSE,
+                (3 * std::mem::size_of::<f32>()) as gl::types::GLint,
+                std::ptr::null(),
+            );
+            self.gl.DisableVertexAttribArray(2);
+            self.gl.BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+
+        vbo
+    }
 }
 
 /// Shaders
@@ -178,7 +409,12 @@ impl Gl {
         unsafe { self.gl.CreateShader(shader_type) }
     }
 
-    pub fn compile_shader(&self, shader_id: gl::types::GLuint, shader_source: &CString) {
+    /// Compiles the shader, returning the driver info log on failure
+    pub fn compile_shader(
+        &self,
+        shader_id: gl::types::GLuint,
+        shader_source: &CString,
+    ) -> Result<(), String> {
         unsafe {
             self.gl
                 .ShaderSource(shader_id, 1, &shader_source.as_ptr(), std::ptr::null());
@@ -198,20 +434,22 @@ impl Gl {
                     .GetShaderiv(shader_id, gl::INFO_LOG_LENGTH, &mut len)
             };
 
-            let error: CString = create_whitespace_csting_with_len(len as usize);
-
+            let mut buffer: Vec<u8> = vec![0; len.max(1) as usize];
+            let mut written: gl::types::GLsizei = 0;
             unsafe {
                 self.gl.GetShaderInfoLog(
                     shader_id,
                     len,
-                    std::ptr::null_mut(),
-                    error.as_ptr() as *mut gl::types::GLchar,
+                    &mut written,
+                    buffer.as_mut_ptr() as *mut gl::types::GLchar,
                 )
             };
+            buffer.truncate(written as usize);
 
-            println!("Error: {:?}", error);
-            panic!(error);
+            return Err(String::from_utf8_lossy(&buffer).into_owned());
         }
+
+        Ok(())
     }
 
     pub fn drop_shader(&self, shader_id: gl::types::GLuint) {
@@ -231,7 +469,8 @@ impl Gl {
         unsafe { self.gl.AttachShader(program_id, shader_id) };
     }
 
-    pub fn link_program(&self, program_id: gl::types::GLuint) {
+    /// Links the program, returning the driver info log on failure
+    pub fn link_program(&self, program_id: gl::types::GLuint) -> Result<(), String> {
         unsafe { self.gl.LinkProgram(program_id) };
 
         let mut result: gl::types::GLint = 1;
@@ -246,19 +485,23 @@ impl Gl {
                 self.gl
                     .GetProgramiv(program_id, gl::INFO_LOG_LENGTH, &mut len)
             };
-            let error = create_whitespace_csting_with_len(len as usize);
 
+            let mut buffer: Vec<u8> = vec![0; len.max(1) as usize];
+            let mut written: gl::types::GLsizei = 0;
             unsafe {
                 self.gl.GetProgramInfoLog(
                     program_id,
                     len,
-                    std::ptr::null_mut(),
-                    error.as_ptr() as *mut gl::types::GLchar,
+                    &mut written,
+                    buffer.as_mut_ptr() as *mut gl::types::GLchar,
                 );
             }
+            buffer.truncate(written as usize);
 
-            panic!(error);
+            return Err(String::from_utf8_lossy(&buffer).into_owned());
         }
+
+        Ok(())
     }
 
     pub fn detach_shader(&self, program_id: gl::types::GLuint, shader_id: gl::types::GLuint) {
@@ -284,12 +527,61 @@ impl Gl {
     }
 
     pub fn get_uniform_location(&self, program_id: gl::types::GLuint, location_name: &str) -> i32 {
+        let name = match CString::new(location_name) {
+            Ok(name) => name,
+            Err(_) => return -1,
+        };
+
+        unsafe { self.gl.GetUniformLocation(program_id, name.as_ptr()) }
+    }
+
+    /// Queries every active uniform of a linked program
+    pub fn get_active_uniforms(&self, program_id: gl::types::GLuint) -> Vec<ActiveUniform> {
+        let mut count: gl::types::GLint = 0;
+        let mut max_len: gl::types::GLint = 0;
         unsafe {
-            self.gl.GetUniformLocation(
-                program_id,
-                location_name.to_owned().to_string().as_ptr() as *const i8,
-            )
+            self.gl
+                .GetProgramiv(program_id, gl::ACTIVE_UNIFORMS, &mut count);
+            self.gl
+                .GetProgramiv(program_id, gl::ACTIVE_UNIFORM_MAX_LENGTH, &mut max_len);
+        }
+
+        let mut uniforms = Vec::with_capacity(count as usize);
+        for index in 0..count as gl::types::GLuint {
+            let mut buffer: Vec<u8> = vec![0; max_len.max(1) as usize];
+            let mut len: gl::types::GLsizei = 0;
+            let mut size: gl::types::GLint = 0;
+            let mut gl_type: gl::types::GLenum = 0;
+
+            unsafe {
+                self.gl.GetActiveUniform(
+                    program_id,
+                    index,
+                    max_len,
+                    &mut len,
+                    &mut size,
+                    &mut gl_type,
+                    buffer.as_mut_ptr() as *mut gl::types::GLchar,
+                );
+            }
+
+            buffer.truncate(len as usize);
+            let mut name = String::from_utf8_lossy(&buffer).into_owned();
+            // Arrays are reported as `name[0]`, but are addressed by their bare name
+            if name.ends_with("[0]") {
+                name.truncate(name.len() - 3);
+            }
+            let location = self.get_uniform_location(program_id, &name);
+
+            uniforms.push(ActiveUniform {
+                name,
+                gl_type,
+                size,
+                location,
+            });
         }
+
+        uniforms
     }
 
     pub fn add_uniform_matrix4f(&self, location: i32, matrix: cgmath::Matrix4<f32>) {
@@ -298,6 +590,52 @@ impl Gl {
                 .UniformMatrix4fv(location, 1, gl::FALSE, matrix.as_ptr());
         }
     }
+
+    pub fn add_uniform_float(&self, location: i32, value: f32) {
+        unsafe {
+            self.gl.Uniform1f(location, value);
+        }
+    }
+
+    pub fn add_uniform_vec2f(&self, location: i32, value: cgmath::Vector2<f32>) {
+        unsafe {
+            self.gl.Uniform2f(location, value.x, value.y);
+        }
+    }
+
+    pub fn add_uniform_vec3f(&self, location: i32, value: cgmath::Vector3<f32>) {
+        unsafe {
+            self.gl.Uniform3f(location, value.x, value.y, value.z);
+        }
+    }
+
+    pub fn add_uniform_vec4f(&self, location: i32, value: cgmath::Vector4<f32>) {
+        unsafe {
+            self.gl
+                .Uniform4f(location, value.x, value.y, value.z, value.w);
+        }
+    }
+
+    pub fn add_uniform_int(&self, location: i32, value: i32) {
+        unsafe {
+            self.gl.Uniform1i(location, value);
+        }
+    }
+
+    pub fn add_uniform(&self, location: i32, value: &UniformValue) {
+        match *value {
+            UniformValue::Float(value) => self.add_uniform_float(location, value),
+            UniformValue::Vec2(value) => self.add_uniform_vec2f(location, value),
+            UniformValue::Vec3(value) => self.add_uniform_vec3f(location, value),
+            UniformValue::Vec4(value) | UniformValue::Color(value) => {
+                self.add_uniform_vec4f(location, value)
+            }
+            UniformValue::Int(value) | UniformValue::Sampler(value) => {
+                self.add_uniform_int(location, value)
+            }
+            UniformValue::Mat4(value) => self.add_uniform_matrix4f(location, value),
+        }
+    }
 }
 
 impl Gl {
@@ -320,6 +658,12 @@ impl Gl {
         tex_id
     }
 
+    pub fn drop_texture(&self, texture_id: gl::types::GLuint) {
+        unsafe {
+            self.gl.DeleteTextures(1, &texture_id);
+        }
+    }
+
     pub fn bind_texture(&self, texture_id: gl::types::GLuint) {
         unsafe {
             self.gl.BindTexture(gl::TEXTURE_2D, texture_id);
@@ -338,30 +682,142 @@ impl Gl {
         }
     }
 
-    pub fn set_texture_data(&self, dimensions: cgmath::Vector2<u32>, texture_data: Vec<u8>) {
+    pub fn set_texture_data(
+        &self,
+        level: i32,
+        internal_format: gl::types::GLenum,
+        dimensions: cgmath::Vector2<u32>,
+        texture_data: &[u8],
+    ) {
         unsafe {
             self.gl.TexImage2D(
                 gl::TEXTURE_2D,
-                0,
-                gl::RGBA as i32,
+                level,
+                internal_format as i32,
                 dimensions.x as i32,
                 dimensions.y as i32,
                 0,
                 gl::RGBA,
                 gl::UNSIGNED_BYTE,
-                &texture_data[0] as *const u8 as *const std::ffi::c_void,
+                texture_data.as_ptr() as *const std::ffi::c_void,
             )
         }
     }
 
-    pub fn set_active_texture(&self) {
+    pub fn set_texture_parameter(&self, parameter: gl::types::GLenum, value: gl::types::GLenum) {
         unsafe {
-            self.gl.ActiveTexture(gl::TEXTURE0);
+            self.gl
+                .TexParameteri(gl::TEXTURE_2D, parameter, value as gl::types::GLint);
+        }
+    }
+
+    pub fn set_texture_parameter_f(&self, parameter: gl::types::GLenum, value: f32) {
+        unsafe {
+            self.gl.TexParameterf(gl::TEXTURE_2D, parameter, value);
+        }
+    }
+
+    pub fn set_active_texture(&self, unit: u32) {
+        unsafe {
+            self.gl.ActiveTexture(gl::TEXTURE0 + unit);
         }
     }
 }
 
+/// `GL_EXT_texture_filter_anisotropic`, core only since 4.6 so it is missing from the bindings
+const TEXTURE_MAX_ANISOTROPY: gl::types::GLenum = 0x84FE;
+const MAX_TEXTURE_MAX_ANISOTROPY: gl::types::GLenum = 0x84FF;
+
+/// Samplers
 impl Gl {
+    pub fn create_sampler(&self) -> gl::types::GLuint {
+        let mut sampler_id: gl::types::GLuint = 0;
+        unsafe { self.gl.GenSamplers(1, &mut sampler_id) };
+        sampler_id
+    }
+
+    pub fn set_sampler_parameter(
+        &self,
+        sampler_id: gl::types::GLuint,
+        parameter: gl::types::GLenum,
+        value: gl::types::GLenum,
+    ) {
+        unsafe {
+            self.gl
+                .SamplerParameteri(sampler_id, parameter, value as gl::types::GLint);
+        }
+    }
+
+    pub fn set_sampler_anisotropy(&self, sampler_id: gl::types::GLuint, anisotropy: f32) {
+        unsafe {
+            self.gl
+                .SamplerParameterf(sampler_id, TEXTURE_MAX_ANISOTROPY, anisotropy);
+        }
+    }
+
+    pub fn bind_sampler(&self, unit: u32, sampler_id: gl::types::GLuint) {
+        unsafe {
+            self.gl.BindSampler(unit, sampler_id);
+        }
+    }
+
+    pub fn unbind_sampler(&self, unit: u32) {
+        unsafe {
+            self.gl.BindSampler(unit, 0);
+        }
+    }
+
+    pub fn drop_sampler(&self, sampler_id: gl::types::GLuint) {
+        unsafe {
+            self.gl.DeleteSamplers(1, &sampler_id);
+        }
+    }
+
+    pub fn has_extension(&self, name: &str) -> bool {
+        let mut count: gl::types::GLint = 0;
+        unsafe { self.gl.GetIntegerv(gl::NUM_EXTENSIONS, &mut count) };
+
+        (0..count as gl::types::GLuint).any(|index| unsafe {
+            let ptr = self.gl.GetStringi(gl::EXTENSIONS, index);
+            !ptr.is_null() && CStr::from_ptr(ptr as *const _).to_bytes() == name.as_bytes()
+        })
+    }
+
+    /// Highest anisotropy level the driver accepts, `None` if anisotropic filtering is unsupported
+    pub fn get_max_anisotropy(&self) -> Option<f32> {
+        if !self.has_extension("GL_EXT_texture_filter_anisotropic")
+            && !self.has_extension("GL_ARB_texture_filter_anisotropic")
+        {
+            return None;
+        }
+
+        let mut max: gl::types::GLfloat = 1.0;
+        unsafe { self.gl.GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut max) };
+        Some(max)
+    }
+}
+
+impl Gl {
+    pub fn get_error(&self) -> Option<gl::types::GLenum> {
+        let errno = unsafe { self.gl.GetError() };
+        if errno != gl::NO_ERROR {
+            Some(errno)
+        } else {
+            None
+        }
+    }
+
+    /// Drops errors left behind by earlier calls, so the next `get_error` only
+    /// reports what happened after this. Stops after a few rounds in case the
+    /// context is gone and the driver keeps reporting it
+    pub fn clear_errors(&self) {
+        for _ in 0..16 {
+            if self.get_error().is_none() {
+                break;
+            }
+        }
+    }
+
     pub fn print_error(&self) {
         unsafe {
             let errno = self.gl.GetError();